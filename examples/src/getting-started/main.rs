@@ -18,7 +18,7 @@ fn main() {
 
     let order_created = order_created::create();
     let configuration = in_memory::configuration(TOPIC, ".*", false);
-    in_memory::setup(configuration);
+    in_memory::setup(configuration).unwrap();
 
     let handler_topic_channel = in_memory::message_channel(TOPIC, "Order");
     let order_created_handler = order_created::handler();
@@ -22,7 +22,7 @@ fn main() {
     let order_canceled = order_canceled::create();
 
     let configuration = in_memory::configuration(TOPIC, ".*", false);
-    in_memory::setup(configuration);
+    in_memory::setup(configuration).unwrap();
 
     let handler_topic_channel = in_memory::message_channel(TOPIC, "Orders");
     let order_created_handler = order_created::handler();
@@ -34,17 +34,17 @@ fn main() {
 
     in_memory::emit(&order_created);
     in_memory::emit(&order_canceled);
-    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "Accounts" });
-    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "*" });
-    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "Orders" });
-    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: QUEUE, name: "Orders" });
+    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "Accounts".into() });
+    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "*".into() });
+    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: "Orders".into() });
+    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: QUEUE, name: "Orders".into() });
 
     let order_created_handler = order_created::handler();
     in_memory::unregister(order_created_handler);
     let order_created_handler = order_created::handler();
     in_memory::unregister(order_created_handler);
 
-    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: QUEUE, name: "Orders" });
+    in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: QUEUE, name: "Orders".into() });
 
     println!();
 }
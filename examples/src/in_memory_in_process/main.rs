@@ -0,0 +1,82 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use eventure::{in_memory, model};
+use eventure::in_memory::ChannelType::TOPIC;
+use examples::shared::order_created;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Main function
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+const EMITS: usize = 100_000;
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+fn main() {
+    println!();
+    init_logger();
+
+    // async mode: emitted events are serialized, queued and deserialized by the worker
+    in_memory::setup(in_memory::configuration(TOPIC, "Orders", true)).unwrap();
+    in_memory::register(in_memory::message_channel(TOPIC, "Orders"), CountingEventHandler);
+
+    let serialized = emit_repeatedly(in_memory::emit);
+    let in_process = emit_repeatedly(in_memory::emit_in_process);
+    in_memory::shutdown();
+
+    println!("{} emits, serialized and queued to the worker: {:?}", EMITS, serialized);
+    println!("{} emits, dispatched in process:             {:?}", EMITS, in_process);
+    println!("speedup: {:.2}x", serialized.as_secs_f64() / in_process.as_secs_f64());
+    println!();
+}
+
+/// Emits the event repeatedly, returning the time until all the events are handled.
+fn emit_repeatedly(emit: impl Fn(&dyn model::Event)) -> Duration {
+    let order_created = order_created::create();
+    HANDLED.store(0, Ordering::SeqCst);
+    let started = Instant::now();
+    for _ in 0..EMITS {
+        emit(&order_created);
+    }
+    while HANDLED.load(Ordering::SeqCst) < EMITS {
+        thread::yield_now();
+    }
+    started.elapsed()
+}
+
+fn init_logger() {
+    SimpleLogger::new()
+        .with_colors(false)
+        .with_level(LevelFilter::Warn)
+        .init().unwrap();
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct CountingEventHandler;
+
+impl Display for CountingEventHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", "CountingEventHandler")
+    }
+}
+
+impl model::EventHandler for CountingEventHandler {
+    fn handle(&self, _event: &(dyn model::Event + '_)) {
+        HANDLED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn id(&self) -> String {
+        String::from("CountingEventHandler")
+    }
+}
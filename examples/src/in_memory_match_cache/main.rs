@@ -0,0 +1,63 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::time::{Duration, Instant};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use eventure::in_memory;
+use eventure::in_memory::MessageChannel;
+use eventure::in_memory::ChannelType::TOPIC;
+use examples::shared::order_created;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Main function
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+const HANDLERS: usize = 50;
+const EMITS: usize = 20_000;
+const CHANNEL: &str = "Orders-fulfillment-priority-eu-central-20240501";
+
+fn main() {
+    println!();
+    init_logger();
+
+    // plain patterns, cheap to evaluate anyway
+    compare("plain", |index| format!("^Payments-{}$", index));
+    // costly patterns, worth caching
+    compare("costly", |index| format!(r"(?i)\b(accounts|payments|invoices|shipments)[\w-]*-(eu|us|ap)-\d{{{}}}\b", index + 1));
+}
+
+fn compare(group: &str, pattern: impl Fn(usize) -> String) {
+    for index in 0..HANDLERS {
+        in_memory::register_in_group(group, in_memory::message_channel(TOPIC, pattern(index)), order_created::handler());
+    }
+    in_memory::register_in_group(group, in_memory::message_channel(TOPIC, "^Orders-"), order_created::handler());
+
+    in_memory::set_match_cache_capacity(0);
+    let uncached = emit_repeatedly();
+    in_memory::set_match_cache_capacity(1024);
+    let cached = emit_repeatedly();
+    in_memory::unregister_group(group);
+
+    println!("{} emits to {} handlers with {} patterns, without match cache: {:?}", EMITS, HANDLERS + 1, group, uncached);
+    println!("{} emits to {} handlers with {} patterns, with match cache:    {:?}", EMITS, HANDLERS + 1, group, cached);
+    println!("speedup: {:.2}x", uncached.as_secs_f64() / cached.as_secs_f64());
+    println!();
+}
+
+fn emit_repeatedly() -> Duration {
+    let order_created = order_created::create();
+    let started = Instant::now();
+    for _ in 0..EMITS {
+        in_memory::emit_to_channel(&order_created, MessageChannel { channel_type: TOPIC, name: CHANNEL.into() });
+    }
+    started.elapsed()
+}
+
+fn init_logger() {
+    SimpleLogger::new()
+        .with_colors(false)
+        .with_level(LevelFilter::Warn)
+        .init().unwrap();
+}
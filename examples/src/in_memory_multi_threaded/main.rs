@@ -18,7 +18,7 @@ fn main() {
     init_logger();
 
     let configuration = in_memory::configuration(TOPIC, ".*", false);
-    in_memory::setup(configuration);
+    in_memory::setup(configuration).unwrap();
 
     let order_created_handler = order_created::handler();
     let handler_topic_channel = in_memory::message_channel(TOPIC, "Order");
@@ -24,7 +24,7 @@ fn main() {
 
     let message_channel = kafka::message_channel("orders", 0, "consumer_group");
     let order_created_handler = order_created::handler();
-    kafka::register(message_channel, order_created_handler);
+    kafka::register(message_channel, order_created_handler).unwrap();
 
     let order_created = order_created::create();
     kafka::emit(&order_created);
@@ -24,15 +24,15 @@ fn main() {
 
     let message_channel = kafka::message_channel("orders", 0, "consumer_group1");
     let order_created_handler = order_created::handler();
-    kafka::register(message_channel, order_created_handler);
+    kafka::register(message_channel, order_created_handler).unwrap();
 
     let message_channel = kafka::message_channel("orders", 0, "consumer_group2");
     let order_created_handler = order_created::handler();
-    kafka::register(message_channel, order_created_handler);
+    kafka::register(message_channel, order_created_handler).unwrap();
 
     let message_channel = kafka::message_channel("orders", 0, "consumer_group3");
     let order_created_handler = order_created::handler();
-    kafka::register(message_channel, order_created_handler);
+    kafka::register(message_channel, order_created_handler).unwrap();
 
     let order_created = order_created::create();
     kafka::emit(&order_created);
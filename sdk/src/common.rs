@@ -1,3 +1,26 @@
+//! Facilities shared by the message broker implementations.
+
+mod channel_spec;
+mod dedup;
+mod middleware;
+mod redaction;
 mod utils;
 
-pub use self::utils::format_target;
+pub use self::channel_spec::ChannelSpec;
+pub use self::channel_spec::ChannelConversionError;
+pub use self::channel_spec::channel_spec;
+pub use self::dedup::DedupStore;
+pub use self::dedup::InMemoryDedupStore;
+#[cfg(feature = "redis")]
+pub use self::dedup::RedisDedupStore;
+pub use self::middleware::Handler;
+pub use self::middleware::Middleware;
+pub use self::middleware::MiddlewareStack;
+pub use self::middleware::set_middleware;
+pub use self::redaction::set_log_redactor;
+pub(crate) use self::redaction::redacted;
+pub(crate) use self::redaction::redacted_payload;
+pub(crate) use self::middleware::with_middleware;
+pub(crate) use self::middleware::has_middleware;
+pub(crate) use self::middleware::catch_panic;
+pub(crate) use self::utils::format_target;
@@ -0,0 +1,142 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::{iggy, in_memory, kafka};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Logical message channel, translated to the message channel of each backend (so that channels don't have to be
+/// rewritten when porting code between backends):
+/// - In-Memory (`From`): `name` becomes the channel name; channel is a `QUEUE` if `group` is set (the group members
+///   share the events), a `TOPIC` otherwise. `partition` is ignored.
+/// - Kafka (`TryFrom`): `name` becomes the topic, `group` the group id (`kafka::default_group_id` if not set). Fails
+///   if `partition` exceeds `u16::MAX`.
+/// - Iggy (`TryFrom`): `name` holds numeric stream and topic ids, as `<stream_id>/<topic_id>`. Fails otherwise.
+///
+/// # Examples
+/// ```
+/// use eventure::{common, iggy, in_memory, kafka};
+///
+/// let in_memory_channel = in_memory::MessageChannel::from(common::channel_spec("Orders", 0, None));
+/// assert_eq!(in_memory_channel.channel_type, in_memory::ChannelType::TOPIC);
+/// assert_eq!(in_memory_channel.name, "Orders");
+///
+/// let in_memory_channel = in_memory::MessageChannel::from(common::channel_spec("Orders", 0, Some("billing")));
+/// assert_eq!(in_memory_channel.channel_type, in_memory::ChannelType::QUEUE);
+///
+/// let kafka_channel = kafka::MessageChannel::try_from(common::channel_spec("orders", 3, Some("billing"))).unwrap();
+/// assert_eq!((kafka_channel.topic, kafka_channel.partition), ("orders", 3));
+/// assert_eq!(kafka_channel.group_id, "billing");
+///
+/// let kafka_channel = kafka::MessageChannel::try_from(common::channel_spec("orders", 0, None)).unwrap();
+/// assert_eq!(kafka_channel.group_id, "default");
+///
+/// let iggy_channel = iggy::MessageChannel::try_from(common::channel_spec("1/2", 3, None)).unwrap();
+/// assert_eq!((iggy_channel.stream_id, iggy_channel.topic_id, iggy_channel.partition_id), (1, 2, 3));
+/// ```
+///
+/// Channels which can't be represented by the backend are rejected:
+/// ```
+/// use eventure::{common, iggy, kafka};
+/// use eventure::common::ChannelConversionError;
+///
+/// assert_eq!(kafka::MessageChannel::try_from(common::channel_spec("orders", 70_000, None)).err(),
+///            Some(ChannelConversionError::PartitionOutOfRange(70_000)));
+/// assert_eq!(iggy::MessageChannel::try_from(common::channel_spec("orders", 0, None)).err(),
+///            Some(ChannelConversionError::InvalidName("orders")));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelSpec {
+    pub name: &'static str,
+    pub partition: u32,
+    pub group: Option<&'static str>,
+}
+
+/// Error of translating `ChannelSpec` to the message channel of a backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelConversionError {
+    /// Partition isn't representable by the backend.
+    PartitionOutOfRange(u32),
+    /// Name doesn't follow the backend's naming rules.
+    InvalidName(&'static str),
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Creates logical message channel.
+///
+/// # Examples
+/// ```
+/// use eventure::common;
+///
+/// let channel_spec = common::channel_spec("orders", 0, Some("billing"));
+/// assert_eq!(channel_spec.group, Some("billing"));
+/// ```
+pub fn channel_spec(name: &'static str, partition: u32, group: Option<&'static str>) -> ChannelSpec {
+    ChannelSpec {
+        name,
+        partition,
+        group,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl From<ChannelSpec> for in_memory::MessageChannel {
+    fn from(channel_spec: ChannelSpec) -> Self {
+        let channel_type = match channel_spec.group {
+            Some(_) => in_memory::ChannelType::QUEUE,
+            None => in_memory::ChannelType::TOPIC,
+        };
+        in_memory::message_channel(channel_type, channel_spec.name)
+    }
+}
+
+impl TryFrom<ChannelSpec> for kafka::MessageChannel {
+    type Error = ChannelConversionError;
+
+    fn try_from(channel_spec: ChannelSpec) -> Result<Self, Self::Error> {
+        let partition = u16::try_from(channel_spec.partition)
+            .map_err(|_| ChannelConversionError::PartitionOutOfRange(channel_spec.partition))?;
+        Ok(kafka::message_channel(channel_spec.name, partition, channel_spec.group.map(String::from).unwrap_or_else(kafka::default_group_id)))
+    }
+}
+
+impl TryFrom<ChannelSpec> for iggy::MessageChannel {
+    type Error = ChannelConversionError;
+
+    fn try_from(channel_spec: ChannelSpec) -> Result<Self, Self::Error> {
+        let ids = channel_spec.name.split_once('/')
+            .and_then(|(stream_id, topic_id)| Some((stream_id.parse().ok()?, topic_id.parse().ok()?)));
+        match ids {
+            Some((stream_id, topic_id)) => Ok(iggy::message_channel(stream_id, topic_id, channel_spec.partition)),
+            None => Err(ChannelConversionError::InvalidName(channel_spec.name)),
+        }
+    }
+}
+
+impl Display for ChannelSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:?},{:?},{:?}]", self.name, self.partition, self.group)
+    }
+}
+
+impl Display for ChannelConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelConversionError::PartitionOutOfRange(partition) => write!(f, "partition {} out of range", partition),
+            ChannelConversionError::InvalidName(name) => write!(f, "invalid channel name {:?}", name),
+        }
+    }
+}
+
+impl Error for ChannelConversionError {}
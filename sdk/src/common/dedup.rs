@@ -0,0 +1,165 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+#[cfg(feature = "redis")]
+use std::time::Duration;
+#[cfg(feature = "redis")]
+use log::error;
+#[cfg(feature = "redis")]
+use crate::common;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Bounded in-process dedup store, forgetting the oldest ids once the capacity is reached.
+///
+/// # Examples
+/// ```
+/// use eventure::common::{DedupStore, InMemoryDedupStore};
+///
+/// let store = InMemoryDedupStore::new(2);
+/// assert!(store.insert("1"));
+/// assert!(!store.insert("1"));
+///
+/// store.insert("2");
+/// store.insert("3");
+/// // the oldest id was forgotten
+/// assert!(!store.contains("1"));
+/// assert!(store.contains("3"));
+/// ```
+pub struct InMemoryDedupStore {
+    capacity: usize,
+    seen: Mutex<SeenIds>,
+}
+
+/// Dedup store kept in Redis (requires the `redis` feature), so that processes sharing the Redis instance process an
+/// event only once. Ids are stored under the key prefix and expire after the time to live. When Redis can't be
+/// reached, the error is logged and events are treated as not processed yet (processed rather than lost).
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use eventure::common::{DedupStore, RedisDedupStore};
+/// use eventure::{in_memory, kafka};
+///
+/// let store = Arc::new(RedisDedupStore::new("redis://127.0.0.1/", "orders-service:dedup:", Duration::from_secs(86_400)).unwrap());
+/// assert!(store.insert("1"));
+/// assert!(!store.insert("1"));
+///
+/// in_memory::set_dedup_store(store.clone());
+/// kafka::set_dedup_store(store);
+/// ```
+#[cfg(feature = "redis")]
+pub struct RedisDedupStore {
+    connection: Mutex<redis::Connection>,
+    key_prefix: String,
+    time_to_live: Duration,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public traits
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Store of already processed event ids, shared by the backends (see `in_memory::set_dedup_store` and
+/// `kafka::set_dedup_store`), so that an event is processed only once, whichever way it arrives.
+pub trait DedupStore: Send + Sync {
+    /// Records the id, returning false if it was already recorded (event is a duplicate).
+    fn insert(&self, id: &str) -> bool;
+    /// Forgets the id, so the event can be processed again (e.g. when it wasn't processed after all).
+    fn remove(&self, id: &str);
+    /// Checks whether the id is recorded.
+    fn contains(&self, id: &str) -> bool;
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct SeenIds {
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl InMemoryDedupStore {
+    pub fn new(capacity: usize) -> Self {
+        InMemoryDedupStore {
+            capacity,
+            seen: Mutex::new(SeenIds { ids: HashSet::new(), order: VecDeque::new() }),
+        }
+    }
+}
+
+impl DedupStore for InMemoryDedupStore {
+    fn insert(&self, id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.ids.contains(id) {
+            return false;
+        }
+        if seen.order.len() >= self.capacity {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.ids.remove(&oldest);
+            }
+        }
+        seen.ids.insert(String::from(id));
+        seen.order.push_back(String::from(id));
+        true
+    }
+
+    fn remove(&self, id: &str) {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.ids.remove(id) {
+            seen.order.retain(|seen_id| seen_id != id);
+        }
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.seen.lock().unwrap().ids.contains(id)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl RedisDedupStore {
+    /// Connects to Redis at the URL (e.g. `redis://127.0.0.1/`).
+    pub fn new(url: &str, key_prefix: impl Into<String>, time_to_live: Duration) -> Result<Self, redis::RedisError> {
+        let connection = redis::Client::open(url)?.get_connection()?;
+        Ok(RedisDedupStore {
+            connection: Mutex::new(connection),
+            key_prefix: key_prefix.into(),
+            time_to_live,
+        })
+    }
+
+    fn query<T: redis::FromRedisValue>(&self, command: &mut redis::Cmd, default: T) -> T {
+        command.query(&mut *self.connection.lock().unwrap()).unwrap_or_else(|e| {
+            error!(target: &common::format_target("RedisDedupStore"), "dedup store query failed: {}", e);
+            default
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl DedupStore for RedisDedupStore {
+    fn insert(&self, id: &str) -> bool {
+        let key = format!("{}{}", self.key_prefix, id);
+        let mut command = redis::cmd("SET");
+        command.arg(key).arg(1).arg("NX").arg("PX").arg(self.time_to_live.as_millis() as u64);
+        self.query::<Option<String>>(&mut command, Some(String::from("OK"))).is_some()
+    }
+
+    fn remove(&self, id: &str) {
+        self.query::<()>(redis::cmd("DEL").arg(format!("{}{}", self.key_prefix, id)), ());
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.query(redis::cmd("EXISTS").arg(format!("{}{}", self.key_prefix, id)), false)
+    }
+}
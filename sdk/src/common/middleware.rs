@@ -0,0 +1,243 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use log::info;
+use crate::common;
+use crate::model::{Event, HandlerError};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Handler of an event wrapped by the middlewares: either the next middleware, or the event handler itself (terminal
+/// handler), reporting failure of the event handler (see `EventHandler::try_handle`), including its panic.
+pub type Handler<'a> = Box<dyn Fn(&dyn Event) -> Result<(), HandlerError> + 'a>;
+
+/// Builder of the middleware stack, layering middlewares around the terminal handler: the first middleware added is
+/// the outermost one, so it runs first before the event is handled, and last after that.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, in_memory, model};
+/// use eventure::common::{Handler, Middleware, MiddlewareStack};
+///
+/// static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// // counts handled events
+/// struct Counter(AtomicUsize);
+///
+/// impl Middleware for Counter {
+///     fn wrap<'a>(&'a self, next: Handler<'a>) -> Handler<'a> {
+///         Box::new(move |event| {
+///             CALLS.lock().unwrap().push(String::from("counter: before"));
+///             let result = next(event);
+///             let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+///             CALLS.lock().unwrap().push(format!("counter: after ({} handled, {:?})", count, result));
+///             result
+///         })
+///     }
+/// }
+///
+/// // passes the event on with its id tagged
+/// struct Transformer;
+///
+/// impl Middleware for Transformer {
+///     fn wrap<'a>(&'a self, next: Handler<'a>) -> Handler<'a> {
+///         Box::new(move |event| {
+///             CALLS.lock().unwrap().push(String::from("transformer"));
+///             next(&OrderCreated { event_id: format!("{}-transformed", event.id()) })
+///         })
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         CALLS.lock().unwrap().push(format!("handler: {}", event.id()));
+///         if event.id() == "2-transformed" {
+///             panic!("order not found");
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// common::set_middleware(MiddlewareStack::new().with(Counter(AtomicUsize::new(0))).with(Transformer).build());
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+/// in_memory::emit(&OrderCreated { event_id: String::from("1") });
+/// // the panic is reported to the middleware as failure
+/// in_memory::emit(&OrderCreated { event_id: String::from("2") });
+///
+/// assert_eq!(*CALLS.lock().unwrap(), vec![
+///     "counter: before",
+///     "transformer",
+///     "handler: 1-transformed",
+///     "counter: after (1 handled, Ok(()))",
+///     "counter: before",
+///     "transformer",
+///     "handler: 2-transformed",
+///     "counter: after (2 handled, Err(HandlerError { message: \"event handler panicked: order not found\" }))",
+/// ]);
+/// ```
+#[derive(Default)]
+pub struct MiddlewareStack {
+    middlewares: Vec<Box<dyn Middleware>>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public traits
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Cross-cutting concern (e.g. logging, metrics, tracing or retry) applied to every event handled by the consumers of
+/// all the backends (see `set_middleware`). Middleware wraps the next handler into a new one, deciding whether (and
+/// how many times) to pass the event on, possibly transformed, and what to do with the result.
+pub trait Middleware: Send + Sync {
+    fn wrap<'a>(&'a self, next: Handler<'a>) -> Handler<'a>;
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Sets middleware (typically built by `MiddlewareStack`) wrapping the event handlers of the in-memory handlers (as
+/// well as their tasks and history replay), of the Kafka consumers and of the file source. For the Kafka batch
+/// consumers, the middleware wraps admission of each event into the batch (so it can leave the event out, or replace
+/// it), rather than handling of the batch. Replaces the one set before.
+pub fn set_middleware(middleware: Arc<dyn Middleware>) {
+    info!(target: &common::format_target("Middleware"), "middleware set");
+    *MIDDLEWARE.lock().unwrap() = Some(middleware);
+    MIDDLEWARE_VERSION.fetch_add(1, Ordering::SeqCst);
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Handles the event by the terminal handler, wrapped in the middleware, if set. Panic of the terminal handler is
+/// reported to the middleware as its failure (handlers not wrapped panic as usual).
+pub(crate) fn with_middleware(event: &dyn Event, handler: &dyn Fn(&dyn Event) -> Result<(), HandlerError>) -> Result<(), HandlerError> {
+    match current_middleware() {
+        Some(middleware) => middleware.wrap(Box::new(|event| catch_panic(event, handler)))(event),
+        None => handler(event),
+    }
+}
+
+/// Handles the event by the handler, reporting its panic as `HandlerError` (so it doesn't unwind into the caller).
+pub(crate) fn catch_panic(event: &dyn Event, handler: &dyn Fn(&dyn Event) -> Result<(), HandlerError>) -> Result<(), HandlerError> {
+    panic::catch_unwind(AssertUnwindSafe(|| handler(event))).unwrap_or_else(|panic| {
+        let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(HandlerError::new(format!("event handler panicked: {}", message)))
+    })
+}
+
+/// Checks, whether the middleware is set.
+pub(crate) fn has_middleware() -> bool {
+    current_middleware().is_some()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static MIDDLEWARE: Mutex<Option<Arc<dyn Middleware>>> = Mutex::new(None);
+/// Incremented whenever the middleware is set, so the threads refresh their copy of it.
+static MIDDLEWARE_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static CURRENT_MIDDLEWARE: RefCell<(usize, Option<Arc<dyn Middleware>>)> = const { RefCell::new((0, None)) };
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Returns the middleware set, read from the thread's copy (the global one is locked only once it's been set again).
+fn current_middleware() -> Option<Arc<dyn Middleware>> {
+    let version = MIDDLEWARE_VERSION.load(Ordering::SeqCst);
+    if version == 0 {
+        return None;
+    }
+    CURRENT_MIDDLEWARE.with(|current| {
+        let mut current = current.borrow_mut();
+        if current.0 != version {
+            *current = (version, MIDDLEWARE.lock().unwrap().clone());
+        }
+        current.1.clone()
+    })
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl MiddlewareStack {
+    pub fn new() -> Self {
+        MiddlewareStack { middlewares: Vec::new() }
+    }
+
+    /// Adds middleware, wrapped by the ones added before.
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    pub fn build(self) -> Arc<dyn Middleware> {
+        Arc::new(self)
+    }
+}
+
+impl Middleware for MiddlewareStack {
+    /// Wraps the next handler by the middlewares, the first one added outermost.
+    fn wrap<'a>(&'a self, next: Handler<'a>) -> Handler<'a> {
+        self.middlewares.iter().rev().fold(next, |next, middleware| middleware.wrap(next))
+    }
+}
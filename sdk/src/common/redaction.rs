@@ -0,0 +1,162 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use crate::model::Event;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Sets log redactor, rendering events in the log messages of all the backends instead of their `Display`, so that
+/// sensitive fields (e.g. customer ids) can be masked. Handlers still receive the full events. Serialized events
+/// which can't be deserialized (so can't be redacted) are logged only by their id (if known) and size.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use log::{LevelFilter, Log, Metadata, Record};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, in_memory, model};
+///
+/// static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// struct CapturingLogger;
+///
+/// impl Log for CapturingLogger {
+///     fn enabled(&self, _metadata: &Metadata) -> bool {
+///         true
+///     }
+///     fn log(&self, record: &Record) {
+///         LOGGED.lock().unwrap().push(record.args().to_string());
+///     }
+///     fn flush(&self) {}
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {} of customer {}",
+///                "OrderCreated", self.event_id, self.customer_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// log::set_logger(&CapturingLogger).unwrap();
+/// log::set_max_level(LevelFilter::Info);
+/// common::set_log_redactor(|event| format!("{} event with id ***", event.name()));
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderCreatedEventHandler);
+/// in_memory::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer-42") });
+///
+/// let logged = LOGGED.lock().unwrap();
+/// assert!(logged.iter().any(|message| message.ends_with("in-memory event emitted: OrderCreated event with id ***")));
+/// assert!(logged.iter().all(|message| !message.contains("customer-42")));
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["OrderCreated event with id 1 of customer customer-42"]);
+/// ```
+pub fn set_log_redactor(redactor: impl Fn(&dyn Event) -> String + Send + Sync + 'static) {
+    *REDACTOR.lock().unwrap() = Some(Arc::new(redactor));
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Wraps event for log messages, rendered (lazily, only when logged) by the log redactor, if set.
+pub(crate) fn redacted(event: &dyn Event) -> Redacted<'_> {
+    Redacted { event }
+}
+
+/// Wraps serialized event (e.g. one that can't be deserialized) for log messages, rendered by its id (if known) and
+/// size, without the payload.
+pub(crate) fn redacted_payload<'a>(event_id: Option<&'a str>, payload: &[u8]) -> RedactedPayload<'a> {
+    RedactedPayload { event_id, size: payload.len() }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static REDACTOR: Mutex<Option<Arc<Redactor>>> = Mutex::new(None);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(crate) struct Redacted<'a> {
+    event: &'a dyn Event,
+}
+
+pub(crate) struct RedactedPayload<'a> {
+    event_id: Option<&'a str>,
+    size: usize,
+}
+
+type Redactor = dyn Fn(&dyn Event) -> String + Send + Sync;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Display for Redacted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let redactor = REDACTOR.lock().unwrap().clone();
+        match redactor {
+            Some(redactor) => write!(f, "{}", redactor(self.event)),
+            None => write!(f, "{}", self.event),
+        }
+    }
+}
+
+impl Display for RedactedPayload<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.event_id {
+            Some(event_id) => write!(f, "with id {} ({} bytes)", event_id, self.size),
+            None => write!(f, "({} bytes)", self.size),
+        }
+    }
+}
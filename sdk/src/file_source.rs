@@ -0,0 +1,13 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+//! File source: events read from a directory of JSON files (e.g. fixtures for testing, or batch import).
+
+mod implementation;
+
+pub use self::implementation::MessageBrokerConfiguration;
+pub use self::implementation::configuration;
+pub use self::implementation::setup;
+pub use self::implementation::register;
+pub use self::implementation::shutdown;
@@ -0,0 +1,328 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::{fs, io, thread};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use log::{error, info, warn};
+use crate::common;
+use crate::model::{Event, EventHandler};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// File source configuration.
+#[derive(Debug, Clone)]
+pub struct MessageBrokerConfiguration {
+    /// Directory with the event files (`.json` extension, each holding one event, serialized with `Event::to_json`).
+    pub directory: &'static str,
+    /// Interval of checking the directory for new files, if it should be watched after the initial read.
+    pub watch_interval: Option<Duration>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Creates file source configuration.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use eventure::file_source;
+///
+/// let configuration = file_source::configuration("fixtures/events", Some(Duration::from_secs(1)));
+/// assert_eq!(configuration.directory, "fixtures/events");
+/// ```
+pub fn configuration(directory: &'static str, watch_interval: Option<Duration>) -> MessageBrokerConfiguration {
+    MessageBrokerConfiguration {
+        directory,
+        watch_interval,
+    }
+}
+
+/// Registers event handler, receiving all the events read from the directory.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use eventure::{file_source, model};
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}", event);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// file_source::register(OrderEventHandler);
+/// ```
+pub fn register(event_handler: impl EventHandler + Send + 'static) {
+    info!(target: &common::format_target("FileSource"), "event handler registered: {}", event_handler);
+    HANDLERS.lock().unwrap().push(Arc::new(Mutex::new(event_handler)));
+}
+
+/// Reads all the `.json` files of the configured directory, dispatching the events to registered handlers in the
+/// filename order, and returns the number of dispatched events. Files not holding an event (or not readable) are
+/// logged and skipped. With watch interval set, the directory is then checked for new files (dispatched the same way)
+/// by a background thread, until `shutdown`; skipped files (e.g. still being written) are read again on each check.
+/// Handlers are wrapped in the middleware (see `common::set_middleware`), if set. Handler failures (panics included)
+/// are logged, without stopping the dispatch.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::fs;
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{file_source, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///         if event.id() == "2" {
+///             panic!("poison pill");
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let directory = std::env::temp_dir().join(format!("eventure-fixtures-{}", std::process::id()));
+/// fs::create_dir_all(&directory).unwrap();
+/// for (file, id) in [("03-order.json", "3"), ("01-order.json", "1"), ("02-order.json", "2")] {
+///     let event = OrderCreated { event_id: String::from(id) };
+///     fs::write(directory.join(file), model::Event::to_json(&event)).unwrap();
+/// }
+/// fs::write(directory.join("README.md"), "not an event").unwrap();
+/// // still being written
+/// fs::write(directory.join("04-order.json"), r#"{"type":"OrderCreated","#).unwrap();
+///
+/// file_source::register(OrderEventHandler);
+/// let watched: &'static str = Box::leak(directory.to_str().unwrap().to_string().into_boxed_str());
+/// let dispatched = file_source::setup(file_source::configuration(watched, Some(Duration::from_millis(50)))).unwrap();
+///
+/// assert_eq!(dispatched, 3);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3"]);
+///
+/// // read again once complete, the handler panicking on the second event notwithstanding
+/// fs::write(directory.join("04-order.json"), model::Event::to_json(&OrderCreated { event_id: String::from("4") })).unwrap();
+/// while HANDLED.lock().unwrap().len() < 4 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3", "4"]);
+/// assert!(file_source::shutdown());
+/// fs::remove_dir_all(directory).unwrap();
+/// ```
+pub fn setup(configuration: MessageBrokerConfiguration) -> io::Result<usize> {
+    shutdown();
+    let directory = PathBuf::from(configuration.directory);
+    let mut files = SeenFiles { seen: BTreeSet::new(), failed: BTreeSet::new() };
+    let dispatched = dispatch_new(&directory, &mut files)?;
+    info!(target: &common::format_target("FileSource"), "{} events read from {}", dispatched, directory.display());
+    if let Some(interval) = configuration.watch_interval {
+        let stop = Arc::new(AtomicBool::new(false));
+        let watcher_stop = Arc::clone(&stop);
+        let thread = thread::spawn(move || watch(&directory, files, interval, &watcher_stop));
+        *WATCHER.lock().unwrap() = Some(Watcher { stop, thread });
+    }
+    Ok(dispatched)
+}
+
+/// Stops watching the directory (if watched). Returns whether a watcher was stopped.
+///
+/// # Examples
+/// ```
+/// use eventure::file_source;
+///
+/// assert!(!file_source::shutdown());
+/// ```
+pub fn shutdown() -> bool {
+    let watcher = WATCHER.lock().unwrap().take();
+    match watcher {
+        Some(watcher) => {
+            watcher.stop.store(true, Ordering::SeqCst);
+            watcher.thread.thread().unpark();
+            let _ = watcher.thread.join();
+            info!(target: &common::format_target("FileSource"), "directory watcher stopped");
+            true
+        }
+        None => false,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static HANDLERS: Mutex<Vec<SharedHandler>> = Mutex::new(Vec::new());
+static WATCHER: Mutex<Option<Watcher>> = Mutex::new(None);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct Watcher {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Files read (dispatched) so far, and the ones skipped, to be read again.
+struct SeenFiles {
+    seen: BTreeSet<PathBuf>,
+    failed: BTreeSet<PathBuf>,
+}
+
+/// Registered handler, locked only while handling an event (so handlers may register other handlers meanwhile).
+type SharedHandler = Arc<Mutex<dyn EventHandler + Send>>;
+
+enum FileError {
+    Io(io::Error),
+    Malformed(serde_json::Error),
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Checks the directory every interval until stopped (`shutdown` unparks the watcher, so it stops right away).
+fn watch(directory: &Path, mut files: SeenFiles, interval: Duration, stop: &AtomicBool) {
+    let mut next_check = Instant::now() + interval;
+    while !stop.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now < next_check {
+            thread::park_timeout(next_check - now);
+            continue;
+        }
+        if let Err(error) = dispatch_new(directory, &mut files) {
+            warn!(target: &common::format_target("FileSource"), "directory {} not read: {}", directory.display(), error);
+        }
+        next_check = Instant::now() + interval;
+    }
+}
+
+/// Dispatches events of the files not seen yet, in the filename order. Files failing to be read aren't marked as
+/// seen, so they're read again next time (logged only the first time).
+fn dispatch_new(directory: &Path, files: &mut SeenFiles) -> io::Result<usize> {
+    let mut new_files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json") && !files.seen.contains(&path) {
+            new_files.push(path);
+        }
+    }
+    new_files.sort_by(|first, second| first.file_name().cmp(&second.file_name()));
+
+    let mut dispatched = 0;
+    for file in new_files {
+        match read_event(&file) {
+            Ok(event) => {
+                dispatch(event.as_ref(), &file);
+                dispatched += 1;
+                files.failed.remove(&file);
+                files.seen.insert(file);
+            }
+            Err(error) => {
+                if !files.failed.contains(&file) {
+                    warn!(target: &common::format_target("FileSource"), "file {} skipped: {}", file.display(), error);
+                    files.failed.insert(file);
+                }
+            }
+        }
+    }
+    Ok(dispatched)
+}
+
+fn read_event(file: &Path) -> Result<Box<dyn Event>, FileError> {
+    let content = fs::read_to_string(file).map_err(FileError::Io)?;
+    serde_json::from_str(&content).map_err(FileError::Malformed)
+}
+
+fn dispatch(event: &dyn Event, file: &Path) {
+    info!(target: &common::format_target("FileSource"), "file event {} read from {}", common::redacted(event), file.display());
+    let event_handlers: Vec<SharedHandler> = HANDLERS.lock().unwrap().clone();
+    for event_handler in event_handlers {
+        let event_handler = event_handler.lock().unwrap();
+        // panic is caught even without middleware, so it neither stops the watcher nor poisons the handler
+        let handled = common::with_middleware(event, &|event| common::catch_panic(event, &|event| event_handler.try_handle(event)));
+        if let Err(e) = handled {
+            error!(target: &common::format_target("FileSource"), "handler {} failed on file event {}: {}",
+                event_handler, common::redacted(event), e);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "not readable: {}", error),
+            FileError::Malformed(error) => write!(f, "not an event: {}", error),
+        }
+    }
+}
@@ -4,17 +4,125 @@
 
 //! In-Memory message broken implementation.
 //!
-//! At the moment only synchronous mode is supported (for queues and topics).
+//! Events are dispatched synchronously by default (for queues and topics). In async mode, emitted events are
+//! queued and dispatched by a pool of worker threads.
+//!
+//! Handlers always receive `&dyn Event`, which can be downcast to the concrete event type. Synchronous dispatch
+//! passes the emitted instance itself to the handlers. The following features serialize events via `Event::to_json`
+//! (the ones deserializing them later do so through `typetag`, so such events need to be registered with
+//! `#[typetag::serde]`):
+//! - async mode: queued events are deserialized by the worker. Use `emit_in_process`/`emit_in_process_to_channel`
+//!   to dispatch specific events on the calling thread instead.
+//! - `emit_signal` in synchronous mode: signals are deserialized by the signal worker.
+//! - event history (see `set_history_capacity`): every dispatched event is recorded, once the capacity is set.
+//! - dead letters (see `set_dead_letter_capacity`): events no handler handled are kept, once the capacity is set.
+//! - `register_async_task`: events are forwarded to the task.
+//! - `register_jsonpath`: the expression is evaluated against the serialized event (not deserialized again).
+//! - `request`/`reply`: replies are passed back to the requester.
+//!
+//! The same applies to the events emitted in-process.
 
+mod causes;
+mod config_toml;
+mod dead_letters;
+mod dispatcher;
+mod history;
 mod implementation;
+mod lifecycle;
+mod match_cache;
+mod metrics;
+mod rate_limiter;
+mod replies;
+mod scheduler;
+mod sequencer;
+mod trace;
 
 pub use self::implementation::ChannelType;
+pub use self::implementation::EmitError;
+pub use self::implementation::RegistrationError;
+pub use self::implementation::RoundtripError;
+pub use self::implementation::ScheduleError;
+pub use self::implementation::RequestError;
+pub use self::implementation::EmitLoopDetected;
+pub use self::implementation::EmitReport;
+pub use self::implementation::LocalRegistry;
+pub use self::implementation::BatchOrder;
 pub use self::implementation::MessageChannel;
 pub use self::implementation::MessageBrokerConfiguration;
+pub use self::implementation::AsyncSettings;
+pub use self::implementation::Scheduling;
+pub use self::implementation::FanoutMode;
+pub use self::implementation::ConfigurationError;
+pub use self::implementation::ConfigImportError;
 pub use self::implementation::setup;
+pub use self::implementation::export_config_toml;
+pub use self::implementation::import_config_toml;
 pub use self::implementation::register;
+#[cfg(feature = "tokio")]
+pub use self::implementation::register_async_task;
+pub use self::implementation::register_or_replace;
+pub use self::implementation::register_jsonpath;
+pub use self::implementation::register_with_error_handler;
+pub use self::implementation::register_with_max_concurrency;
+pub use self::implementation::register_pipeline;
+pub use self::implementation::register_with_snapshot;
+pub use self::implementation::register_in_group;
+pub use self::implementation::enable_group;
+pub use self::implementation::disable_group;
+pub use self::implementation::unregister_group;
 pub use self::implementation::unregister;
+pub use self::implementation::shutdown;
+pub(crate) use self::implementation::shutdown_counting;
 pub use self::implementation::emit;
 pub use self::implementation::emit_to_channel;
+pub use self::implementation::emit_with_id;
+pub use self::implementation::request;
+pub use self::implementation::request_typed;
+pub use self::implementation::reply;
+pub use self::implementation::emit_idempotent;
+pub use self::implementation::emit_with_callback;
+pub use self::implementation::emit_signal;
+pub use self::implementation::current_event_id;
+pub use self::implementation::current_trace;
+pub use self::implementation::cause_chain;
+pub use self::implementation::set_loop_detection;
+pub use self::implementation::emit_until_err;
+pub use self::implementation::check_roundtrip;
+pub use self::implementation::emit_all;
+pub use self::implementation::emit_in_process;
+pub use self::implementation::emit_in_process_to_channel;
+pub use self::implementation::try_emit;
+pub use self::implementation::try_emit_to_channel;
 pub use self::implementation::configuration;
+pub use self::implementation::async_settings;
 pub use self::implementation::message_channel;
+pub use self::implementation::try_message_channel;
+pub use self::implementation::add_before_handle;
+pub use self::implementation::add_after_handle;
+pub use self::implementation::register_tap;
+pub use self::implementation::export_topology;
+pub use self::implementation::set_rate_limit;
+pub use self::implementation::set_max_fanout;
+pub use self::implementation::set_priority;
+pub use self::implementation::set_concurrency_key;
+pub use self::implementation::set_watermarks;
+pub use self::implementation::set_total_order;
+pub use self::implementation::current_sequence;
+pub use self::implementation::set_match_cache_capacity;
+pub use self::implementation::set_history_capacity;
+pub use self::implementation::set_dead_letter_capacity;
+pub use self::implementation::set_dead_letter_budget;
+pub use self::implementation::replay_dead_letters;
+pub use self::implementation::replay_filtered;
+pub use self::implementation::set_dedup_store;
+pub use self::implementation::emit_after;
+pub use self::implementation::scheduled;
+pub use self::implementation::cancel_schedule;
+pub use self::implementation::metrics_by_event;
+pub use self::rate_limiter::RateLimitMode;
+pub use self::scheduler::ScheduleInfo;
+pub use self::metrics::Counts;
+pub use self::trace::TraceContext;
+pub use self::lifecycle::LifecycleEvent;
+pub use self::lifecycle::LifecycleEventKind;
+pub use self::lifecycle::LIFECYCLE_CHANNEL;
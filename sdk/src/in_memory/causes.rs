@@ -0,0 +1,163 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use log::info;
+use crate::common;
+use super::implementation::EmitLoopDetected;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Ids and names of the events which led to the event being dispatched, the root cause first, the dispatched event
+/// last.
+pub(super) type Ancestry = Vec<(String, String)>;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(max_depth: Option<usize>, detect_name_cycles: bool) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "setting up loop detection: [max_depth:{:?},detect_name_cycles:{}]",
+        max_depth, detect_name_cycles);
+    *LOOP_DETECTION.lock().unwrap() = LoopDetection { max_depth, detect_name_cycles };
+}
+
+/// Returns configured maximum depth and whether name cycles are detected.
+pub(super) fn loop_detection() -> (Option<usize>, bool) {
+    let loop_detection = LOOP_DETECTION.lock().unwrap();
+    (loop_detection.max_depth, loop_detection.detect_name_cycles)
+}
+
+pub(super) fn current() -> Option<Ancestry> {
+    CURRENT_ANCESTRY.with(|current| current.borrow().clone())
+}
+
+/// Runs the function with given ancestry set as the current one (e.g. ancestry captured when the event was queued,
+/// restored by the thread dispatching it).
+pub(super) fn with_current<T>(ancestry: Option<Ancestry>, function: impl FnOnce() -> T) -> T {
+    let _previous = PreviousAncestry(CURRENT_ANCESTRY.with(|current| current.replace(ancestry)));
+    function()
+}
+
+/// Returns ancestry of the event about to be dispatched, extending the current one, and records the event being
+/// handled (if any) as its cause. Fails if the ancestry (the event included) exceeds the maximum depth, or its event
+/// names repeat a cycle (so the event would be dispatched again and again).
+pub(super) fn extend(event_id: &str, event_name: &str) -> Result<Ancestry, EmitLoopDetected> {
+    let mut ancestry = current().unwrap_or_default();
+    let loop_detection = *LOOP_DETECTION.lock().unwrap();
+    ancestry.push((String::from(event_id), String::from(event_name)));
+    if loop_detection.max_depth.is_some_and(|max_depth| ancestry.len() > max_depth) {
+        return Err(EmitLoopDetected::MaxDepthExceeded { event_id: String::from(event_id), depth: ancestry.len() });
+    }
+    if loop_detection.detect_name_cycles {
+        if let Some(cycle) = name_cycle(&ancestry) {
+            return Err(EmitLoopDetected::NameCycle { event_id: String::from(event_id), cycle });
+        }
+    }
+    if let [.., (cause_id, _), _] = &ancestry[..] {
+        record(event_id, cause_id);
+    }
+    Ok(ancestry)
+}
+
+/// Returns ids of the events which led to the event, the root cause first. Stops at an event already visited, so
+/// re-emitted events (same id) don't make the chain endless.
+pub(super) fn chain(event_id: &str) -> Vec<String> {
+    let causes = CAUSES.lock().unwrap();
+    let mut chain: Vec<String> = Vec::new();
+    let mut current = event_id;
+    while let Some(cause_id) = causes.causes.get(current) {
+        if cause_id == event_id || chain.contains(cause_id) {
+            break;
+        }
+        chain.push(cause_id.clone());
+        current = cause_id;
+    }
+    chain.reverse();
+    chain
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static CAUSES: Mutex<Causes> = Mutex::new(Causes::new());
+static LOOP_DETECTION: Mutex<LoopDetection> = Mutex::new(LoopDetection { max_depth: Some(DEFAULT_MAX_DEPTH), detect_name_cycles: false });
+
+thread_local! {
+    static CURRENT_ANCESTRY: RefCell<Option<Ancestry>> = const { RefCell::new(None) };
+}
+
+const CAPACITY: usize = 10_000;
+/// Maximum depth applied unless configured otherwise, so that handlers re-triggering themselves are stopped well
+/// before the stack overflows.
+pub(super) const DEFAULT_MAX_DEPTH: usize = 64;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Cause (event id) by emitted event id, along with the recording order (for eviction).
+struct Causes {
+    causes: BTreeMap<String, String>,
+    order: VecDeque<String>,
+}
+
+/// Ancestry current before `with_current`, restored when dropped (also if the function panics).
+struct PreviousAncestry(Option<Ancestry>);
+
+#[derive(Clone, Copy)]
+struct LoopDetection {
+    max_depth: Option<usize>,
+    detect_name_cycles: bool,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Records cause of the event. Once the capacity is reached, the oldest records are evicted.
+fn record(event_id: &str, cause_id: &str) {
+    let mut causes = CAUSES.lock().unwrap();
+    if causes.causes.insert(String::from(event_id), String::from(cause_id)).is_none() {
+        causes.order.push_back(String::from(event_id));
+    }
+    while causes.order.len() > CAPACITY {
+        if let Some(evicted) = causes.order.pop_front() {
+            causes.causes.remove(&evicted);
+        }
+    }
+}
+
+/// Returns event names the ancestry ends with twice in a row (e.g. `A` for `X, A, A`, or `A, B` for `X, A, B, A, B`),
+/// if any.
+fn name_cycle(ancestry: &Ancestry) -> Option<Vec<String>> {
+    let names: Vec<&str> = ancestry.iter().map(|(_, name)| name.as_str()).collect();
+    (1..=names.len() / 2)
+        .find(|length| names[names.len() - length..] == names[names.len() - 2 * length..names.len() - length])
+        .map(|length| names[names.len() - length..].iter().map(|name| String::from(*name)).collect())
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Causes {
+    const fn new() -> Self {
+        Causes {
+            causes: BTreeMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl Drop for PreviousAncestry {
+    fn drop(&mut self) {
+        CURRENT_ANCESTRY.with(|current| current.replace(self.0.take()));
+    }
+}
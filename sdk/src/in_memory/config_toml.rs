@@ -0,0 +1,117 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::Error;
+use super::implementation::{AsyncSettings, ChannelType, ConfigImportError, FanoutMode};
+use super::causes;
+use super::match_cache;
+use super::rate_limiter::RateLimitMode;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Message broker settings exported to TOML: `[message_channel]` (omitted if not configured), `[async]` (omitted in
+/// sync mode), `[limits]` and `[[priorities]]` tables. Tables and keys missing in the TOML default to the settings of
+/// unconfigured message broker; unknown ones are rejected.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) message_channel: Option<ChannelSettings>,
+    #[serde(rename = "async", skip_serializing_if = "Option::is_none")]
+    pub(super) async_settings: Option<AsyncSettings>,
+    pub(super) limits: Limits,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) priorities: Vec<Priority>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ChannelSettings {
+    pub(super) channel_type: ChannelType,
+    /// Channel name regex, checked to be valid when read.
+    #[serde(deserialize_with = "regex")]
+    pub(super) name: String,
+}
+
+/// Limits, unset ones (and the modes of unset rate limit and max fan-out) omitted.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct Limits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) rate_limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) rate_limit_mode: Option<RateLimitMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) max_fanout: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) fanout_mode: Option<FanoutMode>,
+    /// Maximum depth of emit loops, zero for none.
+    pub(super) loop_max_depth: usize,
+    pub(super) loop_detect_name_cycles: bool,
+    pub(super) match_cache_capacity: usize,
+    pub(super) history_capacity: usize,
+    pub(super) dead_letter_capacity: usize,
+    pub(super) dead_letter_budget: usize,
+    pub(super) total_order: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Priority {
+    pub(super) channel_type: ChannelType,
+    pub(super) name: String,
+    pub(super) priority: u8,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn to_toml(settings: &Settings) -> String {
+    toml::to_string(settings).expect("settings serializable to TOML expected")
+}
+
+/// Reads settings from TOML, reporting the line the error (if any) starts at.
+pub(super) fn from_toml(toml: &str) -> Result<Settings, ConfigImportError> {
+    toml::from_str(toml).map_err(|e: toml::de::Error| {
+        let start = e.span().unwrap_or_default().start;
+        ConfigImportError::Malformed { line: toml[..start].matches('\n').count() + 1, reason: String::from(e.message()) }
+    })
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Regex::new(&name).map_err(|e| D::Error::custom(format!("invalid channel name {}: {}", name, e)))?;
+    Ok(name)
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            rate_limit: None,
+            rate_limit_mode: None,
+            max_fanout: None,
+            fanout_mode: None,
+            loop_max_depth: causes::DEFAULT_MAX_DEPTH,
+            loop_detect_name_cycles: false,
+            match_cache_capacity: match_cache::DEFAULT_CAPACITY,
+            history_capacity: 0,
+            dead_letter_capacity: 0,
+            dead_letter_budget: 0,
+            total_order: false,
+        }
+    }
+}
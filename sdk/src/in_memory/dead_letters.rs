@@ -0,0 +1,135 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use log::{error, info, warn};
+use crate::common;
+use crate::model::Event;
+use super::implementation::{self, MessageChannel};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Event no handler matched, along with the channel (and overridden id) it was emitted with.
+pub(super) struct DeadLetter {
+    pub(super) payload: String,
+    pub(super) channel: Option<MessageChannel>,
+    pub(super) event_id: Option<String>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(capacity: usize) {
+    info!(target: &common::format_target("DeadLetterQueue"), "setting up: [capacity:{}]", capacity);
+    let mut dead_letters = DEAD_LETTERS.lock().unwrap();
+    dead_letters.capacity = capacity;
+    dead_letters.evict(0);
+}
+
+/// Sets limit of the total payload bytes retained by the queue (zero means unlimited).
+pub(super) fn configure_budget(byte_budget: usize) {
+    info!(target: &common::format_target("DeadLetterQueue"), "setting up: [byte-budget:{}]", byte_budget);
+    let mut dead_letters = DEAD_LETTERS.lock().unwrap();
+    dead_letters.byte_budget = byte_budget;
+    dead_letters.evict(0);
+}
+
+/// Returns configured capacity and byte budget of the queue.
+pub(super) fn settings() -> (usize, usize) {
+    let dead_letters = DEAD_LETTERS.lock().unwrap();
+    (dead_letters.capacity, dead_letters.byte_budget)
+}
+
+/// Buffers unmatched event (serialized via `Event::to_json`, unless it fails the round-trip check), dropping the oldest
+/// ones once the queue is full (or the byte budget exceeded). Does nothing, unless the queue is enabled.
+pub(super) fn push(event: &dyn Event, channel: Option<&MessageChannel>, event_id: Option<&str>) {
+    if DEAD_LETTERS.lock().unwrap().capacity == 0 {
+        return;
+    }
+    let payload = match implementation::serialize(event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!(target: &common::format_target("DeadLetterQueue"), "event {} not dead-lettered: {}", common::redacted(event), e);
+            return;
+        }
+    };
+    info!(target: &common::format_target("DeadLetterQueue"), "event dead-lettered: {}", common::redacted(event));
+    restore(DeadLetter {
+        payload,
+        channel: channel.cloned(),
+        event_id: event_id.map(String::from),
+    });
+}
+
+/// Buffers dead letter (e.g. taken, but not replayed), the same way as `push`.
+pub(super) fn restore(dead_letter: DeadLetter) {
+    let mut dead_letters = DEAD_LETTERS.lock().unwrap();
+    if dead_letters.capacity == 0 {
+        return;
+    }
+    if dead_letters.byte_budget > 0 && dead_letter.payload.len() > dead_letters.byte_budget {
+        warn!(target: &common::format_target("DeadLetterQueue"), "dead letter exceeds byte budget, dropped: event {}",
+            common::redacted_payload(dead_letter.event_id.as_deref(), dead_letter.payload.as_bytes()));
+        return;
+    }
+    dead_letters.evict(dead_letter.payload.len());
+    dead_letters.bytes += dead_letter.payload.len();
+    dead_letters.letters.push_back(dead_letter);
+}
+
+/// Takes all buffered dead letters, oldest first.
+pub(super) fn take() -> Vec<DeadLetter> {
+    let mut dead_letters = DEAD_LETTERS.lock().unwrap();
+    dead_letters.bytes = 0;
+    dead_letters.letters.drain(..).collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static DEAD_LETTERS: Mutex<DeadLetterQueue> = Mutex::new(DeadLetterQueue::new());
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct DeadLetterQueue {
+    capacity: usize,
+    byte_budget: usize,
+    bytes: usize,
+    letters: VecDeque<DeadLetter>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl DeadLetterQueue {
+    const fn new() -> Self {
+        DeadLetterQueue {
+            capacity: 0,
+            byte_budget: 0,
+            bytes: 0,
+            letters: VecDeque::new(),
+        }
+    }
+
+    /// Drops the oldest dead letters, until there's room for the incoming one (of given size, zero if there's none).
+    fn evict(&mut self, incoming: usize) {
+        let reserved = usize::from(incoming > 0);
+        while !self.letters.is_empty() && (self.letters.len() + reserved > self.capacity
+            || (self.byte_budget > 0 && self.bytes + incoming > self.byte_budget)) {
+            if let Some(dropped) = self.letters.pop_front() {
+                self.bytes -= dropped.payload.len();
+                warn!(target: &common::format_target("DeadLetterQueue"), "queue full, dead letter dropped: event {}",
+                    common::redacted_payload(dropped.event_id.as_deref(), dropped.payload.as_bytes()));
+            }
+        }
+    }
+}
@@ -0,0 +1,380 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use log::{error, info, warn};
+use crate::common;
+use crate::model::Event;
+use super::causes::{self, Ancestry};
+use super::trace::{self, TraceContext};
+use super::implementation::{self, AsyncSettings, ChannelType, EmitReport, MessageChannel, RoundtripError, Scheduling};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Starts async dispatcher with given settings, stopping (and draining) previously running one.
+pub(super) fn start(settings: &AsyncSettings) {
+    stop();
+    let queue = Arc::new(JobQueue::new(settings.capacity));
+    let workers = (0..settings.concurrency)
+        .map(|index| {
+            let queue = Arc::clone(&queue);
+            thread::Builder::new()
+                .name(format!("eventure-dispatcher-{}", index))
+                .spawn(move || work(&queue))
+                .expect("dispatcher worker expected")
+        })
+        .collect();
+    info!(target: &common::format_target("AsyncDispatcher"), "started with {} worker(s), {:?} scheduling",
+        settings.concurrency, settings.scheduling);
+    *DISPATCHER.lock().unwrap() = Some(Dispatcher { queue, workers, settings: settings.clone() });
+}
+
+/// Returns settings of the running async dispatcher, or none in sync mode.
+pub(super) fn settings() -> Option<AsyncSettings> {
+    DISPATCHER.lock().unwrap().as_ref().map(|dispatcher| dispatcher.settings.clone())
+}
+
+/// Stops async dispatcher (if running), waiting for all queued events to be dispatched. Returns number of events
+/// drained from the queue.
+pub(super) fn stop() -> usize {
+    let dispatcher = DISPATCHER.lock().unwrap().take();
+    match dispatcher {
+        Some(Dispatcher { queue, workers, .. }) => {
+            let drained = queue.depth();
+            queue.close();
+            workers.into_iter().for_each(|worker| worker.join().unwrap_or_default());
+            info!(target: &common::format_target("AsyncDispatcher"), "stopped, {} queued events drained", drained);
+            drained
+        }
+        None => 0,
+    }
+}
+
+/// Enqueues event for async dispatch, blocking while the queue is full. If dispatcher isn't running (or the event
+/// can't be serialized), the channel is given back, so the caller can dispatch the event synchronously. Overridden
+/// event id (if any) is queued along with the event.
+pub(super) fn enqueue(event: &dyn Event, channel: Option<MessageChannel>, event_id: Option<&str>) -> Result<(), Option<MessageChannel>> {
+    enqueue_correlated(event, channel, event_id, None)
+}
+
+/// Enqueues request event for async dispatch like `enqueue`, along with the correlation id of the request (see
+/// `implementation::with_correlation_id`).
+pub(super) fn enqueue_request(event: &dyn Event, correlation_id: &str) -> Result<(), Option<MessageChannel>> {
+    enqueue_correlated(event, None, None, Some(correlation_id))
+}
+
+/// Enqueues event for async dispatch like `enqueue`, invoking the callback with dispatch report once all handlers
+/// finished. If dispatcher isn't running (or the event can't be serialized), both the channel and the callback
+/// are given back.
+pub(super) fn enqueue_reporting(event: &dyn Event, channel: Option<MessageChannel>, on_complete: OnComplete)
+                                -> Result<(), (Option<MessageChannel>, OnComplete)> {
+    let (queue, scheduling) = match queue() {
+        Some(queue) => queue,
+        None => return Err((channel, on_complete)),
+    };
+    let payload = match serialize(event) {
+        Some(payload) => payload,
+        None => return Err((channel, on_complete)),
+    };
+    let key = key(event);
+    queue.push(Job { payload, channel, event_id: None, correlation_id: None, key, trace: trace::current(), cause: causes::current(), on_complete: Some(on_complete) }, scheduling)
+        .map(|_| check_watermarks(queue.depth()))
+        .map_err(|job| (job.channel, job.on_complete.unwrap()))
+}
+
+/// Sets priority of the channel (exact type and name match), used by priority scheduling.
+pub(super) fn set_priority(channel: MessageChannel, priority: u8) {
+    let mut priorities = PRIORITIES.lock().unwrap();
+    priorities.retain(|(channel_type, name, _)| *channel_type != channel.channel_type || *name != channel.name);
+    priorities.push((channel.channel_type, String::from(channel.name), priority));
+}
+
+/// Returns priorities of the channels (type, name and priority), in the order they were set.
+pub(super) fn priorities() -> Vec<(ChannelType, String, u8)> {
+    PRIORITIES.lock().unwrap().clone()
+}
+
+/// Replaces priorities of all the channels.
+pub(super) fn replace_priorities(priorities: Vec<(ChannelType, String, u8)>) {
+    *PRIORITIES.lock().unwrap() = priorities;
+}
+
+/// Sets (replaces) queue depth watermarks.
+pub(super) fn set_watermarks(high: usize, low: usize, on_high_watermark: Arc<WatermarkCallback>, on_low_watermark: Arc<WatermarkCallback>) {
+    *WATERMARKS.lock().unwrap() = Some(Watermarks { high, low, above: false, on_high_watermark, on_low_watermark });
+}
+
+/// Sets extractor of the concurrency key, queued along with each event.
+pub(super) fn set_concurrency_key(key_extractor: Arc<KeyExtractor>) {
+    *KEY_EXTRACTOR.lock().unwrap() = Some(key_extractor);
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static DISPATCHER: Mutex<Option<Dispatcher>> = Mutex::new(None);
+static PRIORITIES: Mutex<Vec<(ChannelType, String, u8)>> = Mutex::new(Vec::new());
+static KEY_EXTRACTOR: Mutex<Option<Arc<KeyExtractor>>> = Mutex::new(None);
+static WATERMARKS: Mutex<Option<Watermarks>> = Mutex::new(None);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct Dispatcher {
+    queue: Arc<JobQueue>,
+    workers: Vec<JoinHandle<()>>,
+    settings: AsyncSettings,
+}
+
+/// Bounded queue of jobs, ordered by priority (then by enqueue order). Jobs sharing a concurrency key are dequeued
+/// one at a time: while a job is in flight, later jobs with its key are skipped (but stay queued in order).
+struct JobQueue {
+    state: Mutex<JobQueueState>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct JobQueueState {
+    jobs: BinaryHeap<QueuedJob>,
+    sequence: u64,
+    in_flight_keys: HashSet<String>,
+    closed: bool,
+}
+
+struct QueuedJob {
+    priority: u8,
+    sequence: u64,
+    job: Job,
+}
+
+struct Job {
+    payload: String,
+    channel: Option<MessageChannel>,
+    event_id: Option<String>,
+    correlation_id: Option<String>,
+    key: Option<String>,
+    trace: Option<TraceContext>,
+    cause: Option<Ancestry>,
+    on_complete: Option<OnComplete>,
+}
+
+type OnComplete = Box<dyn FnOnce(EmitReport) + Send>;
+
+/// Queue depth watermarks, `above` tracking whether the high one was crossed (and the low one not yet).
+struct Watermarks {
+    high: usize,
+    low: usize,
+    above: bool,
+    on_high_watermark: Arc<WatermarkCallback>,
+    on_low_watermark: Arc<WatermarkCallback>,
+}
+
+pub(super) type WatermarkCallback = dyn Fn(usize) + Send + Sync;
+pub(super) type KeyExtractor = dyn Fn(&dyn Event) -> Option<String> + Send + Sync;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+fn queue() -> Option<(Arc<JobQueue>, Scheduling)> {
+    DISPATCHER.lock().unwrap().as_ref().map(|dispatcher| (Arc::clone(&dispatcher.queue), dispatcher.settings.scheduling))
+}
+
+fn enqueue_correlated(event: &dyn Event, channel: Option<MessageChannel>, event_id: Option<&str>, correlation_id: Option<&str>)
+                      -> Result<(), Option<MessageChannel>> {
+    let (queue, scheduling) = match queue() {
+        Some(queue) => queue,
+        None => return Err(channel),
+    };
+    let payload = match serialize(event) {
+        Some(payload) => payload,
+        None => return Err(channel),
+    };
+    let key = key(event);
+    queue.push(Job { payload, channel, event_id: event_id.map(String::from), correlation_id: correlation_id.map(String::from), key,
+        trace: trace::current(), cause: causes::current(), on_complete: None }, scheduling)
+        .map(|_| check_watermarks(queue.depth()))
+        .map_err(|job| job.channel)
+}
+
+fn serialize(event: &dyn Event) -> Option<String> {
+    implementation::serialize(event)
+        .inspect_err(|e: &RoundtripError| warn!(target: &common::format_target("AsyncDispatcher"),
+            "event {} can't be queued, dispatching synchronously: {}", common::redacted(event), e))
+        .ok()
+}
+
+fn key(event: &dyn Event) -> Option<String> {
+    let key_extractor = KEY_EXTRACTOR.lock().unwrap().clone();
+    key_extractor.and_then(|key_extractor| key_extractor(event))
+}
+
+/// Invokes high watermark callback once the queue depth reaches the high watermark, then low watermark callback once
+/// it drops below the low one (outside of the lock, so callbacks may emit or configure).
+fn check_watermarks(depth: usize) {
+    let mut watermarks = WATERMARKS.lock().unwrap();
+    let callback = match watermarks.as_mut() {
+        Some(watermarks) if !watermarks.above && depth >= watermarks.high => {
+            watermarks.above = true;
+            Arc::clone(&watermarks.on_high_watermark)
+        }
+        Some(watermarks) if watermarks.above && depth < watermarks.low => {
+            watermarks.above = false;
+            Arc::clone(&watermarks.on_low_watermark)
+        }
+        _ => return,
+    };
+    drop(watermarks);
+    info!(target: &common::format_target("AsyncDispatcher"), "queue watermark crossed at depth {}", depth);
+    callback(depth);
+}
+
+fn priority(channel: Option<&MessageChannel>) -> u8 {
+    channel.and_then(|channel| PRIORITIES.lock().unwrap().iter()
+        .find(|(channel_type, name, _)| *channel_type == channel.channel_type && *name == channel.name)
+        .map(|(_, _, priority)| *priority))
+        .unwrap_or_default()
+}
+
+fn work(queue: &JobQueue) {
+    while let Some(job) = queue.pop() {
+        check_watermarks(queue.depth());
+        let key = job.key.clone();
+        match serde_json::from_str::<Box<dyn Event>>(&job.payload) {
+            Ok(event) => {
+                let dispatched = panic::catch_unwind(AssertUnwindSafe(|| trace::with_current(job.trace, ||
+                    causes::with_current(job.cause, || implementation::with_correlation_id(job.correlation_id, || match job.on_complete {
+                        Some(on_complete) => on_complete(implementation::dispatch_reporting(&*event, job.channel, job.event_id.as_deref())),
+                        None => {
+                            implementation::dispatch(&*event, job.channel, job.event_id.as_deref());
+                        }
+                    })))));
+                if dispatched.is_err() {
+                    error!(target: &common::format_target("AsyncDispatcher"), "event {} dispatch failed", common::redacted(&*event));
+                }
+            }
+            Err(e) => error!(target: &common::format_target("AsyncDispatcher"),
+                "unable to deserialize queued event {}: {}", common::redacted_payload(job.event_id.as_deref(), job.payload.as_bytes()), e),
+        }
+        if let Some(key) = key {
+            queue.release(&key);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl JobQueue {
+    fn new(capacity: usize) -> Self {
+        JobQueue {
+            state: Mutex::new(JobQueueState { jobs: BinaryHeap::new(), sequence: 0, in_flight_keys: HashSet::new(), closed: false }),
+            capacity,
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    /// Enqueues job, blocking while the queue is full. Job is given back, if the queue is closed.
+    fn push(&self, job: Job, scheduling: Scheduling) -> Result<(), Box<Job>> {
+        let priority = match scheduling {
+            Scheduling::FIFO => 0,
+            Scheduling::PRIORITY => priority(job.channel.as_ref()),
+        };
+        let mut state = self.not_full
+            .wait_while(self.state.lock().unwrap(), |state| !state.closed && state.jobs.len() >= self.capacity)
+            .unwrap();
+        if state.closed {
+            return Err(Box::new(job));
+        }
+        let sequence = state.sequence;
+        state.sequence += 1;
+        state.jobs.push(QueuedJob { priority, sequence, job });
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Dequeues the highest priority job whose key isn't in flight, blocking while there's none. Returns `None` once
+    /// the queue is closed and drained. Key of the dequeued job stays in flight until released.
+    fn pop(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(queued) = state.take_next() {
+                if let Some(key) = &queued.job.key {
+                    state.in_flight_keys.insert(key.clone());
+                }
+                self.not_full.notify_one();
+                return Some(queued.job);
+            }
+            if state.closed && state.jobs.is_empty() {
+                return None;
+            }
+            state = self.not_empty.wait(state).unwrap();
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.state.lock().unwrap().jobs.len()
+    }
+
+    /// Releases key of dispatched job, so the next job with the key can be dequeued.
+    fn release(&self, key: &str) {
+        self.state.lock().unwrap().in_flight_keys.remove(key);
+        self.not_empty.notify_all();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+impl JobQueueState {
+    fn take_next(&mut self) -> Option<QueuedJob> {
+        let mut skipped = Vec::new();
+        let mut next = None;
+        while let Some(queued) = self.jobs.pop() {
+            match &queued.job.key {
+                Some(key) if self.in_flight_keys.contains(key) => skipped.push(queued),
+                _ => {
+                    next = Some(queued);
+                    break;
+                }
+            }
+        }
+        self.jobs.extend(skipped);
+        next
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence == other.sequence
+    }
+}
+
+impl Eq for QueuedJob {}
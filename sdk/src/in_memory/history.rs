@@ -0,0 +1,94 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use log::{info, warn};
+use crate::common;
+use crate::model::Event;
+use super::implementation::{self, MessageChannel};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Recently dispatched event, along with the channel it was emitted to.
+#[derive(Clone)]
+pub(super) struct Recorded {
+    pub(super) payload: String,
+    pub(super) channel: Option<MessageChannel>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(capacity: usize) {
+    info!(target: &common::format_target("EventHistory"), "setting up: [capacity:{}]", capacity);
+    let mut history = HISTORY.lock().unwrap();
+    history.capacity = capacity;
+    while history.events.len() > capacity {
+        history.events.pop_front();
+    }
+}
+
+pub(super) fn capacity() -> usize {
+    HISTORY.lock().unwrap().capacity
+}
+
+/// Records dispatched event, evicting the oldest one once the capacity is reached. Nothing is recorded (nor
+/// serialized) with zero capacity.
+pub(super) fn record(event: &dyn Event, channel: Option<&MessageChannel>) {
+    if HISTORY.lock().unwrap().capacity == 0 {
+        return;
+    }
+    let payload = match implementation::serialize(event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!(target: &common::format_target("EventHistory"), "event {} not recorded: {}", common::redacted(event), e);
+            return;
+        }
+    };
+    let mut history = HISTORY.lock().unwrap();
+    if history.capacity == 0 {
+        return;
+    }
+    while history.events.len() >= history.capacity {
+        history.events.pop_front();
+    }
+    history.events.push_back(Recorded { payload, channel: channel.cloned() });
+}
+
+/// Returns recorded events, the oldest first.
+pub(super) fn snapshot() -> Vec<Recorded> {
+    HISTORY.lock().unwrap().events.iter().cloned().collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static HISTORY: Mutex<History> = Mutex::new(History::new());
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct History {
+    capacity: usize,
+    events: VecDeque<Recorded>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl History {
+    const fn new() -> Self {
+        History {
+            capacity: 0,
+            events: VecDeque::new(),
+        }
+    }
+}
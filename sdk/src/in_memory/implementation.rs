@@ -2,12 +2,33 @@
 // Rust-Lang Libs/Eventure 2024
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
-use std::fmt::{Display, Formatter};
-use std::sync::Mutex;
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+use futures::channel::oneshot;
 use regex::Regex;
-use log::{debug, info};
-use crate::common;
-use crate::model::{Event, EventHandler};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use log::{debug, error, info, warn};
+use crate::common::{self, DedupStore};
+use crate::model::{Event, EventHandler, HandlerError, TransformHandler};
+use super::causes::Ancestry;
+use super::{causes, config_toml, dead_letters, dispatcher, history, match_cache, metrics, replies, sequencer, trace};
+use super::lifecycle::{self, LifecycleEvent, LifecycleEventKind, LIFECYCLE_CHANNEL};
+use super::match_cache::MatchCache;
+use super::metrics::Counts;
+use super::rate_limiter::{self, RateLimitMode};
+use super::scheduler::{self, ScheduleInfo};
+use super::trace::TraceContext;
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Public structs
@@ -21,21 +42,194 @@ use crate::model::{Event, EventHandler};
 ///
 /// let message_channel = in_memory::MessageChannel {
 ///         channel_type: in_memory::ChannelType::TOPIC,
-///         name: "Orders",
+///         name: "Orders".into(),
 /// };
 /// ```
+#[derive(Clone)]
 pub struct MessageChannel {
     pub channel_type: ChannelType,
-    pub name: &'static str,
+    pub name: Cow<'static, str>,
 }
 
 /// Channel type
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChannelType {
     TOPIC,
     QUEUE,
 }
 
+/// Order events of a batch are emitted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOrder {
+    /// Events are emitted in the batch order.
+    EMITTED,
+    /// Events are emitted in `Event::occurred_at` order. Events lacking timestamp keep their position in the batch.
+    OCCURRED,
+}
+
+/// In-Memory emit error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmitError {
+    /// Event rejected, since configured emit rate limit was exceeded.
+    RateLimited,
+    /// Event not emitted, since deduplication requires dedup store (see `set_dedup_store`), which isn't set.
+    DedupStoreNotSet,
+}
+
+/// In-Memory event handler registration error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationError {
+    /// JSONPath expression can't be compiled (see `register_jsonpath`), with the compilation error.
+    InvalidJsonPath { jsonpath: String, reason: String },
+    /// Channel name isn't a valid regex (see `try_message_channel`), with the compilation error.
+    InvalidChannelName { name: String, reason: String },
+}
+
+/// In-Memory request/reply error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestError {
+    /// No reply arrived within the timeout.
+    Timeout,
+    /// Message broker was shut down (see `shutdown`), while the request was waiting for reply.
+    Shutdown,
+    /// Request rejected, since configured emit rate limit was exceeded.
+    RateLimited,
+    /// Reply can't be passed back to the requester.
+    Roundtrip(RoundtripError),
+    /// Reply isn't of the type expected by the typed request (see `request_typed`).
+    UnexpectedReply { expected: &'static str, actual: String },
+}
+
+/// In-Memory emit loop, detected from the causation ancestry of an event (see `set_loop_detection`). The event is
+/// not dispatched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmitLoopDetected {
+    /// Depth of the event (number of events which led to it, the event included) exceeds the maximum depth.
+    MaxDepthExceeded { event_id: String, depth: usize },
+    /// Names of the events which led to the event (the event included) end with the cycle (of at least two names)
+    /// repeated twice.
+    NameCycle { event_id: String, cycle: Vec<String> },
+}
+
+/// Error of importing In-Memory message broker configuration from TOML (see `import_config_toml`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigImportError {
+    /// TOML can't be read at the line (or holds unknown table or key, or invalid value).
+    Malformed { line: usize, reason: String },
+    /// Async mode enabled (`[async]` table present) without the default message channel.
+    MissingMessageChannel,
+    /// Imported message broker configuration is not valid.
+    Invalid(ConfigurationError),
+}
+
+/// Error of the event `to_json`/`from_json` round-trip, required by features passing events across threads or time
+/// (async mode, async tasks, delayed emits, dead letters).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripError {
+    /// `Event::to_json` panicked (e.g. `todo!()` stub), with the panic message.
+    Panicked(String),
+    /// `Event::to_json` output can't be deserialized back (through `typetag`), with the deserialization error.
+    Malformed(String),
+}
+
+/// Error of scheduling In-Memory event emit (see `emit_after`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// Event can't be passed to the timer thread.
+    Roundtrip(RoundtripError),
+    /// Timer thread can't be started, with the error.
+    TimerUnavailable(String),
+}
+
+/// In-Memory event handler registry instance, independent of the global one (and of any other instance), e.g. per
+/// thread, per component or per test. Events are dispatched synchronously, on the calling thread; global broker
+/// features (async mode, rate limit, dead letters, lifecycle events, hooks) don't apply to it. Global functions
+/// (`register`, `emit`, ...) delegate to the default instance.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// struct OrderEventHandler {
+///     handled: Arc<AtomicUsize>,
+/// }
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         self.handled.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let (first, second) = (in_memory::LocalRegistry::new(), in_memory::LocalRegistry::new());
+/// let (first_handled, second_handled) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// first.register(channel(), OrderEventHandler { handled: Arc::clone(&first_handled) });
+/// second.register(channel(), OrderEventHandler { handled: Arc::clone(&second_handled) });
+///
+/// let order_created = OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") };
+/// first.emit(&order_created);
+/// first.emit_to_channel(&order_created, channel());
+///
+/// assert_eq!(first_handled.load(Ordering::SeqCst), 2);
+/// assert_eq!(second_handled.load(Ordering::SeqCst), 0);
+/// ```
+pub struct LocalRegistry {
+    registry: Mutex<EventHandlerRegistryImpl>,
+}
+
+/// Report of the In-Memory event dispatch, listing result of each handler the event was dispatched to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmitReport {
+    pub event_id: String,
+    /// Handler id along with its `EventHandler::try_handle` result, in dispatch order. Panicking handler is reported
+    /// as failed.
+    pub results: Vec<(String, Result<(), HandlerError>)>,
+}
+
 /// In-Memory message broker configuration.
 ///
 /// # Examples
@@ -45,17 +239,80 @@ pub enum ChannelType {
 ///
 /// let message_channel = in_memory::MessageChannel {
 ///         channel_type: in_memory::ChannelType::TOPIC,
-///         name: "Orders",
+///         name: "Orders".into(),
 /// };
 ///
 /// let configuration = in_memory::MessageBrokerConfiguration {
 ///     message_channel,
 ///     is_async: false,
+///     async_settings: in_memory::async_settings(1024, 1, true),
 /// };
 /// ```
 pub struct MessageBrokerConfiguration {
     pub message_channel: MessageChannel,
     pub is_async: bool,
+    pub async_settings: AsyncSettings,
+}
+
+/// In-Memory async mode settings, applied when message broker is configured as async.
+///
+/// # Examples
+///
+/// ```
+/// use eventure::in_memory;
+///
+/// let async_settings = in_memory::AsyncSettings {
+///     capacity: 1024,
+///     concurrency: 4,
+///     ordered: false,
+///     scheduling: in_memory::Scheduling::FIFO,
+/// };
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsyncSettings {
+    /// Maximum number of events queued for dispatch. Once the queue is full, emitting blocks (backpressure).
+    pub capacity: usize,
+    /// Number of dispatcher worker threads (pool size), i.e. how many events are handled concurrently.
+    pub concurrency: usize,
+    /// Whether events are dispatched in emission order. Requires single worker thread.
+    pub ordered: bool,
+    /// Order queued events are dispatched in.
+    pub scheduling: Scheduling,
+}
+
+/// Scheduling of the events queued in async mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scheduling {
+    /// Events are dispatched in emission order, regardless of their channel.
+    FIFO,
+    /// Events of higher priority channels (see `set_priority`) are always dispatched before queued events of lower
+    /// priority ones (strict priority). Events of the same priority are dispatched in emission order.
+    PRIORITY,
+}
+
+/// Behaviour once an event matches more handlers than the maximum fan-out (see `set_max_fanout`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FanoutMode {
+    /// Warning is logged, the event is delivered to all the matched handlers.
+    WARN,
+    /// Error is logged, the event is delivered to all the matched handlers.
+    STRICT,
+    /// Warning is logged, the event is delivered to the first (in registration order) `max_fanout` handlers only.
+    CAP,
+}
+
+/// In-Memory message broker configuration error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurationError {
+    /// Async queue capacity is zero, so no event could ever be emitted.
+    ZeroCapacity,
+    /// Async concurrency is zero, so no event could ever be dispatched.
+    ZeroConcurrency,
+    /// Ordered dispatch requested with more than one worker thread.
+    OrderedConcurrency(usize),
+    /// Default channel name isn't a valid regex, with the compilation error.
+    InvalidChannelName { name: String, reason: String },
 }
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
@@ -70,13 +327,108 @@ pub struct MessageBrokerConfiguration {
 ///
 /// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
 /// ```
-pub fn message_channel(channel_type: ChannelType, channel_name: &'static str) -> MessageChannel {
+///
+/// Channel names can be built at runtime as well:
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// let tenant = String::from("acme");
+/// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, format!("orders-{}", tenant));
+/// in_memory::register(handler_channel, OrderCreatedEventHandler);
+///
+/// let order_created = OrderCreated {
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+/// in_memory::emit_to_channel(&order_created, in_memory::message_channel(in_memory::ChannelType::TOPIC, format!("orders-{}", tenant)));
+/// in_memory::emit_to_channel(&order_created, in_memory::message_channel(in_memory::ChannelType::TOPIC, "orders-other"));
+///
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+/// ```
+pub fn message_channel(channel_type: ChannelType, channel_name: impl Into<Cow<'static, str>>) -> MessageChannel {
     MessageChannel {
         channel_type,
-        name: channel_name,
+        name: channel_name.into(),
     }
 }
 
+/// Creates In-Memory message channel for registering handlers, checking its name is a valid regex. Handlers registered
+/// (or brokers set up) with an invalid channel name built by `message_channel` aren't registered, with an error logged.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+/// use eventure::in_memory::{ChannelType, ConfigurationError, RegistrationError};
+///
+/// let tenant = "orders(";
+/// assert!(matches!(in_memory::try_message_channel(ChannelType::TOPIC, tenant),
+///     Err(RegistrationError::InvalidChannelName { .. })));
+/// assert!(in_memory::try_message_channel(ChannelType::TOPIC, regex::escape(tenant)).is_ok());
+///
+/// assert!(matches!(in_memory::setup(in_memory::configuration(ChannelType::TOPIC, tenant, false)),
+///     Err(ConfigurationError::InvalidChannelName { .. })));
+/// ```
+pub fn try_message_channel(channel_type: ChannelType, channel_name: impl Into<Cow<'static, str>>)
+                           -> Result<MessageChannel, RegistrationError> {
+    let message_channel = message_channel(channel_type, channel_name);
+    Regex::new(&message_channel.name)
+        .map_err(|reason| RegistrationError::InvalidChannelName { name: message_channel.name.to_string(), reason: reason.to_string() })?;
+    Ok(message_channel)
+}
+
 /// Creates In-Memory message broker configuration.
 ///
 /// # Examples
@@ -86,40 +438,71 @@ pub fn message_channel(channel_type: ChannelType, channel_name: &'static str) ->
 ///
 /// let configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false);
 /// ```
-pub fn configuration(channel_type: ChannelType, channel_name: &'static str, is_async: bool) -> MessageBrokerConfiguration {
+pub fn configuration(channel_type: ChannelType, channel_name: impl Into<Cow<'static, str>>, is_async: bool) -> MessageBrokerConfiguration {
     MessageBrokerConfiguration {
         message_channel: message_channel(channel_type, channel_name),
         is_async,
+        async_settings: async_settings(1024, 1, true),
+    }
+}
+
+/// Creates In-Memory async mode settings.
+///
+/// # Examples
+///
+/// ```
+/// use eventure::in_memory;
+///
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(256, 4, false);
+/// ```
+pub fn async_settings(capacity: usize, concurrency: usize, ordered: bool) -> AsyncSettings {
+    AsyncSettings {
+        capacity,
+        concurrency,
+        ordered,
+        scheduling: Scheduling::FIFO,
     }
 }
 
 /// Sets up In-Memory message broker configuration by passing MessageBrokerConfiguration instance.
+/// In async mode, emitted events are queued and dispatched by a pool of worker threads. Switching back to
+/// synchronous mode waits for all queued events to be dispatched.
 ///
 ///  # Examples
 /// ```
 /// use eventure::in_memory;
+/// use eventure::in_memory::ConfigurationError;
 ///
 /// let configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false);
-/// in_memory::setup(configuration);
+/// in_memory::setup(configuration).unwrap();
+///
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(256, 4, false);
+/// assert_eq!(in_memory::setup(configuration), Ok(()));
+///
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(0, 1, true);
+/// assert_eq!(in_memory::setup(configuration), Err(ConfigurationError::ZeroCapacity));
+///
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(256, 0, false);
+/// assert_eq!(in_memory::setup(configuration), Err(ConfigurationError::ZeroConcurrency));
+///
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(256, 4, true);
+/// assert_eq!(in_memory::setup(configuration), Err(ConfigurationError::OrderedConcurrency(4)));
 /// ```
-pub fn setup(configuration: MessageBrokerConfiguration) {
-    info!(target: &common::format_target("MessageBrokerConfiguration"), "setting up: {}",configuration);
-    BROKER_CONFIGURATION.lock().unwrap().update(MessageBrokerConfigurationInternal::from(configuration));
-}
-
-/// Registers In-Memory event handler.
 ///
-/// # Examples
+/// Dispatching events asynchronously:
 /// ```
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
 /// use serde::{Deserialize, Serialize};
 /// use eventure::{in_memory, model};
-/// use model::Event;
-///
-/// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
 ///
-/// struct OrderCreatedEventHandler;
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
 ///
 /// #[derive(Serialize, Deserialize)]
 /// struct OrderCreated {
@@ -146,14 +529,12 @@ pub fn setup(configuration: MessageBrokerConfiguration) {
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         serde_json::to_string(&self).unwrap()
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
-///     event_id: String::from("event_id"),
-///     customer_id: String::from("customer_id"),
-/// };
+/// struct OrderCreatedEventHandler;
 ///
 /// impl Display for OrderCreatedEventHandler {
 ///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -163,10 +544,8 @@ pub fn setup(configuration: MessageBrokerConfiguration) {
 ///
 /// impl model::EventHandler for OrderCreatedEventHandler {
 ///     fn handle(&self, event: &(dyn model::Event + '_)) {
-///         match event.as_any().downcast_ref::<OrderCreated>() {
-///             Some(order_create) => self.handle(order_create),
-///             None => println!("{}: not handling {}", "OrderCreatedEventHandler", event)
-///         }
+///         assert!(event.as_any().downcast_ref::<OrderCreated>().is_some());
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
 ///     }
 ///
 ///     fn id(&self) -> String {
@@ -174,30 +553,168 @@ pub fn setup(configuration: MessageBrokerConfiguration) {
 ///     }
 /// }
 ///
-/// impl OrderCreatedEventHandler {
-///     fn handle(&self, event: &OrderCreated) {
-///         println!("{}: handling {}","OrderCreatedEventHandler", event)
-///     }
+/// let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+/// configuration.async_settings = in_memory::async_settings(16, 4, false);
+/// in_memory::setup(configuration).unwrap();
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order"), OrderCreatedEventHandler);
+/// for index in 0..100 {
+///     in_memory::emit(&OrderCreated { event_id: index.to_string(), customer_id: String::from("customer_id") });
 /// }
 ///
-/// let order_created_handler = OrderCreatedEventHandler;
-/// in_memory::register(handler_channel, order_created_handler);
+/// // switching back to synchronous mode waits for queued events
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 100);
 /// ```
-pub fn register(message_channel: MessageChannel, event_handler: impl EventHandler + Send + 'static) {
-    HANDLER_REGISTRY.lock().unwrap().register(
-        MessageChannelInternal::from(message_channel),
-        Box::new(event_handler));
+pub fn setup(configuration: MessageBrokerConfiguration) -> Result<(), ConfigurationError> {
+    info!(target: &common::format_target("MessageBrokerConfiguration"), "setting up: {}",configuration);
+    configuration.validate()?;
+    let description = configuration.to_string();
+    let is_async = configuration.is_async;
+    let async_settings = configuration.async_settings.clone();
+    let internal = MessageBrokerConfigurationInternal::try_from(configuration)?;
+    match is_async {
+        true => dispatcher::start(&async_settings),
+        false => {
+            dispatcher::stop();
+        }
+    }
+    BROKER_CONFIGURATION.lock().unwrap().update(internal);
+    lifecycle::emit(LifecycleEventKind::CONFIGURED, description);
+    Ok(())
 }
 
-/// Unregisters In-Memory event handler.
+/// Exports In-Memory message broker configuration as TOML (to be checked into version control, and loaded with
+/// `import_config_toml` at startup): the default message channel (if set up), async settings (in async mode only),
+/// limits (rate limit, max fan-out, loop detection, match cache, history and dead letter capacities, total order) and
+/// channel priorities. Handlers and callbacks (being code) aren't exported.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+/// use eventure::in_memory::{ChannelType, ConfigImportError, FanoutMode, RateLimitMode, Scheduling};
+///
+/// let mut configuration = in_memory::configuration(ChannelType::QUEUE, "Orders.*", true);
+/// configuration.async_settings = in_memory::async_settings(256, 2, false);
+/// configuration.async_settings.scheduling = Scheduling::PRIORITY;
+/// in_memory::setup(configuration).unwrap();
+/// in_memory::set_rate_limit(100, RateLimitMode::REJECT);
+/// in_memory::set_max_fanout(8, FanoutMode::CAP);
+/// in_memory::set_loop_detection(Some(16), true);
+/// in_memory::set_history_capacity(32);
+/// in_memory::set_priority(in_memory::message_channel(ChannelType::QUEUE, "Payments"), 5);
+///
+/// let exported = in_memory::export_config_toml();
+/// assert!(exported.starts_with("[message_channel]\nchannel_type = \"QUEUE\"\nname = \"Orders.*\"\n"));
+/// assert!(exported.contains("[limits]\nrate_limit = 100\nrate_limit_mode = \"REJECT\"\nmax_fanout = 8\n"));
+///
+/// // importing empty configuration resets the defaults
+/// in_memory::import_config_toml("").unwrap();
+/// assert!(!in_memory::export_config_toml().contains("[message_channel]"));
+///
+/// in_memory::import_config_toml(&exported).unwrap();
+/// assert_eq!(in_memory::export_config_toml(), exported);
+///
+/// let imported = in_memory::import_config_toml("[limits]\nmax_fanout = \"many\"");
+/// assert!(matches!(imported, Err(ConfigImportError::Malformed { line: 2, .. })));
+/// ```
+pub fn export_config_toml() -> String {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let message_channel = &configuration.message_channel;
+    let (loop_max_depth, loop_detect_name_cycles) = causes::loop_detection();
+    let (dead_letter_capacity, dead_letter_budget) = dead_letters::settings();
+    let rate_limit = rate_limiter::settings();
+    let max_fanout = *MAX_FANOUT.lock().unwrap();
+    let settings = config_toml::Settings {
+        message_channel: message_channel.name_regex.as_ref()
+            .map(|regex| config_toml::ChannelSettings {
+                channel_type: message_channel.channel_type.clone(),
+                name: String::from(regex.as_str()),
+            }),
+        async_settings: dispatcher::settings(),
+        limits: config_toml::Limits {
+            rate_limit: rate_limit.map(|(events_per_second, _)| events_per_second),
+            rate_limit_mode: rate_limit.map(|(_, mode)| mode),
+            max_fanout: max_fanout.map(|(max_fanout, _)| max_fanout),
+            fanout_mode: max_fanout.map(|(_, mode)| mode),
+            loop_max_depth: loop_max_depth.unwrap_or(0),
+            loop_detect_name_cycles,
+            match_cache_capacity: match_cache::capacity(),
+            history_capacity: history::capacity(),
+            dead_letter_capacity,
+            dead_letter_budget,
+            total_order: sequencer::is_enabled(),
+        },
+        priorities: dispatcher::priorities().into_iter()
+            .map(|(channel_type, name, priority)| config_toml::Priority { channel_type, name, priority })
+            .collect(),
+    };
+    drop(configuration);
+    config_toml::to_toml(&settings)
+}
+
+/// Imports In-Memory message broker configuration from TOML written by `export_config_toml`, setting up the message
+/// broker (see `setup`) and replacing all the exported settings. The message broker is set up as async if the
+/// `[async]` table is present. Settings missing in the TOML are reset to their defaults (so importing empty TOML
+/// resets the configuration). Nothing is changed, if the TOML is malformed.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::import_config_toml(r#"
+/// [message_channel]
+/// channel_type = "TOPIC"
+/// name = "Orders"
+///
+/// [limits]
+/// history_capacity = 100  # replayed to late handlers
+/// "#).unwrap();
+///
+/// assert!(in_memory::export_config_toml().contains("history_capacity = 100\n"));
+/// ```
+pub fn import_config_toml(toml: &str) -> Result<(), ConfigImportError> {
+    let settings = config_toml::from_toml(toml)?;
+    match settings.message_channel {
+        Some(channel) => {
+            let configuration = MessageBrokerConfiguration {
+                message_channel: message_channel(channel.channel_type, channel.name),
+                is_async: settings.async_settings.is_some(),
+                async_settings: settings.async_settings.unwrap_or_default(),
+            };
+            setup(configuration).map_err(ConfigImportError::Invalid)?;
+        }
+        None if settings.async_settings.is_some() => return Err(ConfigImportError::MissingMessageChannel),
+        None => {
+            dispatcher::stop();
+            *BROKER_CONFIGURATION.lock().unwrap() = MessageBrokerConfigurationInternal::new();
+        }
+    }
+    let limits = settings.limits;
+    rate_limiter::configure(limits.rate_limit.unwrap_or(0), limits.rate_limit_mode.unwrap_or(RateLimitMode::BLOCK));
+    *MAX_FANOUT.lock().unwrap() = limits.max_fanout.map(|max_fanout| (max_fanout, limits.fanout_mode.unwrap_or(FanoutMode::WARN)));
+    causes::configure(Some(limits.loop_max_depth).filter(|max_depth| *max_depth > 0), limits.loop_detect_name_cycles);
+    match_cache::configure(limits.match_cache_capacity);
+    history::configure(limits.history_capacity);
+    dead_letters::configure(limits.dead_letter_capacity);
+    dead_letters::configure_budget(limits.dead_letter_budget);
+    sequencer::configure(limits.total_order);
+    dispatcher::replace_priorities(settings.priorities.into_iter()
+        .map(|priority| (priority.channel_type, priority.name, priority.priority))
+        .collect());
+    info!(target: &common::format_target("MessageBrokerConfiguration"), "configuration imported from TOML");
+    Ok(())
+}
+
+/// Registers In-Memory event handler.
 ///
 /// # Examples
 /// ```
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
 /// use serde::{Deserialize, Serialize};
-///
 /// use eventure::{in_memory, model};
+/// use model::Event;
 ///
 /// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
 ///
@@ -264,72 +781,4361 @@ pub fn register(message_channel: MessageChannel, event_handler: impl EventHandle
 ///
 /// let order_created_handler = OrderCreatedEventHandler;
 /// in_memory::register(handler_channel, order_created_handler);
-///
-/// let order_created_handler = OrderCreatedEventHandler;
-/// in_memory::unregister(order_created_handler);
 /// ```
-pub fn unregister(event_handler: impl EventHandler + Send + 'static) {
-    HANDLER_REGISTRY.lock().unwrap().unregister(Box::new(event_handler));
-}
-
-/// Emits In-Memory event without specifying message channel.
 ///
-/// # Examples
+/// Handlers of channels whose name isn't a valid regex aren't registered (the error is logged), nor reported on the
+/// lifecycle channel:
 /// ```
-/// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
-/// use serde::{Deserialize, Serialize};
+/// use std::sync::Mutex;
 /// use eventure::{in_memory, model};
+/// use eventure::in_memory::{LifecycleEvent, LifecycleEventKind};
 ///
-/// #[derive(Serialize, Deserialize)]
-/// struct OrderCreated {
-///     event_id: String,
-///     customer_id: String,
-/// }
+/// static REGISTERED: Mutex<Vec<String>> = Mutex::new(Vec::new());
 ///
-/// impl Display for OrderCreated {
+/// struct LifecycleMonitor;
+///
+/// impl Display for LifecycleMonitor {
 ///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-///         write!(f, "{} event with id {}",
-///                "OrderCreated", self.event_id)
+///         write!(f, "{}", "LifecycleMonitor")
 ///     }
 /// }
 ///
-/// #[typetag::serde]
-/// impl model::Event for OrderCreated {
-///     fn id(&self) -> &str {
-///         &self.event_id[..]
+/// impl model::EventHandler for LifecycleMonitor {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if let Some(lifecycle_event) = event.as_any().downcast_ref::<LifecycleEvent>() {
+///             if lifecycle_event.kind == LifecycleEventKind::REGISTERED {
+///                 REGISTERED.lock().unwrap().push(lifecycle_event.subject.clone());
+///             }
+///         }
 ///     }
-///     fn name(&self) -> &str {
+///
+///     fn id(&self) -> String {
+///         String::from("LifecycleMonitor")
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, in_memory::LIFECYCLE_CHANNEL), LifecycleMonitor);
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order(s"), OrderEventHandler);
+/// assert!(!REGISTERED.lock().unwrap().contains(&String::from("OrderEventHandler")));
+/// ```
+pub fn register(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static) {
+    let handler_id = event_handler.id();
+    let Some(handler_channel) = handler_channel(message_channel, &handler_id) else { return };
+    DEFAULT_REGISTRY.registry.lock().unwrap().register(handler_channel, Box::new(event_handler), None, None);
+    lifecycle::emit(LifecycleEventKind::REGISTERED, handler_id);
+}
+
+/// Registers In-Memory event handler running as a dedicated tokio task (requires `tokio` feature, enabled by default).
+/// Matching events are forwarded (serialized via `Event::to_json`) to the task, which handles them one by one, in
+/// order. The task finishes once the handler is unregistered (or broker shut down) and all forwarded events are
+/// handled, so returned handle can be awaited. The handler is invoked on the runtime's worker thread, so it shouldn't
+/// block for long.
+///
+/// # Panics
+/// Panics, if called outside of tokio runtime.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
+///     let handle = in_memory::register_async_task(handler_channel, OrderCreatedEventHandler);
+///
+///     in_memory::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") });
+///     in_memory::emit(&OrderCreated { event_id: String::from("2"), customer_id: String::from("customer_id") });
+///
+///     // shutdown unregisters the handler, so its task finishes once the forwarded events are handled
+///     in_memory::shutdown();
+///     handle.await.unwrap();
+///
+///     assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2"]);
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn register_async_task(message_channel: MessageChannel, event_handler: impl EventHandler + Send + 'static) -> tokio::task::JoinHandle<()> {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let forwarder = TaskForwarder {
+        id: event_handler.id(),
+        description: event_handler.to_string(),
+        sender,
+    };
+    let handle = tokio::spawn(async move {
+        while let Some(payload) = receiver.recv().await {
+            match serde_json::from_str::<Box<dyn Event>>(&payload) {
+                Ok(event) => handle_with_middleware(&event_handler, &*event),
+                Err(e) => error!(target: &common::format_target("EventHandlerRegistry"),
+                    "unable to deserialize forwarded event {}: {}", common::redacted_payload(None, payload.as_bytes()), e),
+            }
+        }
+        info!(target: &common::format_target("EventHandlerRegistry"), "event handler task finished: {}", event_handler);
+    });
+    register(message_channel, forwarder);
+    handle
+}
+
+/// Registers In-Memory event handler, invoked only for the events matched by the JSONPath expression. Each event
+/// dispatched to the handler is serialized via `Event::to_json` and the expression evaluated against it; the event
+/// matches, if the expression selects at least one truthy value (neither `null` nor `false`). Events not matched
+/// aren't dispatched to the handler, so the ones not matched by any handler are dead-lettered (see
+/// `set_dead_letter_capacity`). Returns an error, if the JSONPath expression is invalid.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct VipOrderEventHandler;
+///
+/// impl Display for VipOrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "VipOrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for VipOrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("VipOrderEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dead_letter_capacity(16);
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// assert!(matches!(in_memory::register_jsonpath(channel(), VipOrderEventHandler, "$[?(@.customer_id=="),
+///     Err(in_memory::RegistrationError::InvalidJsonPath { .. })));
+/// in_memory::register_jsonpath(channel(), VipOrderEventHandler, r#"$[?(@.customer_id=="vip")]"#).unwrap();
+///
+/// in_memory::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("vip") });
+/// in_memory::emit(&OrderCreated { event_id: String::from("2"), customer_id: String::from("regular") });
+/// in_memory::emit(&OrderCreated { event_id: String::from("3"), customer_id: String::from("vip") });
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "3"]);
+///
+/// // event not matched by any handler is dead-lettered
+/// in_memory::register_jsonpath(channel(), VipOrderEventHandler, r#"$[?(@.customer_id=="regular")]"#).unwrap();
+/// assert_eq!(in_memory::replay_dead_letters(), 1);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "3", "2"]);
+/// ```
+pub fn register_jsonpath(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static,
+                         jsonpath: &str) -> Result<(), RegistrationError> {
+    let compiled = jsonpath_lib::Compiled::compile(jsonpath)
+        .map_err(|reason| RegistrationError::InvalidJsonPath { jsonpath: String::from(jsonpath), reason })?;
+    let handler_channel = MessageChannelInternal::try_from(message_channel)?;
+    let handler_id = event_handler.id();
+    DEFAULT_REGISTRY.registry.lock().unwrap().register(
+        handler_channel,
+        Box::new(event_handler),
+        None,
+        Some(Arc::new(JsonPathFilter { jsonpath: String::from(jsonpath), compiled })));
+    lifecycle::emit(LifecycleEventKind::REGISTERED, handler_id);
+    Ok(())
+}
+
+/// Registers In-Memory event handler along with its own error callback, invoked with the event and the error whenever
+/// the handler's `try_handle` fails. The handler is dispatched via `try_handle` (instead of `handle`), so that failures
+/// get reported; `emit_with_callback`/`emit_until_err` still receive the error as well.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static FAILURES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PaymentReceived {
+///     event_id: String,
+///     amount: u32,
+/// }
+///
+/// impl Display for PaymentReceived {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "PaymentReceived", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PaymentReceived {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PaymentReceived"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PaymentEventHandler;
+///
+/// impl Display for PaymentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PaymentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PaymentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         let _ = self.try_handle(event);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PaymentEventHandler")
+///     }
+///
+///     fn try_handle(&self, event: &dyn model::Event) -> Result<(), model::HandlerError> {
+///         match event.as_any().downcast_ref::<PaymentReceived>() {
+///             Some(payment) if payment.amount == 0 => Err(model::HandlerError::new("empty payment")),
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments");
+/// in_memory::register_with_error_handler(channel, PaymentEventHandler, |event, error| {
+///     FAILURES.lock().unwrap().push((event.id().to_string(), error.to_string()));
+/// });
+///
+/// in_memory::emit(&PaymentReceived { event_id: String::from("1"), amount: 100 });
+/// in_memory::emit(&PaymentReceived { event_id: String::from("2"), amount: 0 });
+///
+/// assert_eq!(*FAILURES.lock().unwrap(), vec![(String::from("2"), String::from("empty payment"))]);
+/// ```
+pub fn register_with_error_handler(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static,
+                                   on_error: impl Fn(&dyn Event, &HandlerError) + Send + Sync + 'static) {
+    register(message_channel, ErrorCallbackHandler {
+        handler: Box::new(event_handler),
+        on_error: Box::new(on_error),
+    });
+}
+
+/// Registers In-Memory event handler, limiting its concurrent invocations (e.g. for a handler wrapping a connection
+/// pool of given size). Invocations wait for a permit of the handler's semaphore while `max` invocations of it are in
+/// flight, blocking the dispatching thread (so in async mode, the worker waits, and the queue fills up as usual).
+/// Invocation from a thread holding a permit already (e.g. the handler emitting event to itself) fails instead of
+/// waiting, which would deadlock. Limit of zero is treated as one.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// static STORED: AtomicUsize = AtomicUsize::new(0);
+/// static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+/// static PAID: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct NotificationEventHandler;
+///
+/// impl Display for NotificationEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "NotificationEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for NotificationEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         NOTIFIED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("NotificationEventHandler")
+///     }
+/// }
+///
+/// // handler holding a single database connection
+/// struct StoringEventHandler;
+///
+/// impl Display for StoringEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "StoringEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for StoringEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+///         MAX_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+///         thread::sleep(Duration::from_millis(50));
+///         IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+///         STORED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("StoringEventHandler")
+///     }
+/// }
+///
+/// // handler emitting follow-up event to itself
+/// struct PaymentEventHandler;
+///
+/// impl Display for PaymentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PaymentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PaymentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         PAID.lock().unwrap().push(format!("{} started", event.id()));
+///         if event.id() == "payment" {
+///             let refund = OrderCreated { event_id: String::from("refund") };
+///             in_memory::emit_to_channel(&refund, in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"));
+///         }
+///         PAID.lock().unwrap().push(format!("{} finished", event.id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PaymentEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+///     configuration.async_settings = in_memory::async_settings(64, 4, false);
+///     in_memory::setup(configuration).unwrap();
+///     let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+///     in_memory::register(channel(), NotificationEventHandler);
+///     in_memory::register_with_max_concurrency(channel(), StoringEventHandler, 1);
+///
+///     for index in 0..6 {
+///         in_memory::emit_to_channel(&OrderCreated { event_id: index.to_string() }, channel());
+///     }
+///
+///     // the other handler is invoked before the limited one waits for the permit
+///     while NOTIFIED.load(Ordering::SeqCst) < 4 {
+///         tokio::time::sleep(Duration::from_millis(1)).await;
+///     }
+///     assert!(STORED.load(Ordering::SeqCst) < 6);
+///
+///     // setting up again waits for queued events to be dispatched
+///     in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+///     assert_eq!(STORED.load(Ordering::SeqCst), 6);
+///     assert_eq!(NOTIFIED.load(Ordering::SeqCst), 6);
+///     assert_eq!(MAX_IN_FLIGHT.load(Ordering::SeqCst), 1);
+///
+///     // the event emitted by the handler to itself fails, as the emitting invocation holds the only permit
+///     in_memory::register_with_max_concurrency(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"), PaymentEventHandler, 1);
+///     in_memory::emit_to_channel(&OrderCreated { event_id: String::from("payment") },
+///         in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"));
+///     assert_eq!(*PAID.lock().unwrap(), vec!["payment started", "payment finished"]);
+/// }
+/// ```
+pub fn register_with_max_concurrency(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static,
+                                     max: usize) {
+    register(message_channel, ConcurrencyLimitedHandler {
+        handler: Box::new(event_handler),
+        max: max.max(1),
+        holders: Mutex::new(Vec::new()),
+        released: Condvar::new(),
+    });
+}
+
+/// Registers pipeline of In-Memory event handlers: each event matching the channel passes through the stages in the
+/// given order, on the dispatching thread, each stage receiving the event returned by the previous one (see
+/// `model::TransformHandler`). The event returned by the last stage is dropped. The pipeline counts as a single
+/// handler, identified by its stage ids.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static SHIPPED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Clone, Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+///     customer_tier: Option<String>,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// struct EnrichingStage;
+///
+/// impl Display for EnrichingStage {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "EnrichingStage")
+///     }
+/// }
+///
+/// impl model::TransformHandler for EnrichingStage {
+///     fn transform(&self, event: &dyn model::Event) -> Option<Box<dyn model::Event>> {
+///         let order_created = event.as_any().downcast_ref::<OrderCreated>()?;
+///         let mut enriched = order_created.clone();
+///         enriched.customer_tier = Some(String::from(if order_created.customer_id == "customer-1" { "gold" } else { "basic" }));
+///         Some(Box::new(enriched))
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("EnrichingStage")
+///     }
+/// }
+///
+/// struct ShippingStage;
+///
+/// impl Display for ShippingStage {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "ShippingStage")
+///     }
+/// }
+///
+/// impl model::TransformHandler for ShippingStage {
+///     fn transform(&self, event: &dyn model::Event) -> Option<Box<dyn model::Event>> {
+///         if let Some(order_created) = event.as_any().downcast_ref::<OrderCreated>() {
+///             let tier = order_created.customer_tier.clone().unwrap_or_default();
+///             SHIPPED.lock().unwrap().push(format!("{}:{}", order_created.event_id, tier));
+///         }
+///         None
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("ShippingStage")
+///     }
+/// }
+///
+/// in_memory::register_pipeline(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"),
+///                              vec![Box::new(EnrichingStage), Box::new(ShippingStage)]);
+///
+/// let order_created = |event_id: &str, customer_id: &str| OrderCreated {
+///     event_id: String::from(event_id),
+///     customer_id: String::from(customer_id),
+///     customer_tier: None,
+/// };
+/// in_memory::emit(&order_created("1", "customer-1"));
+/// in_memory::emit(&order_created("2", "customer-2"));
+///
+/// assert_eq!(*SHIPPED.lock().unwrap(), vec!["1:gold", "2:basic"]);
+/// ```
+pub fn register_pipeline(message_channel: MessageChannel, stages: Vec<Box<dyn TransformHandler + Send + Sync>>) {
+    register(message_channel, PipelineHandler { stages });
+}
+
+/// Registers In-Memory event handler receiving the snapshot of recent events first: recorded events (see
+/// `set_history_capacity`) matching the channel are replayed to the handler, on the calling thread, before this
+/// function returns. New events are delivered as usual.
+///
+/// There's no gap between the snapshot and the new events: events dispatched concurrently with the registration are
+/// either in the snapshot, or delivered as new ones. An event can be both though (so handlers should tolerate
+/// duplicates, e.g. by event id), and new events dispatched on other threads can arrive while the snapshot is being
+/// replayed.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceChanged {
+///     event_id: String,
+///     price: u64,
+/// }
+///
+/// impl Display for PriceChanged {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "PriceChanged", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceChanged {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceChanged"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PriceEventHandler;
+///
+/// impl Display for PriceEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PriceEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PriceEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PriceEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_history_capacity(16);
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Prices");
+/// for index in 1..=3 {
+///     in_memory::emit_to_channel(&PriceChanged { event_id: index.to_string(), price: index * 100 }, channel());
+/// }
+/// in_memory::emit_to_channel(&PriceChanged { event_id: String::from("other"), price: 0 },
+///                            in_memory::message_channel(in_memory::ChannelType::TOPIC, "Stock"));
+///
+/// in_memory::register_with_snapshot(channel(), PriceEventHandler);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3"]);
+///
+/// in_memory::emit_to_channel(&PriceChanged { event_id: String::from("4"), price: 400 }, channel());
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3", "4"]);
+/// ```
+pub fn register_with_snapshot(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static) {
+    let handler_id = event_handler.id();
+    let Some(handler_channel) = handler_channel(message_channel.clone(), &handler_id) else { return };
+    let event_handler: Arc<dyn EventHandler + Send + Sync> = Arc::new(event_handler);
+    let snapshot = {
+        // events are recorded before the registry is read, so the snapshot and the registration are consistent
+        let mut registry = DEFAULT_REGISTRY.registry.lock().unwrap();
+        let snapshot = history::snapshot();
+        registry.register(handler_channel.clone(), Box::new(SharedHandler(Arc::clone(&event_handler))), None, None);
+        snapshot
+    };
+    lifecycle::emit(LifecycleEventKind::REGISTERED, handler_id);
+
+    let matching: Vec<_> = snapshot.into_iter()
+        .filter(|recorded| recorded.channel.as_ref().is_none_or(|channel| handler_channel.matches(channel)))
+        .collect();
+    info!(target: &common::format_target("EventHandlerRegistry"), "replaying {} recorded event(s) to {}", matching.len(), event_handler);
+    for recorded in matching {
+        match serde_json::from_str::<Box<dyn Event>>(&recorded.payload) {
+            Ok(event) => handle_with_middleware(&*event_handler, &*event),
+            Err(e) => error!(target: &common::format_target("EventHandlerRegistry"),
+                "unable to deserialize recorded event {}: {}", common::redacted_payload(None, recorded.payload.as_bytes()), e),
+        }
+    }
+}
+
+/// Registers In-Memory event handler as a member of the named handler group. Groups are enabled by default and can be
+/// disabled (their handlers are skipped in dispatch, while staying registered), enabled again or unregistered as a whole.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// struct OrderEventHandler {
+///     id: String,
+/// }
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.id)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(format!("{}@{}", event.id(), self.id));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from(&self.id)
+///     }
+/// }
+///
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// in_memory::register_in_group("beta", channel(), OrderEventHandler { id: String::from("BetaShipping") });
+/// in_memory::register_in_group("beta", channel(), OrderEventHandler { id: String::from("BetaBilling") });
+///
+/// in_memory::disable_group("beta");
+/// in_memory::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") });
+/// assert!(HANDLED.lock().unwrap().is_empty());
+///
+/// in_memory::enable_group("beta");
+/// in_memory::emit(&OrderCreated { event_id: String::from("2"), customer_id: String::from("customer_id") });
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["2@BetaShipping", "2@BetaBilling"]);
+///
+/// in_memory::unregister_group("beta");
+/// in_memory::emit(&OrderCreated { event_id: String::from("3"), customer_id: String::from("customer_id") });
+/// assert_eq!(HANDLED.lock().unwrap().len(), 2);
+/// ```
+pub fn register_in_group(group: &str, message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static) {
+    let handler_id = event_handler.id();
+    let Some(handler_channel) = handler_channel(message_channel, &handler_id) else { return };
+    DEFAULT_REGISTRY.registry.lock().unwrap().register(
+        handler_channel,
+        Box::new(event_handler),
+        Some(String::from(group)),
+        None);
+    lifecycle::emit(LifecycleEventKind::REGISTERED, handler_id);
+}
+
+/// Enables In-Memory handler group, so its handlers are dispatched events again.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::enable_group("beta");
+/// ```
+pub fn enable_group(group: &str) {
+    DEFAULT_REGISTRY.registry.lock().unwrap().set_group_enabled(group, true);
+}
+
+/// Disables In-Memory handler group, so its handlers are skipped in dispatch (without being unregistered).
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::disable_group("beta");
+/// ```
+pub fn disable_group(group: &str) {
+    DEFAULT_REGISTRY.registry.lock().unwrap().set_group_enabled(group, false);
+}
+
+/// Unregisters all handlers of the In-Memory handler group.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::unregister_group("beta");
+/// ```
+pub fn unregister_group(group: &str) {
+    let unregistered = DEFAULT_REGISTRY.registry.lock().unwrap().unregister_group(group);
+    unregistered.into_iter().for_each(|handler_id| lifecycle::emit(LifecycleEventKind::UNREGISTERED, handler_id));
+}
+
+/// Registers In-Memory event handler, replacing already registered handler of the same id (at its position).
+/// Replacement is graceful: events dispatched from now on go to the new handler, while in-flight invocations of the
+/// old one (in async mode, or on other threads) are allowed to complete; the old handler is dropped once the last
+/// of them completes.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static STARTED: AtomicBool = AtomicBool::new(false);
+/// static RELEASED: AtomicBool = AtomicBool::new(false);
+/// static DROPPED: AtomicBool = AtomicBool::new(false);
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler {
+///     version: u8,
+/// }
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} v{}", "OrderEventHandler", self.version)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if self.version == 1 {
+///             // slow handler, still in-flight when replaced
+///             STARTED.store(true, Ordering::SeqCst);
+///             while !RELEASED.load(Ordering::SeqCst) {
+///                 thread::sleep(Duration::from_millis(10));
+///             }
+///         }
+///         HANDLED.lock().unwrap().push(format!("{}@v{}", event.id(), self.version));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// impl Drop for OrderEventHandler {
+///     fn drop(&mut self) {
+///         if self.version == 1 {
+///             DROPPED.store(true, Ordering::SeqCst);
+///         }
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+///     configuration.async_settings = in_memory::async_settings(16, 2, false);
+///     in_memory::setup(configuration).unwrap();
+///     let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+///     in_memory::register(channel(), OrderEventHandler { version: 1 });
+///
+///     in_memory::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") });
+///     while !STARTED.load(Ordering::SeqCst) {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///     }
+///
+///     in_memory::register_or_replace(channel(), OrderEventHandler { version: 2 });
+///     in_memory::emit(&OrderCreated { event_id: String::from("2"), customer_id: String::from("customer_id") });
+///     while HANDLED.lock().unwrap().is_empty() {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///     }
+///     assert_eq!(*HANDLED.lock().unwrap(), vec!["2@v2"]);
+///     assert!(!DROPPED.load(Ordering::SeqCst));
+///
+///     RELEASED.store(true, Ordering::SeqCst);
+///     // setting up again waits for queued (and in-flight) events to be dispatched
+///     in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+///     assert_eq!(*HANDLED.lock().unwrap(), vec!["2@v2", "1@v1"]);
+///     assert!(DROPPED.load(Ordering::SeqCst));
+/// }
+/// ```
+pub fn register_or_replace(message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static) {
+    let handler_id = event_handler.id();
+    let Some(handler_channel) = handler_channel(message_channel, &handler_id) else { return };
+    let replaced = DEFAULT_REGISTRY.registry.lock().unwrap().replace(
+        handler_channel,
+        Box::new(event_handler));
+    if replaced {
+        lifecycle::emit(LifecycleEventKind::UNREGISTERED, handler_id.clone());
+    }
+    lifecycle::emit(LifecycleEventKind::REGISTERED, handler_id);
+}
+
+/// Unregisters In-Memory event handler.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+///
+/// use eventure::{in_memory, model};
+///
+/// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
+///
+/// struct OrderCreatedEventHandler;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         match event.as_any().downcast_ref::<OrderCreated>() {
+///             Some(order_create) => self.handle(order_create),
+///             None => println!("{}: not handling {}", "OrderCreatedEventHandler", event)
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// impl OrderCreatedEventHandler {
+///     fn handle(&self, event: &OrderCreated) {
+///         println!("{}: handling {}","OrderCreatedEventHandler", event)
+///     }
+/// }
+///
+/// let order_created_handler = OrderCreatedEventHandler;
+/// in_memory::register(handler_channel, order_created_handler);
+///
+/// let order_created_handler = OrderCreatedEventHandler;
+/// in_memory::unregister(order_created_handler);
+/// ```
+pub fn unregister(event_handler: impl EventHandler + Send + 'static) {
+    let handler_id = event_handler.id();
+    let removed = DEFAULT_REGISTRY.registry.lock().unwrap().unregister(Box::new(event_handler));
+    if removed {
+        lifecycle::emit(LifecycleEventKind::UNREGISTERED, handler_id);
+    }
+}
+
+/// Shuts In-Memory message broker down: drops pending scheduled emits (see `emit_after`), waits for queued (async)
+/// events to be dispatched, fails requests still waiting for reply (see `request`), emits shutdown lifecycle event and
+/// unregisters all event handlers.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::{LifecycleEvent, LifecycleEventKind};
+///
+/// static LIFECYCLE: Mutex<Vec<(LifecycleEventKind, String)>> = Mutex::new(Vec::new());
+///
+/// struct LifecycleMonitor;
+///
+/// impl Display for LifecycleMonitor {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "LifecycleMonitor")
+///     }
+/// }
+///
+/// impl model::EventHandler for LifecycleMonitor {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if let Some(lifecycle_event) = event.as_any().downcast_ref::<LifecycleEvent>() {
+///             LIFECYCLE.lock().unwrap().push((lifecycle_event.kind, lifecycle_event.subject.clone()));
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("LifecycleMonitor")
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderCreatedEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, in_memory::LIFECYCLE_CHANNEL), LifecycleMonitor);
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderCreatedEventHandler);
+/// in_memory::unregister(OrderCreatedEventHandler);
+/// in_memory::shutdown();
+///
+/// let lifecycle = LIFECYCLE.lock().unwrap();
+/// assert!(lifecycle.contains(&(LifecycleEventKind::REGISTERED, String::from("OrderCreatedEventHandler"))));
+/// assert!(lifecycle.contains(&(LifecycleEventKind::UNREGISTERED, String::from("OrderCreatedEventHandler"))));
+/// assert_eq!(lifecycle.last().unwrap().0, LifecycleEventKind::SHUTDOWN);
+/// ```
+pub fn shutdown() {
+    shutdown_counting();
+}
+
+/// Shuts In-Memory message broker down like `shutdown`, returning numbers of drained (queued) events and unregistered
+/// event handlers.
+pub(crate) fn shutdown_counting() -> (usize, usize) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "in-memory message broker shutting down");
+    scheduler::stop();
+    let drained = dispatcher::stop();
+    replies::fail_all(RequestError::Shutdown);
+    lifecycle::emit(LifecycleEventKind::SHUTDOWN, String::from("in-memory"));
+    let mut registry = DEFAULT_REGISTRY.registry.lock().unwrap();
+    let unregistered = registry.handler_configs.len();
+    registry.clear();
+    (drained, unregistered)
+}
+
+/// Emits In-Memory event without specifying message channel.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// in_memory::emit(&order_created);
+/// ```
+pub fn emit(event: &dyn Event) {
+    if let Err(error) = try_emit(event) {
+        warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory event {} not emitted: {}", common::redacted(event), error);
+    }
+}
+
+/// Emits In-Memory event to specific message channel.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+/// in_memory::emit_to_channel(&order_created, in_memory::MessageChannel { channel_type: in_memory::ChannelType::QUEUE, name: ".*".into() });
+/// ```
+pub fn emit_to_channel(event: &dyn Event, channel: MessageChannel) {
+    if let Err(error) = try_emit_to_channel(event, channel) {
+        warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory event {} not emitted: {}", common::redacted(event), error);
+    }
+}
+
+/// Emits In-Memory event without specifying message channel, using given id instead of the one reported by the event
+/// (e.g. the id assigned by an upstream system). The override travels with the event (also through the async queue)
+/// and is reported by `current_event_id` while the event is handled; the event itself is left untouched.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// static TRACED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+///
+/// in_memory::add_before_handle(|_handler_id, event| {
+///     TRACED.lock().unwrap().push((event.id().to_string(), in_memory::current_event_id().unwrap()));
+/// });
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+///
+/// in_memory::emit_with_id(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") }, "upstream-42");
+/// in_memory::emit(&OrderCreated { event_id: String::from("2"), customer_id: String::from("customer_id") });
+///
+/// assert_eq!(*TRACED.lock().unwrap(), vec![
+///     (String::from("1"), String::from("upstream-42")),
+///     (String::from("2"), String::from("2")),
+/// ]);
+/// assert_eq!(in_memory::current_event_id(), None);
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {}
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+/// ```
+pub fn emit_with_id(event: &dyn Event, id: &str) {
+    if let Err(error) = rate_limiter::acquire() {
+        warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory event {} not emitted: {}", common::redacted(event), error);
+        return;
+    }
+    if let Err(channel) = dispatcher::enqueue(event, None, Some(id)) {
+        dispatch(event, channel, Some(id));
+    }
+}
+
+/// Emits In-Memory event without specifying message channel, deduplicated on the idempotency key rather than the event
+/// id (e.g. for retries generating new event ids, while representing the same intent). The key is recorded in the
+/// dedup store (see `set_dedup_store`), prefixed with `idempotency:` so that it can't collide with the event ids
+/// recorded there, and events with already recorded key are skipped. The key is forgotten again if no handler received
+/// the event (also in async mode, once dispatched). Fails if the dedup store isn't set, or the event is rejected (see
+/// `set_rate_limit`).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::{Arc, Mutex};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::common::InMemoryDedupStore;
+/// use eventure::in_memory::EmitError;
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PaymentRequested {
+///     event_id: String,
+///     order_id: String,
+/// }
+///
+/// impl Display for PaymentRequested {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "PaymentRequested", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PaymentRequested {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PaymentRequested"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PaymentEventHandler;
+///
+/// impl Display for PaymentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PaymentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PaymentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PaymentEventHandler")
+///     }
+/// }
+///
+/// let payment_requested = PaymentRequested { event_id: String::from("1"), order_id: String::from("order-7") };
+/// assert_eq!(in_memory::emit_idempotent(&payment_requested, "pay-order-7"), Err(EmitError::DedupStoreNotSet));
+///
+/// in_memory::set_dedup_store(Arc::new(InMemoryDedupStore::new(1024)));
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"), PaymentEventHandler);
+///
+/// // retry of the same payment request, with a new event id
+/// in_memory::emit_idempotent(&payment_requested, "pay-order-7").unwrap();
+/// in_memory::emit_idempotent(&PaymentRequested { event_id: String::from("2"), order_id: String::from("order-7") }, "pay-order-7").unwrap();
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1"]);
+/// ```
+pub fn emit_idempotent(event: &dyn Event, idempotency_key: &str) -> Result<(), EmitError> {
+    let dedup_store = DEDUP_STORE.lock().unwrap().clone().ok_or(EmitError::DedupStoreNotSet)?;
+    let key = format!("{}{}", IDEMPOTENCY_KEY_PREFIX, idempotency_key);
+    if !dedup_store.insert(&key) {
+        info!(target: &common::format_target("EventHandlerRegistry"), "duplicate event {} skipped (idempotency key {})",
+            common::redacted(event), idempotency_key);
+        return Ok(());
+    }
+    if let Err(error) = rate_limiter::acquire() {
+        dedup_store.remove(&key);
+        return Err(error);
+    }
+    let on_complete: Box<dyn FnOnce(EmitReport) + Send> = Box::new(move |report| {
+        if report.results.is_empty() {
+            dedup_store.remove(&key);
+        }
+    });
+    if let Err((channel, on_complete)) = dispatcher::enqueue_reporting(event, None, on_complete) {
+        on_complete(dispatch_reporting(event, channel, None));
+    }
+    Ok(())
+}
+
+/// Emits In-Memory event without specifying message channel, returning immediately (in async mode) and reporting
+/// the per-handler results to the callback, once all handlers finished. Callback is invoked by the dispatcher
+/// worker in async mode, or on the calling thread otherwise. It's not invoked when the event is rejected.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::mpsc;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler {
+///     id: String,
+///     fails: bool,
+/// }
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.id)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {}
+///
+///     fn id(&self) -> String {
+///         String::from(&self.id)
+///     }
+///
+///     fn try_handle(&self, _event: &(dyn model::Event + '_)) -> Result<(), model::HandlerError> {
+///         match self.fails {
+///             true => Err(model::HandlerError::new("customer not found")),
+///             false => Ok(()),
+///         }
+///     }
+/// }
+///
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true)).unwrap();
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// in_memory::register(channel(), OrderEventHandler { id: String::from("Shipping"), fails: false });
+/// in_memory::register(channel(), OrderEventHandler { id: String::from("Billing"), fails: true });
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let order_created = OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") };
+/// in_memory::emit_with_callback(&order_created, move |report| sender.send(report).unwrap()).unwrap();
+///
+/// let report = receiver.recv().unwrap();
+/// assert_eq!(report.event_id, "1");
+/// assert_eq!(report.results, vec![
+///     (String::from("Shipping"), Ok(())),
+///     (String::from("Billing"), Err(model::HandlerError::new("customer not found"))),
+/// ]);
+/// assert!(!report.is_success());
+/// ```
+pub fn emit_with_callback(event: &dyn Event, on_complete: impl FnOnce(EmitReport) + Send + 'static) -> Result<(), EmitError> {
+    rate_limiter::acquire()?;
+    if let Err((channel, on_complete)) = dispatcher::enqueue_reporting(event, None, Box::new(on_complete)) {
+        on_complete(dispatch_reporting(event, channel, None));
+    }
+    Ok(())
+}
+
+/// Emits In-Memory event without specifying message channel, returning a receiver (a future) resolved with the
+/// per-handler results once all handlers finished, so that async code can await the emit without blocking its runtime.
+/// In synchronous mode, the event is dispatched (serialized via `Event::to_json`) on a worker thread, started with the
+/// first signal and dispatching signals one by one, in order. Signals emitted by the handlers on the worker thread,
+/// and events failing the round-trip (see `check_roundtrip`), are dispatched on the calling thread instead, so their
+/// receiver is resolved once the function returns. The receiver is resolved with `EmitError::RateLimited` when the
+/// event is rejected (see `set_rate_limit`).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread::{self, ThreadId};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::{EmitError, RateLimitMode};
+///
+/// static HANDLED: Mutex<Vec<(String, ThreadId)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push((event.id().to_string(), thread::current().id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+///
+///     let order_created = OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") };
+///     let report = in_memory::emit_signal(&order_created).await.unwrap().unwrap();
+///
+///     // dispatched on the worker thread, not blocking the runtime
+///     assert_eq!(HANDLED.lock().unwrap().len(), 1);
+///     assert_ne!(HANDLED.lock().unwrap()[0].1, thread::current().id());
+///     assert_eq!(report.event_id, "1");
+///     assert_eq!(report.results, vec![(String::from("OrderEventHandler"), Ok(()))]);
+///
+///     in_memory::set_rate_limit(1, RateLimitMode::REJECT);
+///     let first = in_memory::emit_signal(&order_created).await.unwrap();
+///     let second = in_memory::emit_signal(&order_created).await.unwrap();
+///     assert!(first.is_ok());
+///     assert_eq!(second.err(), Some(EmitError::RateLimited));
+/// }
+/// ```
+pub fn emit_signal(event: &dyn Event) -> oneshot::Receiver<Result<EmitReport, EmitError>> {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(error) = rate_limiter::acquire() {
+        let _ = sender.send(Err(error));
+        return receiver;
+    }
+    let on_complete: Box<dyn FnOnce(EmitReport) + Send> = Box::new(move |report| {
+        let _ = sender.send(Ok(report));
+    });
+    if let Err((channel, on_complete)) = dispatcher::enqueue_reporting(event, None, on_complete) {
+        if let Err(on_complete) = send_signal(event, on_complete) {
+            on_complete(dispatch_reporting(event, channel, None));
+        }
+    }
+    receiver
+}
+
+/// Emits In-Memory request event without specifying message channel, and waits (on the calling thread, at most the
+/// timeout) for the reply of a handler (see `reply`). Requests are correlated with replies by a correlation id of
+/// their own (the request keeps its event id), so the handler has to reply while handling the request (events it
+/// emits meanwhile aren't correlated with the request). Replies are passed back
+/// serialized (see `check_roundtrip`), so reply events need to be registered with `#[typetag::serde]`. Pending
+/// requests fail with `RequestError::Shutdown`, once the message broker is shut down.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::RequestError;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceRequested {
+///     event_id: String,
+///     product_id: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceQuoted {
+///     event_id: String,
+///     price: u64,
+/// }
+///
+/// impl Display for PriceRequested {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceRequested", self.event_id)
+///     }
+/// }
+///
+/// impl Display for PriceQuoted {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceQuoted", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceRequested {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceRequested"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceQuoted {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceQuoted"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PricingEventHandler;
+///
+/// impl Display for PricingEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PricingEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PricingEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if let Some(price_requested) = event.as_any().downcast_ref::<PriceRequested>() {
+///             // request keeps its own event id
+///             assert_eq!(in_memory::current_event_id().as_deref(), Some(price_requested.event_id.as_str()));
+///             if price_requested.product_id != "unknown" {
+///                 in_memory::reply(&PriceQuoted { event_id: String::from("quote-1"), price: 1250 });
+///             }
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PricingEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Pricing"), PricingEventHandler);
+///
+/// let request = PriceRequested { event_id: String::from("1"), product_id: String::from("product-1") };
+/// let reply = in_memory::request(&request, Duration::from_secs(1)).unwrap();
+/// assert_eq!(reply.as_any().downcast_ref::<PriceQuoted>().unwrap().price, 1250);
+///
+/// let request = PriceRequested { event_id: String::from("2"), product_id: String::from("unknown") };
+/// assert_eq!(in_memory::request(&request, Duration::from_millis(100)).err(), Some(RequestError::Timeout));
+/// ```
+///
+/// Shutting the message broker down wakes the pending requests up:
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::thread;
+/// use std::time::{Duration, Instant};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::RequestError;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceRequested {
+///     event_id: String,
+/// }
+///
+/// impl Display for PriceRequested {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceRequested", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceRequested {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceRequested"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct SilentEventHandler;
+///
+/// impl Display for SilentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "SilentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for SilentEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {}
+///
+///     fn id(&self) -> String {
+///         String::from("SilentEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Pricing"), SilentEventHandler);
+/// thread::spawn(|| {
+///     thread::sleep(Duration::from_millis(200));
+///     in_memory::shutdown();
+/// });
+///
+/// let started = Instant::now();
+/// let reply = in_memory::request(&PriceRequested { event_id: String::from("1") }, Duration::from_secs(60));
+/// assert_eq!(reply.err(), Some(RequestError::Shutdown));
+/// assert!(started.elapsed() < Duration::from_secs(5));
+/// ```
+pub fn request(event: &dyn Event, timeout: Duration) -> Result<Box<dyn Event>, RequestError> {
+    if let Err(error) = rate_limiter::acquire() {
+        warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory request {} not emitted: {}", common::redacted(event), error);
+        return Err(RequestError::RateLimited);
+    }
+    let correlation_id = Uuid::new_v4().to_string();
+    replies::open(&correlation_id);
+    if let Err(channel) = dispatcher::enqueue_request(event, &correlation_id) {
+        with_correlation_id(Some(correlation_id.clone()), || dispatch(event, channel, None));
+    }
+    let payload = replies::wait(&correlation_id, timeout)?;
+    serde_json::from_str::<Box<dyn Event>>(&payload).map_err(|e| RequestError::Roundtrip(RoundtripError::Malformed(e.to_string())))
+}
+
+/// Emits In-Memory request event and waits for the reply like `request`, returning the reply as the expected event
+/// type. Fails with `RequestError::UnexpectedReply`, if the handler replied with an event of another type.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::RequestError;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceRequested {
+///     event_id: String,
+///     product_id: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceQuoted {
+///     event_id: String,
+///     price: u64,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct ProductUnknown {
+///     event_id: String,
+/// }
+///
+/// impl Display for PriceRequested {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceRequested", self.event_id)
+///     }
+/// }
+///
+/// impl Display for PriceQuoted {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceQuoted", self.event_id)
+///     }
+/// }
+///
+/// impl Display for ProductUnknown {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "ProductUnknown", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceRequested {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceRequested"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceQuoted {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceQuoted"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for ProductUnknown {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "ProductUnknown"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PricingEventHandler;
+///
+/// impl Display for PricingEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PricingEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PricingEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if let Some(price_requested) = event.as_any().downcast_ref::<PriceRequested>() {
+///             match price_requested.product_id.as_str() {
+///                 "unknown" => in_memory::reply(&ProductUnknown { event_id: String::from("unknown-1") }),
+///                 _ => in_memory::reply(&PriceQuoted { event_id: String::from("quote-1"), price: 1250 }),
+///             };
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PricingEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Pricing"), PricingEventHandler);
+///
+/// let request = PriceRequested { event_id: String::from("1"), product_id: String::from("product-1") };
+/// let price_quoted: PriceQuoted = in_memory::request_typed(&request, Duration::from_secs(1)).unwrap();
+/// assert_eq!(price_quoted.price, 1250);
+///
+/// let request = PriceRequested { event_id: String::from("2"), product_id: String::from("unknown") };
+/// let reply = in_memory::request_typed::<PriceRequested, PriceQuoted>(&request, Duration::from_secs(1));
+/// assert!(matches!(reply, Err(RequestError::UnexpectedReply { actual, .. }) if actual == "ProductUnknown"));
+/// ```
+pub fn request_typed<Req: Event, Resp: Event>(request: &Req, timeout: Duration) -> Result<Resp, RequestError> {
+    let reply = self::request(request, timeout)?;
+    let actual = String::from(reply.name());
+    reply.downcast::<Resp>()
+        .map(|reply| *reply)
+        .map_err(|_| RequestError::UnexpectedReply { expected: type_name::<Resp>(), actual })
+}
+
+/// Replies to the In-Memory request (see `request`) being handled on the current thread. Returns false, if there's
+/// no request waiting for the reply (e.g. the handled event isn't a request, it timed out or got replied already).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PriceQuoted {
+///     event_id: String,
+/// }
+///
+/// impl Display for PriceQuoted {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "PriceQuoted", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PriceQuoted {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PriceQuoted"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// // not handling any request
+/// assert!(!in_memory::reply(&PriceQuoted { event_id: String::from("1") }));
+/// ```
+pub fn reply(event: &dyn Event) -> bool {
+    let Some(correlation_id) = CURRENT_CORRELATION_ID.with(|current| current.borrow().clone()) else {
+        return false;
+    };
+    let replied = replies::complete(&correlation_id, serialize(event).map_err(RequestError::Roundtrip));
+    if replied {
+        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory reply {} sent to request {}", common::redacted(event), correlation_id);
+    }
+    replied
+}
+
+/// Returns id of the In-Memory event being handled on the current thread: the id overridden on emit
+/// (see `emit_with_id`), or the event's own id. Returns `None` outside of event handling.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// assert_eq!(in_memory::current_event_id(), None);
+/// ```
+pub fn current_event_id() -> Option<String> {
+    CURRENT_EVENT_ID.with(|current| current.borrow().clone())
+}
+
+/// Returns trace context of the In-Memory event being handled on the current thread. Events emitted by the handler
+/// are dispatched within child spans of the current one (also in async mode, where the context is queued along with
+/// the event). Returns `None` outside of event handling.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static TRACES: Mutex<Vec<(String, in_memory::TraceContext)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct InvoiceRequested {
+///     event_id: String,
+/// }
+///
+/// impl Display for InvoiceRequested {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "InvoiceRequested", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for InvoiceRequested {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "InvoiceRequested"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct TracingEventHandler;
+///
+/// impl Display for TracingEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "TracingEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for TracingEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         TRACES.lock().unwrap().push((event.name().to_string(), in_memory::current_trace().unwrap()));
+///         if event.name() == "OrderCreated" {
+///             in_memory::emit(&InvoiceRequested { event_id: format!("invoice-{}", event.id()) });
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("TracingEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), TracingEventHandler);
+/// in_memory::emit(&OrderCreated { event_id: String::from("1") });
+///
+/// let traces = TRACES.lock().unwrap();
+/// let (parent_name, parent) = &traces[0];
+/// let (child_name, child) = &traces[1];
+/// assert_eq!((parent_name.as_str(), child_name.as_str()), ("OrderCreated", "InvoiceRequested"));
+/// assert_eq!(parent.parent_span_id, None);
+/// assert_eq!(child.parent_span_id.as_ref(), Some(&parent.span_id));
+/// assert_eq!(child.trace_id, parent.trace_id);
+/// assert_ne!(child.span_id, parent.span_id);
+/// assert_eq!(in_memory::current_trace(), None);
+/// ```
+pub fn current_trace() -> Option<TraceContext> {
+    trace::current()
+}
+
+/// Returns ids of the In-Memory events which led to the event (the root cause first): events emitted while handling
+/// an event are recorded as caused by it (also in async mode), so cascades of emits can be traced back, e.g. when
+/// diagnosing event storms. Returns an empty chain for events not emitted by handlers (or no longer recorded, the
+/// most recent 10000 causes are kept).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct StepCompleted {
+///     event_id: String,
+/// }
+///
+/// impl Display for StepCompleted {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "StepCompleted", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for StepCompleted {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "StepCompleted"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct CascadingEventHandler;
+///
+/// impl Display for CascadingEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "CascadingEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for CascadingEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         match event.id() {
+///             "A" => in_memory::emit(&StepCompleted { event_id: String::from("B") }),
+///             "B" => in_memory::emit(&StepCompleted { event_id: String::from("C") }),
+///             _ => {}
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("CascadingEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), CascadingEventHandler);
+/// in_memory::emit(&StepCompleted { event_id: String::from("A") });
+///
+/// assert_eq!(in_memory::cause_chain("C"), vec!["A", "B"]);
+/// assert_eq!(in_memory::cause_chain("B"), vec!["A"]);
+/// assert!(in_memory::cause_chain("A").is_empty());
+/// ```
+pub fn cause_chain(event_id: &str) -> Vec<String> {
+    causes::chain(event_id)
+}
+
+/// Emits batch of In-Memory events without specifying message channel, one by one, in the given order.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::time::{Duration, SystemTime};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::BatchOrder;
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     occurred_at: SystemTime,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+///     fn occurred_at(&self) -> Option<SystemTime> {
+///         Some(self.occurred_at)
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order"), OrderCreatedEventHandler);
+///
+/// let now = SystemTime::now();
+/// let order_created = |id: &str, seconds: u64| OrderCreated {
+///     event_id: String::from(id),
+///     occurred_at: now + Duration::from_secs(seconds),
+/// };
+/// let (second, third, first) = (order_created("second", 2), order_created("third", 3), order_created("first", 1));
+///
+/// in_memory::emit_all(&[&second, &third, &first], BatchOrder::OCCURRED);
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["first", "second", "third"]);
+/// ```
+pub fn emit_all(events: &[&dyn Event], order: BatchOrder) {
+    let mut events = events.to_vec();
+    if order == BatchOrder::OCCURRED {
+        let positions: Vec<usize> = (0..events.len()).filter(|&index| events[index].occurred_at().is_some()).collect();
+        let mut timestamped: Vec<&dyn Event> = positions.iter().map(|&index| events[index]).collect();
+        timestamped.sort_by_key(|event| event.occurred_at());
+        positions.into_iter().zip(timestamped).for_each(|(index, event)| events[index] = event);
+    }
+    events.into_iter().for_each(emit);
+}
+
+/// Emits In-Memory event without specifying message channel, always dispatching it on the calling thread.
+/// Unlike `emit`, event isn't queued (not even in async mode), handlers receive the very same instance. Features
+/// serializing events (e.g. history, dead letters or tasks, see the module docs) still serialize it.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread::{self, ThreadId};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// // address of the handled instance and the thread handling it
+/// static HANDLED: Mutex<Vec<(usize, ThreadId)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if let Some(order_created) = event.as_any().downcast_ref::<OrderCreated>() {
+///             HANDLED.lock().unwrap().push((order_created as *const OrderCreated as usize, thread::current().id()));
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true)).unwrap();
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order"), OrderCreatedEventHandler);
+/// let order_created = OrderCreated {
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// let instance = (&order_created as *const OrderCreated as usize, thread::current().id());
+///
+/// // in async mode, emitted event is serialized and handled by a worker thread
+/// in_memory::emit(&order_created);
+/// // setting up again waits for queued events to be dispatched
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true)).unwrap();
+/// assert_ne!(HANDLED.lock().unwrap().pop(), Some(instance));
+///
+/// in_memory::emit_in_process(&order_created);
+/// assert_eq!(HANDLED.lock().unwrap().pop(), Some(instance));
+/// ```
+pub fn emit_in_process(event: &dyn Event) {
+    match rate_limiter::acquire() {
+        Ok(()) => {
+            dispatch(event, None, None);
+        }
+        Err(error) => warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory event {} not emitted: {}", common::redacted(event), error),
+    }
+}
+
+/// Emits In-Memory event to specific message channel, always dispatching it on the calling thread.
+/// Unlike `emit_to_channel`, event isn't queued (not even in async mode). Features serializing events
+/// (e.g. history, dead letters or tasks, see the module docs) still serialize it.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true)).unwrap();
+/// in_memory::emit_in_process_to_channel(&order_created, in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"));
+/// ```
+pub fn emit_in_process_to_channel(event: &dyn Event, channel: MessageChannel) {
+    match rate_limiter::acquire() {
+        Ok(()) => {
+            dispatch(event, Some(channel), None);
+        }
+        Err(error) => warn!(target: &common::format_target("EventHandlerRegistry"), "in-memory event {} not emitted: {}", common::redacted(event), error),
+    }
+}
+
+/// Emits In-Memory event without specifying message channel, reporting an error if event wasn't emitted.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// assert_eq!(in_memory::try_emit(&order_created), Ok(()));
+/// ```
+pub fn try_emit(event: &dyn Event) -> Result<(), EmitError> {
+    rate_limiter::acquire()?;
+    if let Err(channel) = dispatcher::enqueue(event, None, None) {
+        dispatch(event, channel, None);
+    }
+    Ok(())
+}
+
+/// Emits In-Memory event to specific message channel, reporting an error if event wasn't emitted.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// let channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// assert_eq!(in_memory::try_emit_to_channel(&order_created, channel), Ok(()));
+/// ```
+pub fn try_emit_to_channel(event: &dyn Event, channel: MessageChannel) -> Result<(), EmitError> {
+    rate_limiter::acquire()?;
+    if let Err(channel) = dispatcher::enqueue(event, Some(channel), None) {
+        dispatch(event, channel, None);
+    }
+    Ok(())
+}
+
+/// Sets In-Memory emit rate limit (token bucket, shared by all emitting threads), applied to `emit`/`emit_to_channel`
+/// and their `try_` variants. Once exceeded, emits either block or are rejected, depending on the mode.
+/// Setting rate to zero removes the limit.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::{Duration, Instant};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::{EmitError, RateLimitMode};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// in_memory::set_rate_limit(5, RateLimitMode::REJECT);
+/// let results: Vec<Result<(), EmitError>> = (0..10).map(|_| in_memory::try_emit(&order_created)).collect();
+/// assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 5);
+/// assert!(results[5..].iter().all(|result| *result == Err(EmitError::RateLimited)));
+///
+/// in_memory::set_rate_limit(20, RateLimitMode::BLOCK);
+/// let started = Instant::now();
+/// (0..30).for_each(|_| in_memory::emit(&order_created));
+/// assert!(started.elapsed() >= Duration::from_millis(400));
+/// ```
+pub fn set_rate_limit(events_per_second: u32, mode: RateLimitMode) {
+    rate_limiter::configure(events_per_second, mode);
+}
+
+/// Sets maximum fan-out of the In-Memory events: number of handlers an event may match, guarding against runaway
+/// fan-out (e.g. misconfigured catch-all channel). Events matching more handlers are logged, and delivered according
+/// to the mode. Fan-out isn't limited by default.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use log::{Level, LevelFilter, Log, Metadata, Record};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+/// use eventure::in_memory::FanoutMode;
+///
+/// static LOGGED: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct CapturingLogger;
+///
+/// impl Log for CapturingLogger {
+///     fn enabled(&self, _metadata: &Metadata) -> bool {
+///         true
+///     }
+///     fn log(&self, record: &Record) {
+///         LOGGED.lock().unwrap().push((record.level(), record.args().to_string()));
+///     }
+///     fn flush(&self) {}
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler(usize);
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "OrderEventHandler{}", self.0)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         format!("OrderEventHandler{}", self.0)
+///     }
+/// }
+///
+/// log::set_logger(&CapturingLogger).unwrap();
+/// log::set_max_level(LevelFilter::Warn);
+///
+/// for index in 0..5 {
+///     in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), OrderEventHandler(index));
+/// }
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+///
+/// in_memory::set_max_fanout(3, FanoutMode::WARN);
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") }, channel());
+/// assert_eq!(HANDLED.swap(0, Ordering::SeqCst), 5);
+/// assert_eq!(*LOGGED.lock().unwrap(), vec![(Level::Warn, String::from(
+///     "event OrderCreated event with id 1 matched 5 handlers, exceeding max fan-out of 3"))]);
+///
+/// in_memory::set_max_fanout(3, FanoutMode::CAP);
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("2") }, channel());
+/// assert_eq!(HANDLED.swap(0, Ordering::SeqCst), 3);
+/// assert!(LOGGED.lock().unwrap()[1].1.ends_with("exceeding max fan-out of 3, delivered to the first 3 only"));
+///
+/// in_memory::set_max_fanout(3, FanoutMode::STRICT);
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("3") }, channel());
+/// assert_eq!(HANDLED.swap(0, Ordering::SeqCst), 5);
+/// assert_eq!(LOGGED.lock().unwrap()[2].0, Level::Error);
+/// ```
+pub fn set_max_fanout(max_fanout: usize, mode: FanoutMode) {
+    *MAX_FANOUT.lock().unwrap() = Some((max_fanout, mode));
+}
+
+/// Sets detection of In-Memory emit loops, breaking handlers which (directly or transitively) re-trigger themselves
+/// instead of recursing endlessly. Event is not dispatched (logging `EmitLoopDetected` error), once its depth (number
+/// of events which led to it, see `cause_chain`, plus the event itself) exceeds the maximum depth, or, with name cycles
+/// detected, once names of the events which led to it end with a cycle repeated twice (e.g. `A, A` for a handler
+/// re-emitting the event which triggered it, or `A, B, A, B`). Applies in async mode as well. Maximum depth is 64 by
+/// default (`None` removes it); name cycles aren't detected by default, since they also break legitimate chains of
+/// events sharing a name (like pages of a paged fetch).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct StockChanged {
+///     event_id: String,
+/// }
+///
+/// impl Display for StockChanged {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "StockChanged", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for StockChanged {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "StockChanged"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct RecursiveEventHandler;
+///
+/// impl Display for RecursiveEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "RecursiveEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for RecursiveEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///         // re-emits the triggering event
+///         in_memory::emit(event);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("RecursiveEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), RecursiveEventHandler);
+/// in_memory::emit(&StockChanged { event_id: String::from("1") });
+/// // broken at the default maximum depth
+/// assert_eq!(HANDLED.swap(0, Ordering::SeqCst), 64);
+///
+/// in_memory::set_loop_detection(Some(5), false);
+/// in_memory::emit(&StockChanged { event_id: String::from("2") });
+/// // the event and its 4 re-emits handled, the next re-emit (at depth 6) aborted
+/// assert_eq!(HANDLED.swap(0, Ordering::SeqCst), 5);
+///
+/// in_memory::set_loop_detection(Some(5), true);
+/// in_memory::emit(&StockChanged { event_id: String::from("3") });
+/// // the first re-emit repeats the name of the triggering event
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+/// ```
+///
+/// Events ping-ponging between handlers are stopped once the cycle repeats:
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Signal {
+///     event_id: String,
+///     event_name: String,
+/// }
+///
+/// impl Display for Signal {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                self.event_name, self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for Signal {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         &self.event_name[..]
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PingPongEventHandler;
+///
+/// impl Display for PingPongEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PingPongEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PingPongEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.name().to_string());
+///         let event_name = if event.name() == "Ping" { "Pong" } else { "Ping" };
+///         let event_id = (event.id().parse::<u32>().unwrap() + 1).to_string();
+///         in_memory::emit(&Signal { event_id, event_name: String::from(event_name) });
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PingPongEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_loop_detection(Some(100), true);
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), PingPongEventHandler);
+/// in_memory::emit(&Signal { event_id: String::from("1"), event_name: String::from("Ping") });
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["Ping", "Pong", "Ping"]);
+/// ```
+pub fn set_loop_detection(max_depth: Option<usize>, detect_name_cycles: bool) {
+    causes::configure(max_depth, detect_name_cycles);
+}
+
+/// Sets priority of the In-Memory message channel (matched exactly by type and name), applied to the events emitted
+/// to the channel in async mode with `Scheduling::PRIORITY`. Channels default to the lowest priority (zero).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static STARTED: AtomicBool = AtomicBool::new(false);
+/// static RELEASED: AtomicBool = AtomicBool::new(false);
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Notification {
+///     event_id: String,
+/// }
+///
+/// impl Display for Notification {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "Notification", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for Notification {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "Notification"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct NotificationEventHandler;
+///
+/// impl Display for NotificationEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "NotificationEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for NotificationEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if event.id() == "gate" {
+///             // holds the worker, until all the other events are queued
+///             STARTED.store(true, Ordering::SeqCst);
+///             while !RELEASED.load(Ordering::SeqCst) {
+///                 thread::sleep(Duration::from_millis(10));
+///             }
+///         }
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("NotificationEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let reports = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Reports");
+///     let alerts = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Alerts");
+///
+///     let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+///     configuration.async_settings.scheduling = in_memory::Scheduling::PRIORITY;
+///     in_memory::setup(configuration).unwrap();
+///     in_memory::set_priority(alerts(), 10);
+///     in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, ".*"), NotificationEventHandler);
+///
+///     in_memory::emit_to_channel(&Notification { event_id: String::from("gate") }, reports());
+///     while !STARTED.load(Ordering::SeqCst) {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///     }
+///     in_memory::emit_to_channel(&Notification { event_id: String::from("report-1") }, reports());
+///     in_memory::emit_to_channel(&Notification { event_id: String::from("alert-1") }, alerts());
+///     in_memory::emit_to_channel(&Notification { event_id: String::from("report-2") }, reports());
+///     in_memory::emit_to_channel(&Notification { event_id: String::from("alert-2") }, alerts());
+///     RELEASED.store(true, Ordering::SeqCst);
+///
+///     // setting up again waits for queued events to be dispatched
+///     in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+///     assert_eq!(*HANDLED.lock().unwrap(), vec!["gate", "alert-1", "alert-2", "report-1", "report-2"]);
+/// }
+/// ```
+pub fn set_priority(channel: MessageChannel, priority: u8) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "channel {} priority set to {}", channel, priority);
+    dispatcher::set_priority(channel, priority);
+}
+
+/// Sets In-Memory async mode queue watermarks, signalling backpressure to producers before emits start blocking.
+/// Once the number of queued events reaches the `high` watermark, `on_high_watermark` is invoked (with the queue
+/// depth), so producers can voluntarily slow down. Once it then drops below the `low` watermark, `on_low_watermark`
+/// is invoked. Each callback fires once per crossing, on the emitting or dispatching thread.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static STARTED: AtomicBool = AtomicBool::new(false);
+/// static RELEASED: AtomicBool = AtomicBool::new(false);
+/// static SIGNALS: Mutex<Vec<(&str, usize)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if event.id() == "gate" {
+///             // holds the worker, until the queue fills up
+///             STARTED.store(true, Ordering::SeqCst);
+///             while !RELEASED.load(Ordering::SeqCst) {
+///                 thread::sleep(Duration::from_millis(10));
+///             }
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+///     configuration.async_settings = in_memory::async_settings(16, 1, true);
+///     in_memory::setup(configuration).unwrap();
+///     in_memory::set_watermarks(8, 2,
+///         |depth| SIGNALS.lock().unwrap().push(("high", depth)),
+///         |depth| SIGNALS.lock().unwrap().push(("low", depth)));
+///     in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+///
+///     in_memory::emit(&OrderCreated { event_id: String::from("gate") });
+///     while !STARTED.load(Ordering::SeqCst) {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///     }
+///     for index in 0..10 {
+///         in_memory::emit(&OrderCreated { event_id: index.to_string() });
+///     }
+///     assert_eq!(*SIGNALS.lock().unwrap(), vec![("high", 8)]);
+///
+///     RELEASED.store(true, Ordering::SeqCst);
+///     // setting up again waits for queued events to be dispatched
+///     in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+///     assert_eq!(*SIGNALS.lock().unwrap(), vec![("high", 8), ("low", 1)]);
+/// }
+/// ```
+pub fn set_watermarks(high: usize, low: usize, on_high_watermark: impl Fn(usize) + Send + Sync + 'static,
+                      on_low_watermark: impl Fn(usize) + Send + Sync + 'static) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "queue watermarks set: [high:{},low:{}]", high, low);
+    dispatcher::set_watermarks(high, low, Arc::new(on_high_watermark), Arc::new(on_low_watermark));
+}
+
+/// Sets In-Memory async mode concurrency key extractor. Events emitted in async mode sharing a key (e.g. the same
+/// aggregate id) are dispatched one at a time, in emission order, while events with distinct keys (or no key at all)
+/// are dispatched concurrently by the worker threads. Extractor returning `None` for every event disables keyed
+/// dispatch.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// static HANDLED: Mutex<Vec<(String, u32)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderUpdated {
+///     event_id: String,
+///     order_id: String,
+///     revision: u32,
+/// }
+///
+/// impl Display for OrderUpdated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderUpdated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderUpdated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderUpdated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderUpdatedEventHandler;
+///
+/// impl Display for OrderUpdatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderUpdatedEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderUpdatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+///         MAX_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+///         thread::sleep(Duration::from_millis(20));
+///         let order_updated = event.as_any().downcast_ref::<OrderUpdated>().unwrap();
+///         HANDLED.lock().unwrap().push((order_updated.order_id.clone(), order_updated.revision));
+///         IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderUpdatedEventHandler")
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut configuration = in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true);
+///     configuration.async_settings = in_memory::async_settings(64, 4, false);
+///     in_memory::setup(configuration).unwrap();
+///     in_memory::set_concurrency_key(|event| event.as_any().downcast_ref::<OrderUpdated>()
+///         .map(|order_updated| order_updated.order_id.clone()));
+///     in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order"), OrderUpdatedEventHandler);
+///
+///     // revisions of two orders, interleaved
+///     for revision in 0..5 {
+///         for order_id in ["order-a", "order-b"] {
+///             in_memory::emit(&OrderUpdated {
+///                 event_id: format!("{}-{}", order_id, revision),
+///                 order_id: String::from(order_id),
+///                 revision,
+///             });
+///         }
+///     }
+///
+///     // setting up again waits for queued events to be dispatched
+///     in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", false)).unwrap();
+///     let handled = HANDLED.lock().unwrap();
+///     for order_id in ["order-a", "order-b"] {
+///         let revisions: Vec<u32> = handled.iter()
+///             .filter(|(id, _)| id == order_id)
+///             .map(|(_, revision)| *revision)
+///             .collect();
+///         assert_eq!(revisions, vec![0, 1, 2, 3, 4]);
+///     }
+///     // the orders were handled concurrently, but never two revisions of the same order at once
+///     assert_eq!(MAX_IN_FLIGHT.load(Ordering::SeqCst), 2);
+/// }
+/// ```
+pub fn set_concurrency_key(key_extractor: impl Fn(&dyn Event) -> Option<String> + Send + Sync + 'static) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "concurrency key extractor set");
+    dispatcher::set_concurrency_key(Arc::new(key_extractor));
+}
+
+/// Enables (or disables) In-Memory total-order mode. Once enabled, dispatches of events emitted concurrently
+/// (from multiple threads or async workers) are serialized through a sequencer assigning them global order numbers
+/// (see `current_sequence`), so all handlers observe events in a single consistent order, one event at a time.
+/// It trades throughput for determinism: handlers of different events no longer run in parallel.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static OBSERVED: Mutex<Vec<(String, String, u64)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// struct OrderEventHandler {
+///     id: String,
+/// }
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.id)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         let sequence = in_memory::current_sequence().unwrap();
+///         OBSERVED.lock().unwrap().push((self.id.clone(), event.id().to_string(), sequence));
+///         thread::yield_now();
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from(&self.id)
+///     }
+/// }
+///
+/// in_memory::set_total_order(true);
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// in_memory::register(channel(), OrderEventHandler { id: String::from("Shipping") });
+/// in_memory::register(channel(), OrderEventHandler { id: String::from("Billing") });
+///
+/// let emitters: Vec<_> = (0..4).map(|thread_index| thread::spawn(move || {
+///     for index in 0..50 {
+///         in_memory::emit(&OrderCreated { event_id: format!("{}-{}", thread_index, index) });
+///     }
+/// })).collect();
+/// emitters.into_iter().for_each(|emitter| emitter.join().unwrap());
+///
+/// // each event is handled by both handlers before the next one, in increasing sequence order
+/// let observed = OBSERVED.lock().unwrap();
+/// assert_eq!(observed.len(), 400);
+/// for (index, pair) in observed.chunks(2).enumerate() {
+///     assert_eq!((pair[0].0.as_str(), pair[1].0.as_str()), ("Shipping", "Billing"));
+///     assert_eq!(pair[0].1, pair[1].1);
+///     assert_eq!(pair[0].2, pair[1].2);
+///     if index > 0 {
+///         assert!(pair[0].2 > observed[index * 2 - 1].2);
+///     }
+/// }
+/// ```
+pub fn set_total_order(enabled: bool) {
+    sequencer::configure(enabled);
+}
+
+/// Returns global order number of the In-Memory event being handled on the current thread, assigned in total-order
+/// mode (see `set_total_order`). Returns `None` outside of event handling, or when total order is disabled.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// assert_eq!(in_memory::current_sequence(), None);
+/// ```
+pub fn current_sequence() -> Option<u64> {
+    sequencer::current()
+}
+
+/// Sets capacity of the In-Memory channel match cache, memoizing handlers whose channel regexes match the emit channel
+/// name, so that repeated emits to the same channels don't evaluate the regexes again. The memo belongs to the
+/// current handlers of the registry: registering or unregistering handlers drops it. Capacity is the number of emit
+/// channels memoized, the memo is cleared once full. Zero disables the cache; 1024 by default.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler(&'static str);
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.0)
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(format!("{}:{}", self.0, event.id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from(self.0)
+///     }
+/// }
+///
+/// in_memory::set_match_cache_capacity(16);
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "^Ord"), OrderEventHandler("prefix"));
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"), OrderEventHandler("payments"));
+///
+/// // handlers matching the channel get memoized
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") }, channel());
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("2") }, channel());
+///
+/// // handlers registered later see the events, as the registration drops the memo
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders$"), OrderEventHandler("suffix"));
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("3") }, channel());
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["prefix:1", "prefix:2", "prefix:3", "suffix:3"]);
+/// ```
+pub fn set_match_cache_capacity(capacity: usize) {
+    match_cache::configure(capacity);
+}
+
+/// Sets capacity of the In-Memory event history, recording recently dispatched events (along with the channel they
+/// were emitted to) for the handlers registered with `register_with_snapshot`. Recorded events are serialized via
+/// `Event::to_json`; once the history is full, the oldest ones are dropped. Setting capacity to zero disables the
+/// history (default).
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::set_history_capacity(256);
+/// ```
+pub fn set_history_capacity(capacity: usize) {
+    history::configure(capacity);
+}
+
+/// Sets capacity of the In-Memory dead-letter queue, buffering events no handler matched (along with the channel they
+/// were emitted to). Dead-lettered events are serialized via `Event::to_json`; once the queue is full, the oldest
+/// ones are dropped. Setting capacity to zero disables the queue (default), discarding buffered events.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// in_memory::set_dead_letter_capacity(1024);
+/// ```
+pub fn set_dead_letter_capacity(capacity: usize) {
+    dead_letters::configure(capacity);
+}
+
+/// Sets limit of the total payload bytes (serialized via `Event::to_json`) retained by the In-Memory dead-letter queue,
+/// in addition to its capacity. Once exceeded, the oldest dead letters are dropped; events larger than the whole budget
+/// aren't dead-lettered at all. Setting budget to zero removes the limit (default).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct DocumentUploaded {
+///     event_id: String,
+///     content: String,
+/// }
+///
+/// impl Display for DocumentUploaded {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "DocumentUploaded", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for DocumentUploaded {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "DocumentUploaded"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct DocumentEventHandler;
+///
+/// impl Display for DocumentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "DocumentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for DocumentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("DocumentEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dead_letter_capacity(100);
+/// in_memory::set_dead_letter_budget(500);
+///
+/// let documents: Vec<DocumentUploaded> = [10, 300, 50, 200, 1000, 20].iter().enumerate()
+///     .map(|(index, size)| DocumentUploaded { event_id: index.to_string(), content: "x".repeat(*size) })
+///     .collect();
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Documents");
+/// for document in &documents {
+///     in_memory::emit_to_channel(document, channel());
+/// }
+///
+/// in_memory::register(channel(), DocumentEventHandler);
+/// in_memory::replay_dead_letters();
+///
+/// // the oldest documents were evicted to fit the budget, the largest one was never retained
+/// let handled = HANDLED.lock().unwrap().clone();
+/// assert_eq!(handled, vec!["2", "3", "5"]);
+/// let retained_bytes: usize = handled.iter()
+///     .map(|id| model::Event::to_json(&documents[id.parse::<usize>().unwrap()]).len())
+///     .sum();
+/// assert!(retained_bytes <= 500);
+/// ```
+pub fn set_dead_letter_budget(byte_budget: usize) {
+    dead_letters::configure_budget(byte_budget);
+}
+
+/// Re-emits buffered dead-letter events to their original channels, on the calling thread. Events delivered to at
+/// least one handler are removed from the dead-letter queue, the rest (including the ones which can't be deserialized)
+/// stay buffered. Returns number of delivered events.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct PaymentReceived {
+///     event_id: String,
+/// }
+///
+/// impl Display for PaymentReceived {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "PaymentReceived", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for PaymentReceived {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "PaymentReceived"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct PaymentEventHandler;
+///
+/// impl Display for PaymentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "PaymentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for PaymentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("PaymentEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dead_letter_capacity(16);
+///
+/// // no handler matches the channel yet, so the event is dead-lettered
+/// let channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments");
+/// in_memory::emit_to_channel(&PaymentReceived { event_id: String::from("1") }, channel);
+/// assert!(HANDLED.lock().unwrap().is_empty());
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments"), PaymentEventHandler);
+///
+/// assert_eq!(in_memory::replay_dead_letters(), 1);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1"]);
+/// assert_eq!(in_memory::replay_dead_letters(), 0);
+/// ```
+pub fn replay_dead_letters() -> usize {
+    let dead_letters = dead_letters::take();
+    info!(target: &common::format_target("EventHandlerRegistry"), "replaying {} dead letter(s)", dead_letters.len());
+    let mut delivered = 0;
+    for dead_letter in dead_letters {
+        match serde_json::from_str::<Box<dyn Event>>(&dead_letter.payload) {
+            Ok(event) => if dispatch(&*event, dead_letter.channel, dead_letter.event_id.as_deref()) > 0 {
+                delivered += 1;
+            },
+            Err(e) => {
+                error!(target: &common::format_target("EventHandlerRegistry"), "unable to deserialize dead letter {}, kept: {}",
+                    common::redacted_payload(dead_letter.event_id.as_deref(), dead_letter.payload.as_bytes()), e);
+                dead_letters::restore(dead_letter);
+            }
+        }
+    }
+    delivered
+}
+
+/// Emits In-Memory event (as `emit` does) once the delay elapses, on the scheduler's timer thread (shared by all
+/// schedules, so handlers of scheduled events shouldn't block for long). The event is serialized via `Event::to_json`
+/// and deserialized (through `typetag`) when due. Returns id of the schedule, which can be used to cancel it, or an
+/// error if the event can't be serialized that way or the timer thread can't be started.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct ReminderDue {
+///     event_id: String,
+/// }
+///
+/// impl Display for ReminderDue {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "ReminderDue", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for ReminderDue {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "ReminderDue"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct ReminderEventHandler;
+///
+/// impl Display for ReminderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "ReminderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for ReminderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("ReminderEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Reminders"), ReminderEventHandler);
+///
+/// in_memory::emit_after(&ReminderDue { event_id: String::from("1") }, Duration::from_millis(50)).unwrap();
+/// thread::sleep(Duration::from_millis(500));
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1"]);
+/// ```
+pub fn emit_after(event: &dyn Event, delay: Duration) -> Result<u64, ScheduleError> {
+    scheduler::schedule(event, delay)
+}
+
+/// Lists pending scheduled emits, the earliest due first.
+///
+/// # Examples
+/// ```
+/// use eventure::in_memory;
+///
+/// for schedule in in_memory::scheduled() {
+///     println!("{} ({}) due at {:?}", schedule.event_name, schedule.event_id, schedule.due);
+/// }
+/// ```
+pub fn scheduled() -> Vec<ScheduleInfo> {
+    scheduler::scheduled()
+}
+
+/// Cancels pending scheduled emit. Returns true, if the schedule was cancelled before firing.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct ReminderDue {
+///     event_id: String,
+/// }
+///
+/// impl Display for ReminderDue {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "ReminderDue", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for ReminderDue {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "ReminderDue"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct ReminderEventHandler;
+///
+/// impl Display for ReminderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "ReminderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for ReminderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("ReminderEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Reminders"), ReminderEventHandler);
+///
+/// let id = in_memory::emit_after(&ReminderDue { event_id: String::from("1") }, Duration::from_millis(300)).unwrap();
+/// let scheduled = in_memory::scheduled();
+/// assert_eq!(scheduled.len(), 1);
+/// assert_eq!(scheduled[0].id, id);
+/// assert_eq!(scheduled[0].event_id, "1");
+///
+/// assert!(in_memory::cancel_schedule(id));
+/// assert!(in_memory::scheduled().is_empty());
+///
+/// thread::sleep(Duration::from_millis(600));
+/// assert!(HANDLED.lock().unwrap().is_empty());
+/// assert!(!in_memory::cancel_schedule(id));
+/// ```
+pub fn cancel_schedule(id: u64) -> bool {
+    scheduler::cancel(id)
+}
+
+/// Sets dedup store consulted on dispatch: events whose id (or overridden id) is already recorded in the store are
+/// skipped, the rest are recorded. Events are keyed by their id alone, whichever channel they're emitted to (the same
+/// way Kafka consumers record them), so an event already dispatched to one channel is skipped on another; emit it with
+/// another id (see `emit_with_id`) to deliver it again. Events no handler matched are forgotten again (and
+/// dead-lettered). Sharing the store with `kafka::set_dedup_store` makes events processed only once across both
+/// backends.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::{Arc, Mutex};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dedup_store(Arc::new(common::InMemoryDedupStore::new(1024)));
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+///
+/// in_memory::emit(&OrderCreated { event_id: String::from("1") });
+/// in_memory::emit(&OrderCreated { event_id: String::from("1") });
+/// in_memory::emit(&OrderCreated { event_id: String::from("2") });
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2"]);
+///
+/// // keyed by the id, whichever the channel
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Audit"), OrderEventHandler);
+/// let audit = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Audit");
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") }, audit());
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("3") }, audit());
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3"]);
+/// ```
+pub fn set_dedup_store(dedup_store: Arc<dyn DedupStore>) {
+    info!(target: &common::format_target("EventHandlerRegistry"), "dedup store set");
+    *DEDUP_STORE.lock().unwrap() = Some(dedup_store);
+}
+
+/// Checks that the event survives `Event::to_json`/`from_json` round-trip, which features passing events across
+/// threads or time (async mode, async tasks, delayed emits, dead letters) rely on. These features check it themselves,
+/// once per event type, and skip (or dispatch synchronously) the events failing the check, logging the error.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderShipped {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderShipped {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderShipped", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderShipped {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderShipped"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// assert_eq!(in_memory::check_roundtrip(&OrderCreated { event_id: String::from("1") }), Ok(()));
+///
+/// let error = in_memory::check_roundtrip(&OrderShipped { event_id: String::from("2") }).unwrap_err();
+/// assert!(matches!(error, in_memory::RoundtripError::Panicked(_)));
+/// assert!(error.to_string().starts_with("Event::to_json panicked"));
+/// ```
+pub fn check_roundtrip(event: &dyn Event) -> Result<(), RoundtripError> {
+    let payload = to_json(event)?;
+    serde_json::from_str::<Box<dyn Event>>(&payload).map_err(|e| RoundtripError::Malformed(e.to_string()))?;
+    Ok(())
+}
+
+/// Re-emits buffered dead-letter events passing the predicate (e.g. of given type, or occurred after given time) to
+/// their original channels, on the calling thread, subject to the emit rate limit (see `set_rate_limit`). Events not
+/// passing the predicate, rejected by the rate limiter or not delivered to any handler stay buffered. Returns number
+/// of delivered events.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCanceled {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCanceled {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCanceled", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCanceled {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCanceled"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(format!("{}:{}", event.name(), event.id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dead_letter_capacity(16);
+///
+/// // no handler matches the channel yet, so the events are dead-lettered
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") }, channel());
+/// in_memory::emit_to_channel(&OrderCanceled { event_id: String::from("2") }, channel());
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("3") }, channel());
+///
+/// in_memory::register(channel(), OrderEventHandler);
+///
+/// assert_eq!(in_memory::replay_filtered(|event| event.name() == "OrderCreated"), 2);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["OrderCreated:1", "OrderCreated:3"]);
+///
+/// // the rest stays buffered
+/// assert_eq!(in_memory::replay_dead_letters(), 1);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["OrderCreated:1", "OrderCreated:3", "OrderCanceled:2"]);
+/// ```
+///
+/// Dead letters which can't be deserialized stay buffered as well, so they can be replayed once they can:
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use serde::{Deserialize, Deserializer, Serialize};
+/// use serde::de::Error;
+/// use eventure::{in_memory, model};
+///
+/// static READABLE: AtomicBool = AtomicBool::new(true);
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// fn readable<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+///     if !READABLE.load(Ordering::SeqCst) {
+///         return Err(D::Error::custom("unreadable"));
+///     }
+///     String::deserialize(deserializer)
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct InvoiceIssued {
+///     #[serde(deserialize_with = "readable")]
+///     event_id: String,
+/// }
+///
+/// impl Display for InvoiceIssued {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "InvoiceIssued", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for InvoiceIssued {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "InvoiceIssued"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct InvoiceEventHandler;
+///
+/// impl Display for InvoiceEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "InvoiceEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for InvoiceEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("InvoiceEventHandler")
+///     }
+/// }
+///
+/// in_memory::set_dead_letter_capacity(16);
+///
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Invoices");
+/// in_memory::emit_to_channel(&InvoiceIssued { event_id: String::from("1") }, channel());
+/// in_memory::register(channel(), InvoiceEventHandler);
+///
+/// READABLE.store(false, Ordering::SeqCst);
+/// assert_eq!(in_memory::replay_filtered(|_| true), 0);
+/// assert_eq!(in_memory::replay_dead_letters(), 0);
+///
+/// READABLE.store(true, Ordering::SeqCst);
+/// assert_eq!(in_memory::replay_dead_letters(), 1);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1"]);
+/// ```
+pub fn replay_filtered(predicate: impl Fn(&dyn Event) -> bool) -> usize {
+    let dead_letters = dead_letters::take();
+    info!(target: &common::format_target("EventHandlerRegistry"), "replaying {} dead letter(s) filtered", dead_letters.len());
+    let mut delivered = 0;
+    for dead_letter in dead_letters {
+        match serde_json::from_str::<Box<dyn Event>>(&dead_letter.payload) {
+            Ok(event) if !predicate(&*event) => dead_letters::restore(dead_letter),
+            Ok(event) => match rate_limiter::acquire() {
+                Ok(()) => if dispatch(&*event, dead_letter.channel, dead_letter.event_id.as_deref()) > 0 {
+                    delivered += 1;
+                },
+                Err(error) => {
+                    warn!(target: &common::format_target("EventHandlerRegistry"), "dead letter {} not replayed: {}", common::redacted(&*event), error);
+                    dead_letters::restore(dead_letter);
+                }
+            },
+            Err(e) => {
+                error!(target: &common::format_target("EventHandlerRegistry"), "unable to deserialize dead letter {}, kept: {}",
+                    common::redacted_payload(dead_letter.event_id.as_deref(), dead_letter.payload.as_bytes()), e);
+                dead_letters::restore(dead_letter);
+            }
+        }
+    }
+    delivered
+}
+
+/// Returns In-Memory emit metrics broken down by event name and channel (name of the channel the event was emitted
+/// to, or of the configured default channel): number of dispatched events and handler invocations.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderPaid {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderPaid {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderPaid", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderPaid {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderPaid"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct LoggingEventHandler {
+///     id: String,
+/// }
+///
+/// impl Display for LoggingEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.id)
+///     }
+/// }
+///
+/// impl model::EventHandler for LoggingEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", self.id, event)
+///     }
+///
+///     fn id(&self) -> String {
+///         self.id.clone()
+///     }
+/// }
+///
+/// let orders = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders");
+/// let payments = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Payments");
+/// in_memory::register(orders(), LoggingEventHandler { id: String::from("OrderLog") });
+/// in_memory::register(orders(), LoggingEventHandler { id: String::from("OrderAudit") });
+/// in_memory::register(payments(), LoggingEventHandler { id: String::from("PaymentLog") });
+///
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") }, orders());
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("2") }, orders());
+/// in_memory::emit_to_channel(&OrderPaid { event_id: String::from("3") }, orders());
+/// in_memory::emit_to_channel(&OrderPaid { event_id: String::from("4") }, payments());
+///
+/// let metrics = in_memory::metrics_by_event();
+/// let counts = |event_name: &str, channel: &str| metrics.get(&(event_name.to_string(), channel.to_string())).copied();
+/// assert_eq!(counts("OrderCreated", "Orders"), Some(in_memory::Counts { emitted: 2, handled: 4 }));
+/// assert_eq!(counts("OrderPaid", "Orders"), Some(in_memory::Counts { emitted: 1, handled: 2 }));
+/// assert_eq!(counts("OrderPaid", "Payments"), Some(in_memory::Counts { emitted: 1, handled: 1 }));
+/// assert_eq!(counts("OrderCreated", "Payments"), None);
+/// ```
+pub fn metrics_by_event() -> HashMap<(String, String), Counts> {
+    metrics::by_event()
+}
+
+/// Emits In-Memory event to handlers in registration order, stopping at the first handler failure (a panicking handler
+/// fails as well). Returns number of handlers invoked, if all of them succeeded. The event is dispatched on the calling
+/// thread (also in async mode), otherwise like `emit`: subject to the rate limit, dedup store, loop detection and
+/// middleware, and recorded in history, metrics and taps (dead-lettered if no handler matched). Rejected event fails
+/// with the rate limit error; events skipped as duplicates or emit loops invoke no handler.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::{Arc, Mutex};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, in_memory, model};
+///
+/// static INVOKED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
 ///         "OrderCreated"
 ///     }
 ///     fn as_any(&self) -> &dyn Any {
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         todo!()
+///         serde_json::to_string(&self).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
+/// struct ValidationHandler {
+///     id: String,
+///     valid: bool,
+/// }
+///
+/// impl Display for ValidationHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", self.id)
+///     }
+/// }
+///
+/// impl model::EventHandler for ValidationHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         INVOKED.lock().unwrap().push(self.id());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from(&self.id)
+///     }
+///
+///     fn try_handle(&self, event: &(dyn model::Event + '_)) -> Result<(), model::HandlerError> {
+///         self.handle(event);
+///         match self.valid {
+///             true => Ok(()),
+///             false => Err(model::HandlerError::new("invalid customer")),
+///         }
+///     }
+/// }
+///
+/// let channel = || in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
+/// in_memory::register(channel(), ValidationHandler { id: String::from("first"), valid: true });
+/// in_memory::register(channel(), ValidationHandler { id: String::from("second"), valid: false });
+/// in_memory::register(channel(), ValidationHandler { id: String::from("third"), valid: true });
+///
+/// let order_created = OrderCreated {
 ///     event_id: String::from("event_id"),
 ///     customer_id: String::from("customer_id"),
 /// };
 ///
-/// in_memory::emit(&order_created);
+/// let result = in_memory::emit_until_err(&order_created);
+///
+/// assert_eq!(result, Err(model::HandlerError::new("invalid customer")));
+/// assert_eq!(*INVOKED.lock().unwrap(), vec!["first", "second"]);
+///
+/// // dispatched like any other emit, so the dedup store skips the redelivery
+/// in_memory::set_dedup_store(Arc::new(common::InMemoryDedupStore::new(16)));
+/// let second_order = OrderCreated { event_id: String::from("event_id_2"), customer_id: String::from("customer_id") };
+/// assert!(in_memory::emit_until_err(&second_order).is_err());
+/// assert_eq!(in_memory::emit_until_err(&second_order), Ok(0));
+/// assert_eq!(INVOKED.lock().unwrap().len(), 4);
 /// ```
-pub fn emit(event: &dyn Event) {
-    HANDLER_REGISTRY.lock().unwrap().emit(event, None);
+pub fn emit_until_err(event: &dyn Event) -> Result<usize, HandlerError> {
+    rate_limiter::acquire().map_err(|error| HandlerError::new(error.to_string()))?;
+    dispatch_with(event, None, None, |registry, channel| {
+        let (invoked, result) = registry.emit_until_err(event, channel);
+        (invoked, Some(result))
+    }).unwrap_or(Ok(0))
 }
 
-/// Emits In-Memory event to specific message channel.
+/// Adds In-Memory hook invoked before every event handler invocation, receiving handler id and event.
 ///
 /// # Examples
 /// ```
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
 /// use serde::{Deserialize, Serialize};
 /// use eventure::{in_memory, model};
 ///
+/// static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
 /// #[derive(Serialize, Deserialize)]
 /// struct OrderCreated {
 ///     event_id: String,
@@ -355,58 +5161,536 @@ pub fn emit(event: &dyn Event) {
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         todo!()
+///         serde_json::to_string(&self).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         CALLS.lock().unwrap().push(format!("handle:{}", event.id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// in_memory::add_before_handle(|handler_id, event| {
+///     CALLS.lock().unwrap().push(format!("before:{}:{}", handler_id, event.id()));
+/// });
+/// in_memory::add_after_handle(|handler_id, event| {
+///     CALLS.lock().unwrap().push(format!("after:{}:{}", handler_id, event.id()));
+/// });
+///
+/// let handler_channel = in_memory::message_channel(in_memory::ChannelType::TOPIC, "Order");
+/// in_memory::register(handler_channel, OrderCreatedEventHandler);
+///
+/// let order_created = OrderCreated {
 ///     event_id: String::from("event_id"),
 ///     customer_id: String::from("customer_id"),
 /// };
-/// in_memory::emit_to_channel(&order_created, in_memory::MessageChannel { channel_type: in_memory::ChannelType::QUEUE, name: ".*" });
+/// in_memory::emit(&order_created);
+///
+/// assert_eq!(*CALLS.lock().unwrap(), vec![
+///     "before:OrderCreatedEventHandler:event_id",
+///     "handle:event_id",
+///     "after:OrderCreatedEventHandler:event_id",
+/// ]);
 /// ```
-pub fn emit_to_channel(event: &dyn Event, channel: MessageChannel) {
-    HANDLER_REGISTRY.lock().unwrap().emit(event, Some(channel));
+pub fn add_before_handle(hook: impl Fn(&str, &dyn Event) + Send + Sync + 'static) {
+    DEFAULT_REGISTRY.registry.lock().unwrap().add_before_handle(Arc::new(hook));
+}
+
+/// Adds In-Memory hook invoked after every event handler invocation, receiving handler id and event.
+///
+/// # Examples
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use eventure::in_memory;
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// in_memory::add_after_handle(|_handler_id, _event| {
+///     HANDLED.fetch_add(1, Ordering::SeqCst);
+/// });
+/// ```
+pub fn add_after_handle(hook: impl Fn(&str, &dyn Event) + Send + Sync + 'static) {
+    DEFAULT_REGISTRY.registry.lock().unwrap().add_after_handle(Arc::new(hook));
+}
+
+/// Registers In-Memory tap, invoked after every event dispatch (matched or not) with the event and whether it was
+/// delivered to any handler, e.g. to debug routing. Duplicates skipped by the dedup store aren't tapped.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, model};
+///
+/// static TAPPED: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {}
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+/// in_memory::register_tap(|event, was_delivered| {
+///     TAPPED.lock().unwrap().push((event.id().to_string(), was_delivered));
+/// });
+///
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") },
+///                            in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"));
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("2") },
+///                            in_memory::message_channel(in_memory::ChannelType::TOPIC, "Invoices"));
+///
+/// assert_eq!(*TAPPED.lock().unwrap(), vec![(String::from("1"), true), (String::from("2"), false)]);
+/// ```
+pub fn register_tap(tap: impl Fn(&dyn Event, bool) + Send + Sync + 'static) {
+    DEFAULT_REGISTRY.registry.lock().unwrap().add_tap(Arc::new(tap));
+}
+
+/// Exports In-Memory event routing topology (channels and handlers subscribed to each) as Graphviz DOT diagram.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use eventure::{in_memory, model};
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// struct AccountEventHandler;
+///
+/// impl Display for AccountEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "AccountEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for AccountEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "AccountEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("AccountEventHandler")
+///     }
+/// }
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::QUEUE, "Accounts"), AccountEventHandler);
+///
+/// let topology = in_memory::export_topology();
+///
+/// assert!(topology.starts_with("digraph eventure {"));
+/// assert!(topology.contains("\"TOPIC:Orders\" [shape=box];"));
+/// assert!(topology.contains("\"QUEUE:Accounts\" [shape=box];"));
+/// assert!(topology.contains("\"OrderEventHandler\" [shape=ellipse];"));
+/// assert!(topology.contains("\"TOPIC:Orders\" -> \"OrderEventHandler\";"));
+/// assert!(topology.contains("\"QUEUE:Accounts\" -> \"AccountEventHandler\";"));
+/// ```
+pub fn export_topology() -> String {
+    DEFAULT_REGISTRY.registry.lock().unwrap().export_topology()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Dispatches event to the matching handlers, on the calling thread. Handlers are invoked on a registry snapshot,
+/// so they're free to emit (or register) further events. Overridden event id (if any) is exposed to the handlers
+/// and hooks via `current_event_id` for the duration of the dispatch. Event no handler matched is dead-lettered.
+/// Returns number of handlers the event was dispatched to.
+pub(super) fn dispatch(event: &dyn Event, channel: Option<MessageChannel>, event_id: Option<&str>) -> usize {
+    dispatch_with(event, channel, event_id, |registry, channel| {
+        let handled = registry.emit(event, channel);
+        (handled, handled)
+    })
+}
+
+/// Dispatches event like `dispatch`, reporting result of each handler.
+pub(super) fn dispatch_reporting(event: &dyn Event, channel: Option<MessageChannel>, event_id: Option<&str>) -> EmitReport {
+    let results = dispatch_with(event, channel, event_id, |registry, channel| {
+        let results = registry.emit_reporting(event, channel);
+        (results.len(), results)
+    });
+    EmitReport {
+        event_id: String::from(event_id.unwrap_or(event.id())),
+        results,
+    }
+}
+
+/// Serializes event via `Event::to_json` for the features deserializing it later. Round-trip of the event type is
+/// checked (see `check_roundtrip`) the first time only, then just the panic is guarded.
+pub(super) fn serialize(event: &dyn Event) -> Result<String, RoundtripError> {
+    if VERIFIED_EVENTS.lock().unwrap().iter().any(|name| name == event.name()) {
+        return to_json(event);
+    }
+    let payload = to_json(event)?;
+    serde_json::from_str::<Box<dyn Event>>(&payload).map_err(|e| RoundtripError::Malformed(e.to_string()))?;
+    VERIFIED_EVENTS.lock().unwrap().push(String::from(event.name()));
+    Ok(payload)
+}
+
+/// Dispatches request event (see `request`) with the correlation id, exposed to the handlers of the request only (for
+/// `reply`), not to the handlers of the events they emit.
+pub(super) fn with_correlation_id<T>(correlation_id: Option<String>, dispatch: impl FnOnce() -> T) -> T {
+    PENDING_CORRELATION_ID.with(|pending| pending.replace(correlation_id));
+    let result = dispatch();
+    PENDING_CORRELATION_ID.with(|pending| pending.take());
+    result
+}
+
+/// Dispatches lifecycle event to the handlers registered with the lifecycle channel, on the calling thread.
+pub(super) fn dispatch_lifecycle(event: &LifecycleEvent) {
+    let registry = DEFAULT_REGISTRY.snapshot();
+    registry.emit_lifecycle(event);
 }
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Private statics
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
-static HANDLER_REGISTRY: Mutex<EventHandlerRegistryImpl> = Mutex::new(EventHandlerRegistryImpl::new());
+static DEFAULT_REGISTRY: LocalRegistry = LocalRegistry::new();
 static BROKER_CONFIGURATION: Mutex<MessageBrokerConfigurationInternal> = Mutex::new(MessageBrokerConfigurationInternal::new());
+static DEDUP_STORE: Mutex<Option<Arc<dyn DedupStore>>> = Mutex::new(None);
+static VERIFIED_EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static MAX_FANOUT: Mutex<Option<(usize, FanoutMode)>> = Mutex::new(None);
+/// Worker thread dispatching signals in synchronous mode (see `emit_signal`), started with the first one.
+static SIGNAL_WORKER: Mutex<Option<Sender<SignalJob>>> = Mutex::new(None);
+
+thread_local! {
+    static CURRENT_EVENT_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+    static ON_SIGNAL_WORKER: Cell<bool> = const { Cell::new(false) };
+    /// Correlation id of the request about to be dispatched, taken by its dispatch (see `with_correlation_id`).
+    static PENDING_CORRELATION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Correlation id of the request being handled on the current thread (see `reply`).
+    static CURRENT_CORRELATION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+const IDEMPOTENCY_KEY_PREFIX: &str = "idempotency:";
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Private structs
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
+#[derive(Clone)]
 struct MessageChannelInternal {
     channel_type: ChannelType,
     name_regex: Option<Regex>,
 }
 
-struct MessageBrokerConfigurationInternal {
-    message_channel: MessageChannelInternal,
-    is_async: bool,
+struct MessageBrokerConfigurationInternal {
+    message_channel: MessageChannelInternal,
+    is_async: bool,
+}
+
+/// Channel name which isn't a valid regex, with the compilation error.
+struct InvalidChannelName {
+    name: String,
+    reason: String,
+}
+
+#[derive(Clone)]
+struct EventHandlerRegistryImpl {
+    handler_configs: Vec<Arc<HandlerConfiguration>>,
+    disabled_groups: Vec<String>,
+    before_handle_hooks: Vec<HandleHook>,
+    after_handle_hooks: Vec<HandleHook>,
+    taps: Vec<Tap>,
+    /// Handlers matching the emit channels, for the current handlers (none until a handler is registered).
+    match_cache: Option<Arc<MatchCache>>,
+}
+
+struct HandlerConfiguration {
+    handler: Box<dyn EventHandler + Send + Sync>,
+    channel: MessageChannelInternal,
+    group: Option<String>,
+    filter: Option<Arc<JsonPathFilter>>,
+}
+
+type HandleHook = Arc<dyn Fn(&str, &dyn Event) + Send + Sync>;
+
+type Tap = Arc<dyn Fn(&dyn Event, bool) + Send + Sync>;
+
+#[cfg(feature = "tokio")]
+struct TaskForwarder {
+    id: String,
+    description: String,
+    sender: tokio::sync::mpsc::UnboundedSender<String>,
+}
+
+/// Filter of the events dispatched to the handler (see `register_jsonpath`).
+struct JsonPathFilter {
+    jsonpath: String,
+    compiled: jsonpath_lib::Compiled,
+}
+
+struct ErrorCallbackHandler {
+    handler: Box<dyn EventHandler + Send + Sync>,
+    on_error: ErrorCallback,
+}
+
+type ErrorCallback = Box<dyn Fn(&dyn Event, &HandlerError) + Send + Sync>;
+
+/// Event handler wrapper, admitting at most `max` concurrent invocations of the handler (counting semaphore), the
+/// others waiting for a permit.
+struct ConcurrencyLimitedHandler {
+    handler: Box<dyn EventHandler + Send + Sync>,
+    max: usize,
+    /// Threads holding the permits, once per invocation in flight.
+    holders: Mutex<Vec<ThreadId>>,
+    released: Condvar,
+}
+
+/// Handler registered along with another owner of it (e.g. replaying events to the handler as well).
+struct SharedHandler(Arc<dyn EventHandler + Send + Sync>);
+
+struct PipelineHandler {
+    stages: Vec<Box<dyn TransformHandler + Send + Sync>>,
+}
+
+/// Permit of the concurrency limited handler invocation, released when dropped (also if the handler panics).
+struct Permit<'a> {
+    handler: &'a ConcurrencyLimitedHandler,
+    holder: ThreadId,
+}
+
+/// Id of the event current before dispatch, restored when dropped (also if dispatch panics).
+struct PreviousEventId(Option<String>);
+
+/// Correlation id of the request current before dispatch, restored when dropped (also if dispatch panics).
+struct PreviousCorrelationId(Option<String>);
+
+/// Signal queued for the signal worker, with the context of the emitting thread (like async dispatcher jobs).
+struct SignalJob {
+    payload: String,
+    trace: Option<TraceContext>,
+    cause: Option<Ancestry>,
+    on_complete: Box<dyn FnOnce(EmitReport) + Send>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private traits
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+trait EventHandlerRegistry {
+    fn register(&mut self, message_channel: MessageChannelInternal, event_handler: Box<dyn EventHandler + Send + Sync>, group: Option<String>,
+                filter: Option<Arc<JsonPathFilter>>);
+    fn replace(&mut self, message_channel: MessageChannelInternal, event_handler: Box<dyn EventHandler + Send + Sync>) -> bool;
+    fn unregister(&mut self, event_handler: Box<dyn EventHandler + Send>) -> bool;
+    fn clear(&mut self);
+    fn set_group_enabled(&mut self, group: &str, enabled: bool);
+    fn unregister_group(&mut self, group: &str) -> Vec<String>;
+    fn emit(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> usize;
+    fn emit_reporting(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> Vec<(String, Result<(), HandlerError>)>;
+    fn emit_lifecycle(&self, event: &LifecycleEvent);
+    fn emit_until_err(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> (usize, Result<usize, HandlerError>);
+    fn add_before_handle(&mut self, hook: HandleHook);
+    fn add_after_handle(&mut self, hook: HandleHook);
+    fn add_tap(&mut self, tap: Tap);
+    fn export_topology(&self) -> String;
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+fn handler_channel(message_channel: MessageChannel, handler_id: &str) -> Option<MessageChannelInternal> {
+    MessageChannelInternal::try_from(message_channel)
+        .map_err(RegistrationError::from)
+        .inspect_err(|error| error!(target: &common::format_target("EventHandlerRegistry"), "{} not registered: {}", handler_id, error))
+        .ok()
+}
+
+/// Sends signal to the signal worker (started if not running yet). The callback is given back, if the signal is
+/// emitted on the worker thread itself (which would wait for itself otherwise) or the event can't be serialized.
+fn send_signal(event: &dyn Event, on_complete: Box<dyn FnOnce(EmitReport) + Send>) -> Result<(), Box<dyn FnOnce(EmitReport) + Send>> {
+    if ON_SIGNAL_WORKER.get() {
+        return Err(on_complete);
+    }
+    let payload = match serialize(event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!(target: &common::format_target("EventHandlerRegistry"), "signal {} dispatched on the calling thread: {}", common::redacted(event), e);
+            return Err(on_complete);
+        }
+    };
+    let job = SignalJob { payload, trace: trace::current(), cause: causes::current(), on_complete };
+    let mut worker = SIGNAL_WORKER.lock().unwrap();
+    worker.get_or_insert_with(start_signal_worker).send(job).map_err(|mpsc::SendError(job)| job.on_complete)
+}
+
+fn start_signal_worker() -> Sender<SignalJob> {
+    let (sender, receiver) = mpsc::channel::<SignalJob>();
+    thread::spawn(move || {
+        ON_SIGNAL_WORKER.set(true);
+        for job in receiver {
+            match serde_json::from_str::<Box<dyn Event>>(&job.payload) {
+                Ok(event) => {
+                    let dispatched = panic::catch_unwind(AssertUnwindSafe(|| trace::with_current(job.trace, ||
+                        causes::with_current(job.cause, || (job.on_complete)(dispatch_reporting(&*event, None, None))))));
+                    if dispatched.is_err() {
+                        error!(target: &common::format_target("EventHandlerRegistry"), "signal {} dispatch failed", common::redacted(&*event));
+                    }
+                }
+                Err(e) => error!(target: &common::format_target("EventHandlerRegistry"), "unable to deserialize signal: {}", e),
+            }
+        }
+    });
+    sender
+}
+
+fn to_json(event: &dyn Event) -> Result<String, RoundtripError> {
+    panic::catch_unwind(AssertUnwindSafe(|| event.to_json())).map_err(|panic| {
+        let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        RoundtripError::Panicked(message)
+    })
 }
 
-struct EventHandlerRegistryImpl {
-    handler_configs: Vec<HandlerConfiguration>,
+/// Handles the event, wrapped in the middleware (see `common::set_middleware`), if set. Failure reported by the
+/// middleware (e.g. the handler panicking) is logged.
+fn handle_with_middleware(event_handler: &(impl EventHandler + ?Sized), event: &dyn Event) {
+    let handled = common::with_middleware(event, &|event| {
+        event_handler.handle(event);
+        Ok(())
+    });
+    if let Err(e) = handled {
+        error!(target: &common::format_target("EventHandlerRegistry"), "handler {} failed on event {}: {}",
+            event_handler, common::redacted(event), e);
+    }
 }
 
-struct HandlerConfiguration {
-    handler: Box<dyn EventHandler + Send>,
-    channel: MessageChannelInternal,
+/// Applies maximum fan-out (if set) to the handlers matched by the event.
+fn limit_fanout<'a>(event: &dyn Event, mut configs: Vec<&'a HandlerConfiguration>) -> Vec<&'a HandlerConfiguration> {
+    let max_fanout = *MAX_FANOUT.lock().unwrap();
+    let (max_fanout, mode) = match max_fanout {
+        Some((max_fanout, mode)) if configs.len() > max_fanout => (max_fanout, mode),
+        _ => return configs,
+    };
+    match mode {
+        FanoutMode::WARN => warn!(target: &common::format_target("EventHandlerRegistry"),
+            "event {} matched {} handlers, exceeding max fan-out of {}", common::redacted(event), configs.len(), max_fanout),
+        FanoutMode::STRICT => error!(target: &common::format_target("EventHandlerRegistry"),
+            "event {} matched {} handlers, exceeding max fan-out of {}", common::redacted(event), configs.len(), max_fanout),
+        FanoutMode::CAP => {
+            warn!(target: &common::format_target("EventHandlerRegistry"),
+                "event {} matched {} handlers, exceeding max fan-out of {}, delivered to the first {} only",
+                common::redacted(event), configs.len(), max_fanout, max_fanout);
+            configs.truncate(max_fanout);
+        }
+    }
+    configs
 }
 
-// -----------------------------------------------------------------------------------------------------------------------------------------
-// Private traits
-// -----------------------------------------------------------------------------------------------------------------------------------------
-
-trait EventHandlerRegistry {
-    fn register(&mut self, message_channel: MessageChannelInternal, event_handler: Box<dyn EventHandler + Send>);
-    fn unregister(&mut self, event_handler: Box<dyn EventHandler + Send>);
-    fn emit(&self, event: &dyn Event, channel: Option<MessageChannel>);
+fn dispatch_with<T: Default>(event: &dyn Event, channel: Option<MessageChannel>, event_id: Option<&str>,
+                             emit: impl FnOnce(&EventHandlerRegistryImpl, Option<&MessageChannel>) -> (usize, T)) -> T {
+    let correlation_id = PENDING_CORRELATION_ID.with(|pending| pending.take());
+    let dedup_id = event_id.unwrap_or(event.id());
+    let ancestry = match causes::extend(dedup_id, event.name()) {
+        Ok(ancestry) => ancestry,
+        Err(loop_detected) => {
+            error!(target: &common::format_target("EventHandlerRegistry"), "event {} not dispatched: {}", common::redacted(event), loop_detected);
+            return T::default();
+        }
+    };
+    let dedup_store = DEDUP_STORE.lock().unwrap().clone();
+    if dedup_store.as_ref().is_some_and(|dedup_store| !dedup_store.insert(dedup_id)) {
+        info!(target: &common::format_target("EventHandlerRegistry"), "duplicate event {} skipped", common::redacted(event));
+        return T::default();
+    }
+    history::record(event, channel.as_ref());
+    let registry = DEFAULT_REGISTRY.snapshot();
+    if let Some(event_id) = event_id {
+        debug!(target: &common::format_target("EventHandlerRegistry"), "event {} dispatched with id {}", common::redacted(event), event_id);
+    }
+    let current_event_id = String::from(dedup_id);
+    let previous_event_id = PreviousEventId(CURRENT_EVENT_ID.with(|current| current.replace(Some(current_event_id))));
+    let previous_correlation_id = PreviousCorrelationId(CURRENT_CORRELATION_ID.with(|current| current.replace(correlation_id)));
+    let (handled, result) = causes::with_current(Some(ancestry), ||
+        trace::in_span(|| sequencer::sequenced(|| emit(&registry, channel.as_ref()))));
+    drop(previous_correlation_id);
+    drop(previous_event_id);
+    match &channel {
+        Some(channel) => metrics::record(event.name(), &channel.name, handled),
+        None => metrics::record(event.name(), BROKER_CONFIGURATION.lock().unwrap().message_channel.name(), handled),
+    }
+    registry.taps.iter().for_each(|tap| tap(event, handled > 0));
+    if handled == 0 {
+        if let Some(dedup_store) = dedup_store {
+            dedup_store.remove(dedup_id);
+        }
+        dead_letters::push(event, channel.as_ref(), event_id);
+    }
+    result
 }
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
@@ -421,17 +5705,26 @@ impl MessageChannelInternal {
         }
     }
 
-    fn from(message_channel: MessageChannel) -> Self {
-        MessageChannelInternal {
+    fn try_from(message_channel: MessageChannel) -> Result<Self, InvalidChannelName> {
+        let name_regex = Regex::new(&message_channel.name)
+            .map_err(|reason| InvalidChannelName { name: message_channel.name.to_string(), reason: reason.to_string() })?;
+        Ok(MessageChannelInternal {
             channel_type: message_channel.channel_type,
-            name_regex: Some(Regex::new(message_channel.name).unwrap()),
-        }
+            name_regex: Some(name_regex),
+        })
+    }
+
+    fn name(&self) -> &str {
+        self.name_regex.as_ref().map_or("", |regex| regex.as_str())
     }
 
     fn matches(&self, channel: &MessageChannel) -> bool {
+        self.channel_type == channel.channel_type && self.name_matches(&channel.name)
+    }
+
+    fn name_matches(&self, channel_name: &str) -> bool {
         match &self.name_regex {
-            Some(regex) => self.channel_type == channel.channel_type
-                && (regex.captures(channel.name).is_some() || channel.name == "*"),
+            Some(regex) => channel_name == "*" || regex.is_match(channel_name),
             None => false
         }
     }
@@ -445,11 +5738,12 @@ impl MessageBrokerConfigurationInternal {
         }
     }
 
-    fn from(configuration: MessageBrokerConfiguration) -> Self {
-        MessageBrokerConfigurationInternal {
-            message_channel: MessageChannelInternal::from(configuration.message_channel),
+    fn try_from(configuration: MessageBrokerConfiguration) -> Result<Self, ConfigurationError> {
+        let message_channel = MessageChannelInternal::try_from(configuration.message_channel)?;
+        Ok(MessageBrokerConfigurationInternal {
+            message_channel,
             is_async: configuration.is_async,
-        }
+        })
     }
 
     fn update(&mut self, configuration: MessageBrokerConfigurationInternal) {
@@ -458,54 +5752,295 @@ impl MessageBrokerConfigurationInternal {
     }
 }
 
+impl LocalRegistry {
+    pub const fn new() -> Self {
+        LocalRegistry {
+            registry: Mutex::new(EventHandlerRegistryImpl::new()),
+        }
+    }
+
+    /// Registers event handler with this registry (unless its channel name isn't a valid regex, logging an error).
+    pub fn register(&self, message_channel: MessageChannel, event_handler: impl EventHandler + Send + Sync + 'static) {
+        if let Some(handler_channel) = handler_channel(message_channel, &event_handler.id()) {
+            self.registry.lock().unwrap().register(handler_channel, Box::new(event_handler), None, None);
+        }
+    }
+
+    /// Unregisters event handler from this registry.
+    pub fn unregister(&self, event_handler: impl EventHandler + Send + 'static) {
+        self.registry.lock().unwrap().unregister(Box::new(event_handler));
+    }
+
+    /// Emits event to all handlers of this registry, on the calling thread.
+    pub fn emit(&self, event: &dyn Event) {
+        self.snapshot().emit(event, None);
+    }
+
+    /// Emits event to the handlers of this registry matching the channel, on the calling thread.
+    pub fn emit_to_channel(&self, event: &dyn Event, channel: MessageChannel) {
+        self.snapshot().emit(event, Some(&channel));
+    }
+
+    fn snapshot(&self) -> EventHandlerRegistryImpl {
+        self.registry.lock().unwrap().clone()
+    }
+}
+
+impl Default for AsyncSettings {
+    fn default() -> Self {
+        async_settings(1024, 1, true)
+    }
+}
+
+impl Default for LocalRegistry {
+    fn default() -> Self {
+        LocalRegistry::new()
+    }
+}
+
 impl EventHandlerRegistryImpl {
     const fn new() -> Self {
-        EventHandlerRegistryImpl { handler_configs: Vec::new() }
+        EventHandlerRegistryImpl {
+            handler_configs: Vec::new(),
+            disabled_groups: Vec::new(),
+            before_handle_hooks: Vec::new(),
+            after_handle_hooks: Vec::new(),
+            taps: Vec::new(),
+            match_cache: None,
+        }
     }
-}
 
-impl EventHandlerRegistry for EventHandlerRegistryImpl {
-    fn register(&mut self, channel: MessageChannelInternal, handler: Box<dyn EventHandler + Send>) {
-        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event handler registered: {}",handler);
-        self.handler_configs.push(HandlerConfiguration { handler, channel });
+    /// Drops the handlers memoized as matching the emit channels, once the handlers change (snapshots taken before
+    /// keep the memo matching their handlers).
+    fn reset_match_cache(&mut self) {
+        self.match_cache = Some(Arc::new(MatchCache::default()));
     }
 
-    fn unregister(&mut self, event_handler: Box<dyn EventHandler + Send>) {
-        let removed = self.handler_configs.iter()
-            .position(|config| *config.handler.id() == event_handler.id())
-            .map(|config| self.handler_configs.remove(config))
-            .is_some();
-        if removed {
-            info!(target: &common::format_target("EventHandlerRegistry"), "event handler unregistered: {}", event_handler);
+    /// Returns positions of the handlers whose channel name matches the emit channel name (memoized per registry state).
+    fn matching_names(&self, channel_name: &str) -> Arc<[usize]> {
+        let compute = || -> Vec<usize> {
+            self.handler_configs.iter().enumerate()
+                .filter(|(_, config)| config.channel.name_matches(channel_name))
+                .map(|(position, _)| position)
+                .collect()
+        };
+        match &self.match_cache {
+            Some(match_cache) => match_cache.matching(channel_name, compute),
+            None => compute().into(),
         }
     }
 
-    fn emit(&self, event: &dyn Event, channel_option: Option<MessageChannel>) {
-        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event emitted: {}", event);
+    /// Returns handlers the event is dispatched to: all of them for not-specified channel, otherwise the ones matching
+    /// the channel (just the first one for QUEUE).
+    fn matching(&self, event: &dyn Event, channel_option: Option<&MessageChannel>) -> Vec<&HandlerConfiguration> {
+        let mut configs = Vec::new();
         match channel_option {
             Some(channel) =>
-                for config in self.handler_configs.iter() {
-                    if config.channel.matches(&channel) {
+                for config in self.matching_names(&channel.name).iter()
+                    .map(|position| &self.handler_configs[*position])
+                    .filter(|config| self.is_enabled(config)) {
+                    if config.channel.channel_type == channel.channel_type && self.accepts(config, event) {
                         info!(target: &common::format_target("EventHandlerRegistry"),
-                            "channel matched (handler: {}, channel: {}, event: {})", config.handler, channel, event);
-                        config.handler.handle(event);
+                            "channel matched (handler: {}, channel: {}, event: {})", config.handler, channel, common::redacted(event));
+                        configs.push(config.as_ref());
                         if channel.channel_type == ChannelType::QUEUE {
                             debug!(target: "EventHandlerRegistry",
-                                "event handlers loop stopped for event {} in QUEUE", event);
+                                "event handlers loop stopped for event {} in QUEUE", common::redacted(event));
                             break;
                         }
                     } else {
                         debug!(target: &common::format_target("EventHandlerRegistry"),
-                            "channel not matched (handler: {}, channel: {}, event: {})", config.handler, channel, event);
+                            "channel not matched (handler: {}, channel: {}, event: {})", config.handler, channel, common::redacted(event));
                     }
                 }
             None =>
-                for config in self.handler_configs.iter() {
+                for config in self.handler_configs.iter().filter(|config| self.is_enabled(config) && self.accepts(config, event)) {
                     info!(target: &common::format_target("EventHandlerRegistry"),
-                        "not-specified channel matched by default (handler: {}, event: {})", config.handler, event);
-                    config.handler.handle(event);
+                        "not-specified channel matched by default (handler: {}, event: {})", config.handler, common::redacted(event));
+                    configs.push(config.as_ref());
                 }
         }
+        limit_fanout(event, configs)
+    }
+
+    fn is_enabled(&self, config: &HandlerConfiguration) -> bool {
+        config.group.as_ref().is_none_or(|group| !self.disabled_groups.contains(group))
+    }
+
+    /// Checks, whether the event passes the handler's filter, if any.
+    fn accepts(&self, config: &HandlerConfiguration, event: &dyn Event) -> bool {
+        let accepted = config.filter.as_ref().is_none_or(|filter| filter.matches(event));
+        if !accepted {
+            debug!(target: &common::format_target("EventHandlerRegistry"), "event {} filtered out for handler {}",
+                common::redacted(event), config.handler);
+        }
+        accepted
+    }
+
+    fn handle(&self, config: &HandlerConfiguration, event: &dyn Event) {
+        self.with_hooks(config, event, || handle_with_middleware(&*config.handler, event));
+    }
+
+    fn try_handle(&self, config: &HandlerConfiguration, event: &dyn Event) -> Result<(), HandlerError> {
+        self.with_hooks(config, event, || common::with_middleware(event, &|event| config.handler.try_handle(event)))
+    }
+
+    fn with_hooks<T>(&self, config: &HandlerConfiguration, event: &dyn Event, handle: impl FnOnce() -> T) -> T {
+        let handler_id = config.handler.id();
+        self.before_handle_hooks.iter().for_each(|hook| hook(&handler_id, event));
+        let result = handle();
+        self.after_handle_hooks.iter().for_each(|hook| hook(&handler_id, event));
+        result
+    }
+}
+
+impl EventHandlerRegistry for EventHandlerRegistryImpl {
+    fn register(&mut self, channel: MessageChannelInternal, handler: Box<dyn EventHandler + Send + Sync>, group: Option<String>,
+                filter: Option<Arc<JsonPathFilter>>) {
+        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event handler registered: {}",handler);
+        self.handler_configs.push(Arc::new(HandlerConfiguration { handler, channel, group, filter }));
+        self.reset_match_cache();
+    }
+
+    fn replace(&mut self, channel: MessageChannelInternal, handler: Box<dyn EventHandler + Send + Sync>) -> bool {
+        let handler_id = handler.id();
+        let position = self.handler_configs.iter().position(|config| config.handler.id() == handler_id);
+        // replacing handler stays in the group of the replaced one, filtered the same way
+        let group = position.and_then(|position| self.handler_configs[position].group.clone());
+        let filter = position.and_then(|position| self.handler_configs[position].filter.clone());
+        let config = Arc::new(HandlerConfiguration { handler, channel, group, filter });
+        self.reset_match_cache();
+        match position {
+            Some(position) => {
+                // in-flight invocations hold their own reference, so the old handler is dropped once they complete
+                let replaced = std::mem::replace(&mut self.handler_configs[position], config);
+                info!(target: &common::format_target("EventHandlerRegistry"),
+                    "in-memory event handler {} replaced by {} ({} in-flight)", replaced.handler, self.handler_configs[position].handler,
+                    Arc::strong_count(&replaced) - 1);
+                true
+            }
+            None => {
+                info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event handler registered: {}", config.handler);
+                self.handler_configs.push(config);
+                false
+            }
+        }
+    }
+
+    fn unregister(&mut self, event_handler: Box<dyn EventHandler + Send>) -> bool {
+        let removed = self.handler_configs.iter()
+            .position(|config| *config.handler.id() == event_handler.id())
+            .map(|config| self.handler_configs.remove(config))
+            .is_some();
+        if removed {
+            info!(target: &common::format_target("EventHandlerRegistry"), "event handler unregistered: {}", event_handler);
+            self.reset_match_cache();
+        }
+        removed
+    }
+
+    fn clear(&mut self) {
+        info!(target: &common::format_target("EventHandlerRegistry"), "all {} event handlers unregistered", self.handler_configs.len());
+        self.handler_configs.clear();
+        self.reset_match_cache();
+    }
+
+    fn set_group_enabled(&mut self, group: &str, enabled: bool) {
+        self.disabled_groups.retain(|disabled_group| disabled_group != group);
+        if !enabled {
+            self.disabled_groups.push(String::from(group));
+        }
+        info!(target: &common::format_target("EventHandlerRegistry"), "event handler group {} {}", group,
+            if enabled { "enabled" } else { "disabled" });
+    }
+
+    fn unregister_group(&mut self, group: &str) -> Vec<String> {
+        let mut unregistered = Vec::new();
+        self.handler_configs.retain(|config| match config.group.as_deref() == Some(group) {
+            true => {
+                unregistered.push(config.handler.id());
+                false
+            }
+            false => true,
+        });
+        info!(target: &common::format_target("EventHandlerRegistry"), "{} event handlers of group {} unregistered", unregistered.len(), group);
+        self.reset_match_cache();
+        unregistered
+    }
+
+    fn emit(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> usize {
+        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event emitted: {}", common::redacted(event));
+        let configs = self.matching(event, channel);
+        configs.iter().for_each(|config| self.handle(config, event));
+        configs.len()
+    }
+
+    fn emit_reporting(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> Vec<(String, Result<(), HandlerError>)> {
+        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event emitted with report: {}", common::redacted(event));
+        self.matching(event, channel).iter()
+            .map(|config| {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| self.try_handle(config, event)))
+                    .unwrap_or_else(|_| Err(HandlerError::new(format!("event handler {} panicked", config.handler))));
+                (config.handler.id(), result)
+            })
+            .collect()
+    }
+
+    fn emit_lifecycle(&self, event: &LifecycleEvent) {
+        debug!(target: &common::format_target("EventHandlerRegistry"), "in-memory lifecycle event emitted: {}", common::redacted(event));
+        for config in self.handler_configs.iter().filter(|config| config.channel.name() == LIFECYCLE_CHANNEL && self.is_enabled(config)) {
+            self.handle(config, event);
+        }
+    }
+
+    fn emit_until_err(&self, event: &dyn Event, channel: Option<&MessageChannel>) -> (usize, Result<usize, HandlerError>) {
+        info!(target: &common::format_target("EventHandlerRegistry"), "in-memory event emitted until error: {}", common::redacted(event));
+        let mut handled = 0;
+        for config in self.matching(event, channel) {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.try_handle(config, event)))
+                .unwrap_or_else(|_| Err(HandlerError::new(format!("event handler {} panicked", config.handler))));
+            if let Err(error) = result {
+                info!(target: &common::format_target("EventHandlerRegistry"),
+                    "event handlers loop stopped (handler: {}, event: {}, error: {})", config.handler, common::redacted(event), error);
+                return (handled + 1, Err(error));
+            }
+            handled += 1;
+        }
+        (handled, Ok(handled))
+    }
+
+    fn add_before_handle(&mut self, hook: HandleHook) {
+        self.before_handle_hooks.push(hook);
+    }
+
+    fn add_after_handle(&mut self, hook: HandleHook) {
+        self.after_handle_hooks.push(hook);
+    }
+
+    fn add_tap(&mut self, tap: Tap) {
+        self.taps.push(tap);
+    }
+
+    fn export_topology(&self) -> String {
+        let node_id = |id: &str| format!("\"{}\"", id.replace('"', "\\\""));
+        let channel_id = |config: &HandlerConfiguration|
+            node_id(&format!("{:?}:{}", config.channel.channel_type, config.channel.name()));
+
+        let mut topology = String::from("digraph eventure {\n");
+        let mut channels: Vec<String> = Vec::new();
+        for config in self.handler_configs.iter() {
+            let channel = channel_id(config);
+            if !channels.contains(&channel) {
+                writeln!(topology, "    {} [shape=box];", channel).unwrap();
+                channels.push(channel);
+            }
+        }
+        for config in self.handler_configs.iter() {
+            writeln!(topology, "    {} [shape=ellipse];", node_id(&config.handler.id())).unwrap();
+            writeln!(topology, "    {} -> {};", channel_id(config), node_id(&config.handler.id())).unwrap();
+        }
+        topology.push('}');
+        topology
     }
 }
 
@@ -515,8 +6050,327 @@ impl Display for MessageChannel {
     }
 }
 
+#[cfg(feature = "tokio")]
+impl EventHandler for TaskForwarder {
+    fn handle(&self, event: &dyn Event) {
+        match serialize(event) {
+            Ok(payload) => if self.sender.send(payload).is_err() {
+                warn!(target: &common::format_target("EventHandlerRegistry"), "event handler task finished, event {} dropped", common::redacted(event));
+            },
+            Err(e) => error!(target: &common::format_target("EventHandlerRegistry"),
+                "event {} not forwarded to the task {}: {}", common::redacted(event), self.description, e),
+        }
+    }
+
+    fn id(&self) -> String {
+        String::from(&self.id)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Display for TaskForwarder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (task)", self.description)
+    }
+}
+
+impl JsonPathFilter {
+    fn matches(&self, event: &dyn Event) -> bool {
+        let value: Value = match serde_json::from_str(&event.to_json()) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(target: &common::format_target("EventHandlerRegistry"), "event {} isn't valid JSON: {}", common::redacted(event), e);
+                return false;
+            }
+        };
+        match self.compiled.select(&value) {
+            Ok(selected) => selected.iter().any(|value| !matches!(value, Value::Null | Value::Bool(false))),
+            Err(e) => {
+                warn!(target: &common::format_target("EventHandlerRegistry"),
+                    "JSONPath {} not evaluated for event {}: {}", self.jsonpath, common::redacted(event), e);
+                false
+            }
+        }
+    }
+}
+
+impl EventHandler for ErrorCallbackHandler {
+    fn handle(&self, event: &dyn Event) {
+        let _ = self.try_handle(event);
+    }
+
+    fn id(&self) -> String {
+        self.handler.id()
+    }
+
+    fn try_handle(&self, event: &dyn Event) -> Result<(), HandlerError> {
+        self.handler.try_handle(event).inspect_err(|error| {
+            warn!(target: &common::format_target("EventHandlerRegistry"), "event handler {} failed on {}: {}", self.handler, common::redacted(event), error);
+            (self.on_error)(event, error);
+        })
+    }
+}
+
+impl Display for ErrorCallbackHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.handler)
+    }
+}
+
+impl ConcurrencyLimitedHandler {
+    /// Acquires permit, waiting while `max` invocations are in flight. Fails instead of waiting, if the calling thread
+    /// holds a permit already: the permits in flight could then be held by the waiting threads only.
+    fn acquire(&self) -> Result<Permit<'_>, HandlerError> {
+        let holder = thread::current().id();
+        let holders = self.holders.lock().unwrap();
+        if holders.len() >= self.max && holders.contains(&holder) {
+            let error = HandlerError::new(format!("event handler {} invoked re-entrantly with all {} permits in flight", self.handler, self.max));
+            error!(target: &common::format_target("EventHandlerRegistry"), "{}", error);
+            return Err(error);
+        }
+        if holders.len() >= self.max {
+            debug!(target: &common::format_target("EventHandlerRegistry"),
+                "invocation of event handler {} waiting, {} invocations in flight", self.handler, self.max);
+        }
+        let mut holders = self.released.wait_while(holders, |holders| holders.len() >= self.max).unwrap();
+        holders.push(holder);
+        Ok(Permit { handler: self, holder })
+    }
+}
+
+impl EventHandler for ConcurrencyLimitedHandler {
+    fn handle(&self, event: &dyn Event) {
+        if let Ok(_permit) = self.acquire() {
+            self.handler.handle(event);
+        }
+    }
+
+    fn id(&self) -> String {
+        self.handler.id()
+    }
+
+    fn try_handle(&self, event: &dyn Event) -> Result<(), HandlerError> {
+        let _permit = self.acquire()?;
+        self.handler.try_handle(event)
+    }
+
+    fn handle_batch(&self, events: &[Box<dyn Event>]) {
+        if let Ok(_permit) = self.acquire() {
+            self.handler.handle_batch(events);
+        }
+    }
+}
+
+impl EventHandler for SharedHandler {
+    fn handle(&self, event: &dyn Event) {
+        self.0.handle(event);
+    }
+
+    fn id(&self) -> String {
+        self.0.id()
+    }
+
+    fn try_handle(&self, event: &dyn Event) -> Result<(), HandlerError> {
+        self.0.try_handle(event)
+    }
+
+    fn handle_batch(&self, events: &[Box<dyn Event>]) {
+        self.0.handle_batch(events);
+    }
+}
+
+impl Display for SharedHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl EventHandler for PipelineHandler {
+    fn handle(&self, event: &dyn Event) {
+        let mut transformed: Option<Box<dyn Event>> = None;
+        for stage in self.stages.iter() {
+            let input = transformed.as_deref().unwrap_or(event);
+            if let Some(output) = stage.transform(input) {
+                debug!(target: &common::format_target("EventHandlerRegistry"),
+                    "event {} transformed by pipeline stage {}", common::redacted(input), stage);
+                transformed = Some(output);
+            }
+        }
+    }
+
+    fn id(&self) -> String {
+        let stage_ids: Vec<String> = self.stages.iter().map(|stage| stage.id()).collect();
+        format!("pipeline[{}]", stage_ids.join("->"))
+    }
+}
+
+impl Display for PipelineHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl Display for ConcurrencyLimitedHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.handler)
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut holders = self.handler.holders.lock().unwrap();
+        if let Some(position) = holders.iter().position(|holder| *holder == self.holder) {
+            holders.swap_remove(position);
+        }
+        drop(holders);
+        self.handler.released.notify_one();
+    }
+}
+
+impl Display for EmitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmitError::RateLimited => write!(f, "emit rate limit exceeded"),
+            EmitError::DedupStoreNotSet => write!(f, "dedup store not set"),
+        }
+    }
+}
+
+impl Error for EmitError {}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Timeout => write!(f, "no reply within the timeout"),
+            RequestError::Shutdown => write!(f, "message broker shut down"),
+            RequestError::RateLimited => write!(f, "emit rate limit exceeded"),
+            RequestError::Roundtrip(error) => write!(f, "reply not passed back: {}", error),
+            RequestError::UnexpectedReply { expected, actual } => write!(f, "reply {} is not {}", actual, expected),
+        }
+    }
+}
+
+impl Error for RequestError {}
+
+impl Display for RegistrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationError::InvalidJsonPath { jsonpath, reason } => write!(f, "invalid JSONPath expression {}: {}", jsonpath, reason),
+            RegistrationError::InvalidChannelName { name, reason } => write!(f, "invalid channel name {}: {}", name, reason),
+        }
+    }
+}
+
+impl Error for RegistrationError {}
+
+impl From<InvalidChannelName> for RegistrationError {
+    fn from(error: InvalidChannelName) -> Self {
+        RegistrationError::InvalidChannelName { name: error.name, reason: error.reason }
+    }
+}
+
+impl From<InvalidChannelName> for ConfigurationError {
+    fn from(error: InvalidChannelName) -> Self {
+        ConfigurationError::InvalidChannelName { name: error.name, reason: error.reason }
+    }
+}
+
+impl Display for EmitLoopDetected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmitLoopDetected::MaxDepthExceeded { event_id, depth } =>
+                write!(f, "emit loop detected: event {} at depth {}, exceeding max depth", event_id, depth),
+            EmitLoopDetected::NameCycle { event_id, cycle } =>
+                write!(f, "emit loop detected: event {} repeats cycle [{}]", event_id, cycle.join(",")),
+        }
+    }
+}
+
+impl Error for EmitLoopDetected {}
+
+impl Display for RoundtripError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoundtripError::Panicked(message) => write!(f, "Event::to_json panicked: {}", message),
+            RoundtripError::Malformed(message) => write!(f, "Event::to_json output can't be deserialized (is the event \
+                registered with #[typetag::serde]?): {}", message),
+        }
+    }
+}
+
+impl Error for RoundtripError {}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::Roundtrip(error) => write!(f, "event can't be scheduled: {}", error),
+            ScheduleError::TimerUnavailable(message) => write!(f, "scheduler timer can't be started: {}", message),
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+impl From<RoundtripError> for ScheduleError {
+    fn from(error: RoundtripError) -> Self {
+        ScheduleError::Roundtrip(error)
+    }
+}
+
+impl EmitReport {
+    /// Returns whether all handlers succeeded (also when there was no handler at all).
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+}
+
+impl MessageBrokerConfiguration {
+    fn validate(&self) -> Result<(), ConfigurationError> {
+        let settings = &self.async_settings;
+        match (settings.capacity, settings.concurrency, settings.ordered) {
+            (0, _, _) => Err(ConfigurationError::ZeroCapacity),
+            (_, 0, _) => Err(ConfigurationError::ZeroConcurrency),
+            (_, concurrency, true) if concurrency > 1 => Err(ConfigurationError::OrderedConcurrency(concurrency)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for ConfigurationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigurationError::ZeroCapacity => write!(f, "async capacity must be greater than zero"),
+            ConfigurationError::ZeroConcurrency => write!(f, "async concurrency must be greater than zero"),
+            ConfigurationError::OrderedConcurrency(concurrency) =>
+                write!(f, "ordered dispatch requires concurrency of 1, but {} configured", concurrency),
+            ConfigurationError::InvalidChannelName { name, reason } => write!(f, "invalid channel name {}: {}", name, reason),
+        }
+    }
+}
+
+impl Error for ConfigurationError {}
+
+impl Display for AsyncSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[capacity:{},concurrency:{},ordered:{},scheduling:{:?}]",
+               self.capacity, self.concurrency, self.ordered, self.scheduling)
+    }
+}
+
 impl Display for MessageBrokerConfiguration {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[default-channel:{},async:{}]", self.message_channel, self.is_async)
+        write!(f, "[default-channel:{},async:{},async-settings:{}]", self.message_channel, self.is_async, self.async_settings)
+    }
+}
+
+impl Drop for PreviousEventId {
+    fn drop(&mut self) {
+        CURRENT_EVENT_ID.with(|current| current.replace(self.0.take()));
+    }
+}
+
+impl Drop for PreviousCorrelationId {
+    fn drop(&mut self) {
+        CURRENT_CORRELATION_ID.with(|current| current.replace(self.0.take()));
     }
 }
@@ -0,0 +1,111 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::model::Event;
+use super::implementation;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public constants
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Reserved In-Memory channel lifecycle events are emitted to. Only handlers registered with exactly this channel
+/// name receive them (catch-all handlers don't).
+pub const LIFECYCLE_CHANNEL: &str = "eventure.lifecycle";
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Lifecycle event of the In-Memory message broker itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    pub event_id: String,
+    pub kind: LifecycleEventKind,
+    /// Handler id, configuration or broker the event is about.
+    pub subject: String,
+}
+
+/// Lifecycle event kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifecycleEventKind {
+    /// Event handler registered (subject is the handler id).
+    REGISTERED,
+    /// Event handler unregistered (subject is the handler id).
+    UNREGISTERED,
+    /// Message broker configuration changed (subject is the new configuration).
+    CONFIGURED,
+    /// Message broker shut down.
+    SHUTDOWN,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Emits lifecycle event, unless emitted from within lifecycle event handler (avoiding recursion).
+pub(super) fn emit(kind: LifecycleEventKind, subject: String) {
+    if EMITTING.with(|emitting| emitting.replace(true)) {
+        return;
+    }
+    let _emitting = Emitting;
+    let event = LifecycleEvent {
+        event_id: Uuid::new_v4().to_string(),
+        kind,
+        subject,
+    };
+    implementation::dispatch_lifecycle(&event);
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+thread_local! {
+    static EMITTING: Cell<bool> = const { Cell::new(false) };
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Resets the emitting flag when dropped, also if a lifecycle event handler panics.
+struct Emitting;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Display for LifecycleEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} lifecycle event, id {}, subject {}", self.kind, self.event_id, self.subject)
+    }
+}
+
+#[typetag::serde]
+impl Event for LifecycleEvent {
+    fn id(&self) -> &str {
+        &self.event_id[..]
+    }
+    fn name(&self) -> &str {
+        "LifecycleEvent"
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn to_json(&self) -> String {
+        let event = self as &dyn Event;
+        serde_json::to_string(&event).unwrap()
+    }
+}
+
+impl Drop for Emitting {
+    fn drop(&mut self) {
+        EMITTING.with(|emitting| emitting.set(false));
+    }
+}
@@ -0,0 +1,67 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use log::info;
+use crate::common;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Handlers matching the emit channels (by position in the registry), memoized for one state of the registry: the
+/// registry replaces it with an empty one, whenever its handlers change, while its snapshots keep sharing it.
+#[derive(Default)]
+pub(super) struct MatchCache {
+    matching: RwLock<HashMap<String, Arc<[usize]>>>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(capacity: usize) {
+    info!(target: &common::format_target("ChannelMatchCache"), "setting up: [capacity:{}]", capacity);
+    CAPACITY.store(capacity, Ordering::Relaxed);
+}
+
+pub(super) fn capacity() -> usize {
+    CAPACITY.load(Ordering::Relaxed)
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CAPACITY);
+
+/// Number of emit channels memoized per registry state, unless configured otherwise.
+pub(super) const DEFAULT_CAPACITY: usize = 1024;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl MatchCache {
+    /// Returns positions of the handlers matching the emit channel name, computing them only if not memoized yet.
+    /// Once the capacity is reached, the memo is cleared. With zero capacity, the positions are always computed.
+    pub(super) fn matching(&self, channel_name: &str, compute: impl FnOnce() -> Vec<usize>) -> Arc<[usize]> {
+        let capacity = capacity();
+        if capacity == 0 {
+            return compute().into();
+        }
+        if let Some(matching) = self.matching.read().unwrap().get(channel_name) {
+            return Arc::clone(matching);
+        }
+        let matching: Arc<[usize]> = compute().into();
+        let mut memo = self.matching.write().unwrap();
+        if memo.len() >= capacity {
+            memo.clear();
+        }
+        memo.insert(String::from(channel_name), Arc::clone(&matching));
+        matching
+    }
+}
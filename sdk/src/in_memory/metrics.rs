@@ -0,0 +1,44 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Emit metrics of an event name and channel combination.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Number of dispatched events (duplicates skipped by the dedup store not included).
+    pub emitted: u64,
+    /// Number of handler invocations.
+    pub handled: u64,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Records dispatch of the event to the channel, invoking given number of handlers.
+pub(super) fn record(event_name: &str, channel: &str, handled: usize) {
+    let mut metrics = METRICS.lock().unwrap();
+    let counts = metrics.entry((String::from(event_name), String::from(channel))).or_default();
+    counts.emitted += 1;
+    counts.handled += handled as u64;
+}
+
+/// Returns counts recorded so far, keyed by event name and channel.
+pub(super) fn by_event() -> HashMap<(String, String), Counts> {
+    METRICS.lock().unwrap().iter()
+        .map(|(key, counts)| (key.clone(), *counts))
+        .collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static METRICS: Mutex<BTreeMap<(String, String), Counts>> = Mutex::new(BTreeMap::new());
@@ -0,0 +1,101 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use log::info;
+use serde::{Deserialize, Serialize};
+use crate::common;
+use super::implementation::EmitError;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Behaviour of the emit rate limiter, once the configured rate is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RateLimitMode {
+    /// Emitting thread waits until the event can be emitted.
+    BLOCK,
+    /// Event is rejected with `EmitError::RateLimited`.
+    REJECT,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(events_per_second: u32, mode: RateLimitMode) {
+    info!(target: &common::format_target("RateLimiter"), "setting up: [rate:{}/s,mode:{:?}]", events_per_second, mode);
+    *RATE_LIMITER.lock().unwrap() = match events_per_second {
+        0 => None,
+        _ => Some(TokenBucket::new(events_per_second, mode)),
+    };
+}
+
+/// Returns configured rate (events per second) and mode, if the rate is limited.
+pub(super) fn settings() -> Option<(u32, RateLimitMode)> {
+    RATE_LIMITER.lock().unwrap().as_ref().map(|bucket| (bucket.rate as u32, bucket.mode))
+}
+
+pub(super) fn acquire() -> Result<(), EmitError> {
+    loop {
+        let wait = match RATE_LIMITER.lock().unwrap().as_mut() {
+            None => return Ok(()),
+            Some(bucket) => match bucket.take() {
+                Ok(()) => return Ok(()),
+                Err(wait) if bucket.mode == RateLimitMode::BLOCK => wait,
+                Err(_) => return Err(EmitError::RateLimited),
+            }
+        };
+        thread::sleep(wait);
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static RATE_LIMITER: Mutex<Option<TokenBucket>> = Mutex::new(None);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    mode: RateLimitMode,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl TokenBucket {
+    fn new(events_per_second: u32, mode: RateLimitMode) -> Self {
+        TokenBucket {
+            rate: events_per_second as f64,
+            tokens: events_per_second as f64,
+            refilled_at: Instant::now(),
+            mode,
+        }
+    }
+
+    /// Takes single token, or returns time to wait for the next one.
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
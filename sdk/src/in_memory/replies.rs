@@ -0,0 +1,63 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use log::info;
+use crate::common;
+use super::implementation::RequestError;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Opens request awaiting reply under the correlation id.
+pub(super) fn open(correlation_id: &str) {
+    PENDING.lock().unwrap().insert(String::from(correlation_id), None);
+}
+
+/// Completes the pending request with the reply (serialized reply event). Returns false, if no request with the
+/// correlation id is pending (e.g. timed out, or already replied).
+pub(super) fn complete(correlation_id: &str, reply: Result<String, RequestError>) -> bool {
+    let mut pending = PENDING.lock().unwrap();
+    match pending.get_mut(correlation_id) {
+        Some(slot @ None) => {
+            *slot = Some(reply);
+            REPLIED.notify_all();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Waits (at most the timeout) for the reply to the request, closing it.
+pub(super) fn wait(correlation_id: &str, timeout: Duration) -> Result<String, RequestError> {
+    let pending = PENDING.lock().unwrap();
+    let (mut pending, _) = REPLIED.wait_timeout_while(pending, timeout, |pending|
+        pending.get(correlation_id).is_some_and(Option::is_none)).unwrap();
+    pending.remove(correlation_id).flatten().unwrap_or(Err(RequestError::Timeout))
+}
+
+/// Completes all the pending requests with the error. Returns number of completed requests.
+pub(super) fn fail_all(error: RequestError) -> usize {
+    let mut pending = PENDING.lock().unwrap();
+    let mut failed = 0;
+    for slot in pending.values_mut().filter(|slot| slot.is_none()) {
+        *slot = Some(Err(error.clone()));
+        failed += 1;
+    }
+    if failed > 0 {
+        info!(target: &common::format_target("EventHandlerRegistry"), "{} pending requests failed: {}", failed, error);
+        REPLIED.notify_all();
+    }
+    failed
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static PENDING: Mutex<BTreeMap<String, Option<Result<String, RequestError>>>> = Mutex::new(BTreeMap::new());
+static REPLIED: Condvar = Condvar::new();
@@ -0,0 +1,182 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use log::{error, info};
+use crate::common;
+use crate::model::Event;
+use super::implementation::{self, ScheduleError};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Pending scheduled emit.
+#[derive(Debug, Clone)]
+pub struct ScheduleInfo {
+    /// Id assigned to the schedule at creation.
+    pub id: u64,
+    /// Id of the scheduled event.
+    pub event_id: String,
+    /// Name of the scheduled event.
+    pub event_name: String,
+    /// Instant the event is due to be emitted at.
+    pub due: Instant,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Schedules event (serialized via `Event::to_json`) to be emitted once the delay elapses, on the timer thread
+/// (started on first use). Returns id of the schedule.
+pub(super) fn schedule(event: &dyn Event, delay: Duration) -> Result<u64, ScheduleError> {
+    let payload = implementation::serialize(event)?;
+    let mut schedules = SCHEDULES.lock().unwrap();
+    if schedules.timer.is_none() {
+        let generation = schedules.generation;
+        let timer = thread::Builder::new()
+            .name(String::from("eventure-scheduler"))
+            .spawn(move || run_timer(generation))
+            .map_err(|e| ScheduleError::TimerUnavailable(e.to_string()))?;
+        schedules.timer = Some(timer);
+    }
+    schedules.next_id += 1;
+    let info = ScheduleInfo {
+        id: schedules.next_id,
+        event_id: String::from(event.id()),
+        event_name: String::from(event.name()),
+        due: Instant::now() + delay,
+    };
+    let id = info.id;
+    info!(target: &common::format_target("Scheduler"), "event {} scheduled in {:?} with id {}", common::redacted(event), delay, id);
+    schedules.due.push(Reverse((info.due, id)));
+    schedules.pending.insert(id, Schedule { info, payload });
+    drop(schedules);
+    // the new schedule may be due before the one the timer waits for
+    TIMER.notify_one();
+    Ok(id)
+}
+
+/// Lists pending schedules, the earliest due first.
+pub(super) fn scheduled() -> Vec<ScheduleInfo> {
+    let mut scheduled: Vec<ScheduleInfo> = SCHEDULES.lock().unwrap().pending.values()
+        .map(|schedule| schedule.info.clone())
+        .collect();
+    scheduled.sort_by_key(|info| info.due);
+    scheduled
+}
+
+/// Cancels pending schedule. Returns false, if the schedule doesn't exist (or already fired).
+pub(super) fn cancel(id: u64) -> bool {
+    // the timer skips the due entry of the cancelled schedule, once it gets to it
+    let cancelled = SCHEDULES.lock().unwrap().pending.remove(&id).is_some();
+    if cancelled {
+        info!(target: &common::format_target("Scheduler"), "schedule {} cancelled", id);
+    }
+    cancelled
+}
+
+/// Stops the timer thread (if running), dropping pending schedules. The timer is started again on next schedule.
+pub(super) fn stop() {
+    let mut schedules = SCHEDULES.lock().unwrap();
+    let Some(timer) = schedules.timer.take() else { return };
+    let dropped = schedules.pending.len();
+    schedules.pending.clear();
+    schedules.due.clear();
+    schedules.generation += 1;
+    drop(schedules);
+    TIMER.notify_all();
+    // stopped from a handler of scheduled event, the timer finishes once the handler returns
+    if timer.thread().id() != thread::current().id() && timer.join().is_err() {
+        error!(target: &common::format_target("Scheduler"), "timer thread failed");
+    }
+    info!(target: &common::format_target("Scheduler"), "stopped, {} pending schedule(s) dropped", dropped);
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static SCHEDULES: Mutex<Schedules> = Mutex::new(Schedules::new());
+static TIMER: Condvar = Condvar::new();
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+struct Schedules {
+    next_id: u64,
+    /// Pending schedules by id.
+    pending: BTreeMap<u64, Schedule>,
+    /// Due instants of the schedules, the earliest first (cancelled ones included, until due).
+    due: BinaryHeap<Reverse<(Instant, u64)>>,
+    timer: Option<JoinHandle<()>>,
+    /// Incremented when the timer is stopped, so that it finishes (also if a newer one was started meanwhile).
+    generation: u64,
+}
+
+struct Schedule {
+    info: ScheduleInfo,
+    payload: String,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Waits for the earliest due schedule and emits its event, until stopped.
+fn run_timer(generation: u64) {
+    let mut schedules = SCHEDULES.lock().unwrap();
+    while schedules.generation == generation {
+        let now = Instant::now();
+        match schedules.due.peek().copied() {
+            Some(Reverse((due, id))) if due <= now => {
+                schedules.due.pop();
+                if let Some(schedule) = schedules.pending.remove(&id) {
+                    drop(schedules);
+                    fire(schedule);
+                    schedules = SCHEDULES.lock().unwrap();
+                }
+            }
+            Some(Reverse((due, _))) => schedules = TIMER.wait_timeout(schedules, due - now).unwrap().0,
+            None => schedules = TIMER.wait(schedules).unwrap(),
+        }
+    }
+}
+
+/// Emits the event of due schedule. Panic of the emit is logged, so that it doesn't stop the timer.
+fn fire(schedule: Schedule) {
+    match serde_json::from_str::<Box<dyn Event>>(&schedule.payload) {
+        Ok(event) => {
+            if panic::catch_unwind(AssertUnwindSafe(|| implementation::emit(&*event))).is_err() {
+                error!(target: &common::format_target("Scheduler"), "scheduled event {} emit failed", common::redacted(&*event));
+            }
+        }
+        Err(e) => error!(target: &common::format_target("Scheduler"),
+            "unable to deserialize scheduled event {}: {}",
+            common::redacted_payload(Some(&schedule.info.event_id), schedule.payload.as_bytes()), e),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Schedules {
+    const fn new() -> Self {
+        Schedules {
+            next_id: 0,
+            pending: BTreeMap::new(),
+            due: BinaryHeap::new(),
+            timer: None,
+            generation: 0,
+        }
+    }
+}
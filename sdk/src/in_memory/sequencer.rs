@@ -0,0 +1,80 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use log::info;
+use crate::common;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn configure(enabled: bool) {
+    info!(target: &common::format_target("Sequencer"), "total order {}", if enabled { "enabled" } else { "disabled" });
+    TOTAL_ORDER.store(enabled, Ordering::SeqCst);
+}
+
+pub(super) fn is_enabled() -> bool {
+    TOTAL_ORDER.load(Ordering::SeqCst)
+}
+
+/// Runs the dispatch in total order (if enabled): dispatches are serialized and each one gets the next global
+/// sequence number. Dispatches nested in handlers run within the sequence of the outer one (no deadlock).
+pub(super) fn sequenced<T>(dispatch: impl FnOnce() -> T) -> T {
+    if !TOTAL_ORDER.load(Ordering::SeqCst) {
+        return dispatch();
+    }
+    let lock = match HOLDING.with(|holding| holding.replace(true)) {
+        true => None,
+        false => Some(SEQUENCER.lock().unwrap_or_else(PoisonError::into_inner)),
+    };
+    let sequence = SEQUENCE.fetch_add(1, Ordering::SeqCst) + 1;
+    let _sequenced = Sequenced {
+        lock,
+        previous_sequence: CURRENT_SEQUENCE.with(|current| current.replace(Some(sequence))),
+    };
+    dispatch()
+}
+
+pub(super) fn current() -> Option<u64> {
+    CURRENT_SEQUENCE.with(Cell::get)
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+static TOTAL_ORDER: AtomicBool = AtomicBool::new(false);
+static SEQUENCER: Mutex<()> = Mutex::new(());
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static HOLDING: Cell<bool> = const { Cell::new(false) };
+    static CURRENT_SEQUENCE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Restores the sequencing state of the thread (also when the dispatch panics).
+struct Sequenced {
+    lock: Option<MutexGuard<'static, ()>>,
+    previous_sequence: Option<u64>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Drop for Sequenced {
+    fn drop(&mut self) {
+        CURRENT_SEQUENCE.with(|current| current.set(self.previous_sequence));
+        if self.lock.is_some() {
+            HOLDING.with(|holding| holding.set(false));
+        }
+    }
+}
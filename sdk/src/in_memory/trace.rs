@@ -0,0 +1,94 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Trace context of the event being dispatched. Each dispatch opens a span; events emitted while handling an event
+/// get a span child of the handled event's one (within the same trace), so nested emits form a trace tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// Id of the trace (32 hex chars), shared by all the spans of the tree.
+    pub trace_id: String,
+    /// Id of the span (16 hex chars) of the dispatch.
+    pub span_id: String,
+    /// Id of the parent span, if the event was emitted while handling another event.
+    pub parent_span_id: Option<String>,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+pub(super) fn current() -> Option<TraceContext> {
+    CURRENT_TRACE.with(|current| current.borrow().clone())
+}
+
+/// Runs the dispatch within a new span, child of the current one (if any).
+pub(super) fn in_span<T>(dispatch: impl FnOnce() -> T) -> T {
+    let span = match current() {
+        Some(parent) => TraceContext {
+            trace_id: parent.trace_id,
+            span_id: new_span_id(),
+            parent_span_id: Some(parent.span_id),
+        },
+        None => TraceContext {
+            trace_id: Uuid::new_v4().simple().to_string(),
+            span_id: new_span_id(),
+            parent_span_id: None,
+        },
+    };
+    with_current(Some(span), dispatch)
+}
+
+/// Runs the function with given trace context set as the current one (e.g. context captured when the event was
+/// queued, restored by the thread dispatching it).
+pub(super) fn with_current<T>(trace: Option<TraceContext>, function: impl FnOnce() -> T) -> T {
+    let _previous = PreviousTrace(CURRENT_TRACE.with(|current| current.replace(trace)));
+    function()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private statics
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+thread_local! {
+    static CURRENT_TRACE: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Trace context current before `with_current`, restored when dropped (also if the function panics).
+struct PreviousTrace(Option<TraceContext>);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+fn new_span_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Display for TraceContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[trace:{},span:{},parent:{}]", self.trace_id, self.span_id, self.parent_span_id.as_deref().unwrap_or("-"))
+    }
+}
+
+impl Drop for PreviousTrace {
+    fn drop(&mut self) {
+        CURRENT_TRACE.with(|current| current.replace(self.0.take()));
+    }
+}
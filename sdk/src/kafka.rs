@@ -5,13 +5,49 @@
 //! Apache Kafka integration.
 
 mod implementation;
+mod schema;
 
 pub use self::implementation::MessageChannel;
 pub use self::implementation::MessageBrokerConfiguration;
+pub use self::implementation::ConsumerHandle;
+pub use self::implementation::OffsetCommit;
+pub use self::implementation::ReplayPosition;
+pub use self::implementation::DeserializationError;
+pub use self::implementation::Polled;
+pub use self::implementation::PollError;
+pub use self::implementation::SerializationFormat;
+pub use self::implementation::SequenceViolation;
+pub use self::implementation::SequenceViolationKind;
 pub use self::implementation::setup;
 pub use self::implementation::register;
+pub use self::implementation::register_with_manual_commit;
+pub use self::implementation::register_batch;
+pub use self::implementation::consume_n;
+pub use self::implementation::consumer;
+pub use self::implementation::poll;
+pub use self::implementation::shutdown;
+pub(crate) use self::implementation::shutdown_flushing;
 pub use self::implementation::unregister;
 pub use self::implementation::emit;
 pub use self::implementation::emit_to_channel;
+pub use self::implementation::emit_batch;
+pub use self::implementation::set_partitioner;
 pub use self::implementation::configuration;
+pub use self::implementation::configuration_from_properties;
+pub use self::implementation::set_application_name;
+pub use self::implementation::default_group_id;
 pub use self::implementation::message_channel;
+pub use self::implementation::reset_offsets;
+pub use self::implementation::register_type;
+pub use self::implementation::register_type_with;
+pub use self::implementation::deserialize_event;
+pub use self::implementation::schema_fingerprint;
+pub use self::implementation::expect_schema;
+pub use self::implementation::set_serialization_format;
+pub use self::implementation::set_deserialization_fallback;
+pub use self::implementation::set_sequence_check;
+pub use self::implementation::set_chunking;
+pub use self::implementation::set_on_idle;
+pub use self::implementation::set_dedup_store;
+pub use self::implementation::current_headers;
+pub use rdkafka::error::KafkaError;
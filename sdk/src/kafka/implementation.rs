@@ -2,20 +2,39 @@
 // Rust-Lang Libs/Eventure 2024
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
-use std::{process, thread};
+use std::{fs, io, panic, thread};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::future::Future;
-use std::sync::Mutex;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::ptr;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::mpsc::{RecvTimeoutError, TrySendError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use futures::future::{self, FutureExt};
-use futures::StreamExt;
-use log::info;
+use futures::future::{self, Either, FutureExt};
+use futures::{Stream, StreamExt};
+use log::{debug, error, info, warn};
 use rdkafka::{ClientConfig, Message};
-use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, DefaultConsumerContext, StreamConsumer};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, Headers, OwnedHeaders, OwnedMessage};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
+use rdkafka::{Offset, TopicPartitionList};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use uuid::Uuid;
 use rdkafka::util::AsyncRuntime;
-use crate::common;
-use crate::model::{Event, EventHandler};
+use crate::common::{self, DedupStore};
+use crate::model::{Event, EventHandler, HandlerError};
+use super::schema;
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Public structs
@@ -30,13 +49,13 @@ use crate::model::{Event, EventHandler};
 /// let message_channel = kafka::MessageChannel {
 ///     topic: "Orders",
 ///     partition: 0,
-///     group_id: "default"
+///     group_id: String::from("default")
 /// };
 /// ```
 pub struct MessageChannel {
     pub topic: &'static str,
     pub partition: u16,
-    pub group_id: &'static str
+    pub group_id: String
 }
 
 /// Kafka message broker configuration.
@@ -48,24 +67,195 @@ pub struct MessageChannel {
 /// let message_channel = kafka::MessageChannel {
 ///     topic: "Orders",
 ///     partition: 0,
-///     group_id: "consumer_group"
+///     group_id: String::from("consumer_group")
 /// };
 ///
 /// let configuration = kafka::MessageBrokerConfiguration {
 ///     message_channel,
-///     bootstrap_servers: "localhost:9092",
+///     bootstrap_servers: String::from("localhost:9092"),
 ///     topic_auto_create_enabled: false,
 ///     auto_commit_enabled: false,
 ///     timeout: 10000,
+///     quarantine_topic: Some(String::from("orders-quarantine")),
+///     extra_properties: vec![(String::from("client.id"), String::from("orders-service"))],
+///     processing_deadline: None,
+///     pause_on_deadline: false,
 /// };
 ///
 /// ```
+///
+/// Messages which can't be deserialized (or handled) are republished to the quarantine topic, carrying their
+/// origin in the headers (requires running broker):
+/// ```no_run
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::message::{Headers, Message};
+/// use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+/// use eventure::{kafka, model};
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.quarantine_topic = Some(String::from("orders-quarantine"));
+/// kafka::setup(configuration);
+///
+/// let producer: BaseProducer = ClientConfig::new()
+///     .set("bootstrap.servers", "localhost:9092")
+///     .create().unwrap();
+/// producer.send(BaseRecord::<(), str>::to("orders").payload("{not an event")).unwrap();
+/// producer.flush(Duration::from_secs(10)).unwrap();
+///
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler).unwrap();
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", "localhost:9092")
+///     .set("group.id", "quarantine_inspector")
+///     .set("auto.offset.reset", "earliest")
+///     .create().unwrap();
+/// consumer.subscribe(&["orders-quarantine"]).unwrap();
+///
+/// let message = (0..30)
+///     .find_map(|_| consumer.poll(Duration::from_secs(1)).and_then(Result::ok).map(|m| m.detach()))
+///     .expect("quarantined message expected");
+/// let headers: Vec<(String, Vec<u8>)> = message.headers().unwrap().iter()
+///     .map(|header| (header.key.to_string(), header.value.unwrap_or_default().to_vec()))
+///     .collect();
+///
+/// assert_eq!(message.payload(), Some("{not an event".as_bytes()));
+/// assert!(headers.contains(&("original-topic".to_string(), b"orders".to_vec())));
+/// assert!(headers.contains(&("original-partition".to_string(), b"0".to_vec())));
+/// assert!(headers.iter().any(|(key, _)| key == "original-offset"));
+/// ```
 pub struct MessageBrokerConfiguration {
     pub message_channel: MessageChannel,
-    pub bootstrap_servers: &'static str,
+    pub bootstrap_servers: String,
     pub topic_auto_create_enabled: bool,
     pub auto_commit_enabled: bool,
     pub timeout: u32,
+    /// Topic the consumer republishes raw messages to, when they can't be deserialized or handled.
+    pub quarantine_topic: Option<String>,
+    /// Additional client properties (librdkafka names), passed as they are to consumers and producers, overriding
+    /// the ones derived from the other fields.
+    pub extra_properties: Vec<(String, String)>,
+    /// Time a handler registered with `register` may spend on a message. The handler then runs on a worker thread,
+    /// and the consumer logs overruns, while it keeps polling (so that it stays in the consumer group). Messages
+    /// received meanwhile are buffered (up to 16, then the partitions are paused), and offsets are committed only
+    /// once their messages are handled.
+    pub processing_deadline: Option<Duration>,
+    /// Whether the partition is paused while its handler overruns the processing deadline (resumed once it returns).
+    pub pause_on_deadline: bool,
+}
+
+/// Position consumer group offsets are reset to, for events to be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayPosition {
+    /// The earliest offset still retained in the partition.
+    BEGINNING,
+    /// The offset after the latest message in the partition (nothing gets replayed).
+    END,
+    /// The earliest offset whose timestamp (milliseconds since epoch) is greater or equal to the given one.
+    TIMESTAMP(i64),
+}
+
+/// Handle committing consumer offsets up to (and including) the message being handled, passed to the handlers
+/// registered with `register_with_manual_commit`. Chunks of the messages still being reassembled (see `set_chunking`)
+/// aren't committed.
+pub struct OffsetCommit<'a> {
+    consumer: &'a StreamConsumer<DefaultConsumerContext, SmolRuntime>,
+    topic: String,
+    partition: i32,
+    offset: i64,
+}
+
+/// Kafka consumer polled manually (see `poll`), on the caller's thread, e.g. from the application's own event loop.
+pub struct ConsumerHandle {
+    consumer: BaseConsumer<DefaultConsumerContext>,
+    message_channel: MessageChannel,
+    quarantine: Option<QuarantineProducer>,
+    chunks: Mutex<ChunkAssembler>,
+}
+
+/// Kafka message deserialization error.
+#[derive(Debug)]
+pub enum DeserializationError {
+    /// Payload isn't a valid event JSON (or doesn't match the event type).
+    Malformed(serde_json::Error),
+    /// Event type tag isn't registered in the consumer type map.
+    UnmappedType(String),
+    /// Schema fingerprint stamped by the producer doesn't match the one the consumer expects for the event type.
+    SchemaMismatch { type_tag: String, expected: String, actual: String },
+    /// Payload isn't valid MessagePack (on the topic configured with `SerializationFormat::MESSAGEPACK`).
+    MalformedMessagePack(rmp_serde::decode::Error),
+}
+
+/// Violation of the event sequence detected by the consumer (see `set_sequence_check`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceViolation {
+    pub topic: String,
+    pub partition: i32,
+    /// Partition key of the event (see `Event::partition_key`), empty if not set.
+    pub partition_key: String,
+    pub kind: SequenceViolationKind,
+    /// Sequence number expected at least (the last consumed one, for reordering) or exactly (the next one, for gaps).
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Kind of the event sequence violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceViolationKind {
+    /// Event arrived after an event with greater sequence number.
+    REORDERED,
+    /// Sequence numbers between the last consumed event and this one are missing.
+    GAP,
+}
+
+/// Format of the Kafka message payloads, configured per topic (see `set_serialization_format`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializationFormat {
+    /// Event JSON, as produced by `Event::to_json` (default).
+    JSON,
+    /// MessagePack encoding of the event JSON (field names included, so that events can be decoded as `dyn Event`).
+    MESSAGEPACK,
+}
+
+/// Outcome of a single `poll` of the consumer.
+pub enum Polled {
+    /// Event to be handled by the caller.
+    Event(Box<dyn Event>),
+    /// No message arrived within the timeout.
+    Timeout,
+    /// Message is a duplicate of an event already handled (see `set_dedup_store`), skipped.
+    Duplicate,
+    /// Message is a chunk of an event being reassembled (see `set_chunking`), the event is returned once complete.
+    Chunk,
+}
+
+/// Error of a single `poll` of the consumer.
+#[derive(Debug)]
+pub enum PollError {
+    /// Consumer failed to receive the message.
+    Kafka(KafkaError),
+    /// Message can't be deserialized (and is quarantined, if quarantine topic is configured).
+    Deserialization(DeserializationError),
 }
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
@@ -80,15 +270,16 @@ pub struct MessageBrokerConfiguration {
 ///
 /// let handler_channel = kafka::message_channel("Orders", 0, "consumer_group");
 /// ```
-pub fn message_channel(topic: &'static str, partition: u16, group_id: &'static str) -> MessageChannel {
+pub fn message_channel(topic: &'static str, partition: u16, group_id: impl Into<String>) -> MessageChannel {
     MessageChannel {
         topic,
         partition,
-        group_id
+        group_id: group_id.into()
     }
 }
 
-/// Creates Kafka message broker configuration.
+/// Creates Kafka message broker configuration. Group id of the message channel is the application name (see
+/// `set_application_name`), or "default" if not set.
 ///
 /// # Examples
 ///
@@ -99,11 +290,171 @@ pub fn message_channel(topic: &'static str, partition: u16, group_id: &'static s
 /// ```
 pub fn configuration(topic: &'static str, partition: u16) -> MessageBrokerConfiguration {
     MessageBrokerConfiguration {
-        message_channel: message_channel(topic, partition, "default"),
-        bootstrap_servers: "localhost:9092",
+        message_channel: message_channel(topic, partition, APPLICATION_NAME.lock().unwrap().unwrap_or(DEFAULT_GROUP_ID)),
+        bootstrap_servers: String::from("localhost:9092"),
         topic_auto_create_enabled: false,
         auto_commit_enabled: true,
         timeout: 10000,
+        quarantine_topic: None,
+        extra_properties: Vec::new(),
+        processing_deadline: None,
+        pause_on_deadline: false,
+    }
+}
+
+/// Creates Kafka message broker configuration from standard Kafka (JVM client) `.properties` file, e.g. existing
+/// `consumer.properties` or `producer.properties`. Recognized keys (`bootstrap.servers`, `group.id`,
+/// `enable.auto.commit`, `allow.auto.create.topics`, and eventure's own `quarantine.topic`) are mapped into the
+/// configuration, JVM-only ones (serializer/deserializer classes) are skipped, the rest (e.g. `session.timeout.ms`
+/// or `message.timeout.ms`) becomes extra properties, passed to the clients as they are. Topic defaults to
+/// "default", partition to 0.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use eventure::kafka;
+///
+/// let path = std::env::temp_dir().join("eventure-consumer.properties");
+/// fs::write(&path, "\
+/// ## consumer settings
+/// bootstrap.servers=broker-1:9092,broker-2:9092
+/// group.id = orders-service
+/// enable.auto.commit=false
+/// quarantine.topic=orders-quarantine
+/// session.timeout.ms=45000
+/// key.deserializer=org.apache.kafka.common.serialization.StringDeserializer
+/// fetch.min.bytes: 1024
+/// ").unwrap();
+///
+/// let configuration = kafka::configuration_from_properties(&path).unwrap();
+///
+/// assert_eq!(configuration.bootstrap_servers, "broker-1:9092,broker-2:9092");
+/// assert_eq!(configuration.message_channel.group_id, "orders-service");
+/// assert!(!configuration.auto_commit_enabled);
+/// assert_eq!(configuration.quarantine_topic.as_deref(), Some("orders-quarantine"));
+/// assert_eq!(configuration.timeout, 10000);
+/// assert_eq!(configuration.extra_properties, vec![
+///     (String::from("session.timeout.ms"), String::from("45000")),
+///     (String::from("fetch.min.bytes"), String::from("1024")),
+/// ]);
+/// ```
+pub fn configuration_from_properties(path: impl AsRef<Path>) -> io::Result<MessageBrokerConfiguration> {
+    let mut configuration = configuration("default", 0);
+    for (key, value) in parse_properties(&fs::read_to_string(path)?) {
+        match key.as_str() {
+            "bootstrap.servers" => configuration.bootstrap_servers = value,
+            "group.id" => configuration.message_channel.group_id = value,
+            "enable.auto.commit" => configuration.auto_commit_enabled = parse_property(&key, &value)?,
+            "allow.auto.create.topics" => configuration.topic_auto_create_enabled = parse_property(&key, &value)?,
+            "quarantine.topic" => configuration.quarantine_topic = Some(value),
+            "key.deserializer" | "value.deserializer" | "key.serializer" | "value.serializer" =>
+                info!(target: &common::format_target("MessageBrokerConfiguration"), "JVM-only property {} skipped", key),
+            _ => configuration.extra_properties.push((key, value)),
+        }
+    }
+    Ok(configuration)
+}
+
+/// Sets name of the application, used as the default consumer group id (see `default_group_id`), so independent
+/// services don't end up sharing the "default" consumer group (stealing each other's messages). Instances of the
+/// same application share the group, consuming the partitions together.
+///
+/// # Examples
+/// ```
+/// use eventure::kafka;
+///
+/// kafka::set_application_name("billing-service");
+/// let billing_configuration = kafka::configuration("orders", 0);
+/// kafka::set_application_name("shipping-service");
+/// let shipping_configuration = kafka::configuration("orders", 0);
+///
+/// assert_eq!(billing_configuration.message_channel.group_id, "billing-service");
+/// assert_eq!(shipping_configuration.message_channel.group_id, "shipping-service");
+/// ```
+pub fn set_application_name(application_name: &'static str) {
+    info!(target: &common::format_target("MessageBrokerConfiguration"), "application name set: {}", application_name);
+    *APPLICATION_NAME.lock().unwrap() = Some(application_name);
+}
+
+/// Returns default consumer group id, to register the handlers with: group id of the message channel set up (see
+/// `setup`), which is the application name by default (see `set_application_name`), or "default" if neither is set.
+/// Consumers in the "default" group log a warning, since independent services consuming the same topic would share
+/// the group.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use log::{Level, LevelFilter, Log, Metadata, Record};
+/// use rdkafka::mocking::MockCluster;
+/// use eventure::{kafka, model};
+///
+/// static LOGGED: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+///
+/// struct CapturingLogger;
+///
+/// impl Log for CapturingLogger {
+///     fn enabled(&self, _metadata: &Metadata) -> bool {
+///         true
+///     }
+///     fn log(&self, record: &Record) {
+///         LOGGED.lock().unwrap().push((record.level(), record.args().to_string()));
+///     }
+///     fn flush(&self) {}
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}", event);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// log::set_logger(&CapturingLogger).unwrap();
+/// log::set_max_level(LevelFilter::Info);
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// assert_eq!(kafka::default_group_id(), "default");
+/// kafka::register(kafka::message_channel("orders", 0, kafka::default_group_id()), OrderEventHandler).unwrap();
+///
+/// assert!(LOGGED.lock().unwrap().iter()
+///     .any(|(level, message)| *level == Level::Warn && message.contains("consumer group \"default\"")));
+/// kafka::shutdown();
+///
+/// // configuration created once the application name is set
+/// kafka::set_application_name("billing-service");
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// assert_eq!(kafka::default_group_id(), "billing-service");
+/// kafka::register(kafka::message_channel("orders", 0, kafka::default_group_id()), OrderEventHandler).unwrap();
+///
+/// assert!(LOGGED.lock().unwrap().iter()
+///     .any(|(_, message)| message.contains("consumer of [\"orders\",0] in group billing-service")));
+/// kafka::shutdown();
+/// ```
+pub fn default_group_id() -> String {
+    match &*BROKER_CONFIGURATION.lock().unwrap().message_channel.group_id {
+        DEFAULT_GROUP_ID => String::from(APPLICATION_NAME.lock().unwrap().unwrap_or(DEFAULT_GROUP_ID)),
+        group_id => String::from(group_id),
     }
 }
 
@@ -119,9 +470,13 @@ pub fn configuration(topic: &'static str, partition: u16) -> MessageBrokerConfig
 pub fn setup(configuration: MessageBrokerConfiguration) {
     info!(target: &common::format_target("MessageBrokerConfiguration"), "setting up: {}",configuration);
     BROKER_CONFIGURATION.lock().unwrap().update(MessageBrokerConfigurationInternal::from(configuration));
+    // created again on next emit, for the new configuration
+    *PRODUCER.lock().unwrap() = None;
+    PARTITION_COUNTS.lock().unwrap().clear();
 }
 
-/// Registers Kafka event handler.
+/// Registers Kafka event handler, consuming events on a dedicated thread. Returns an error, if the consumer can't be
+/// created or subscribed to the topic. The idle callback (see `set_on_idle`) is invoked whenever the topic goes quiet.
 ///
 /// # Examples
 /// ```
@@ -193,76 +548,83 @@ pub fn setup(configuration: MessageBrokerConfiguration) {
 ///     }
 /// }
 ///
+/// kafka::setup(kafka::configuration("orders", 0));
+///
 /// let order_created_handler = OrderCreatedEventHandler;
-/// kafka::register(handler_channel, order_created_handler);
+/// kafka::register(handler_channel, order_created_handler).unwrap();
 /// ```
-pub fn register(message_channel: MessageChannel, event_handler: impl EventHandler + Send + 'static) {
-    thread::spawn(move || {
-        let configuration = BROKER_CONFIGURATION.lock().unwrap();
-        smol::block_on(async {
-            let topic = message_channel.topic;
-            let consumer: StreamConsumer<_, SmolRuntime> = ClientConfig::new()
-                .set("bootstrap.servers", configuration.bootstrap_servers)
-                .set("session.timeout.ms", configuration.timeout.to_string())
-                .set("enable.auto.commit", configuration.auto_commit_enabled.to_string())
-                .set("group.id", message_channel.group_id)
-                .set("auto.offset.reset", "earliest")
-                .create().expect("Consumer creation failed");
-            consumer.subscribe(&[&topic]).unwrap();
-
-            drop(configuration);
-
-            loop {
-                let mut stream = consumer.stream();
-                let message = stream.next().await;
-                match message {
-                    Some(Ok(message)) => {
-                        let message_str = match message.payload_view::<str>() {
-                            None => "",
-                            Some(Ok(s)) => s,
-                            Some(Err(_)) => "<invalid utf-8>",
-                        };
-
-                        let event: Box<dyn Event> = serde_json::from_str(message_str).unwrap();
-                        event_handler.handle(&*event);
-                    }
-                    Some(Err(e)) => {
-                        eprintln!("Error receiving message: {}", e);
-                        process::exit(1);
-                    }
-                    None => {
-                        eprintln!("Consumer unexpectedly returned no messages");
-                        process::exit(1);
-                    }
-                }
-            }
-        });
-    });
-}
-
-/// Unregisters Kafka event handler.
 ///
-/// # Examples
+/// Consumer creation failures (e.g. misconfiguration) are reported to the caller, before any thread is spawned:
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use rdkafka::error::KafkaError;
+/// use eventure::{kafka, model};
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.extra_properties = vec![(String::from("no.such.property"), String::from("value"))];
+/// kafka::setup(configuration);
+///
+/// let registered = kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler);
+/// assert!(matches!(registered, Err(KafkaError::ClientConfig(..))));
+/// ```
+///
+/// With processing deadline configured, a slow handler doesn't hold the consumer: the overrun is logged (and the
+/// partition paused, if configured), and the next message is handled once the slow handler returns. Stopping the
+/// consumer, messages already received are handled first, and only handled messages get committed:
 /// ```
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use std::time::Duration;
+/// use log::{LevelFilter, Log, Metadata, Record};
+/// use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::mocking::MockCluster;
 /// use serde::{Deserialize, Serialize};
 /// use eventure::{kafka, model};
 ///
-/// let handler_channel = kafka::message_channel("orders", 0, "consumer_group");
+/// static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
 ///
-/// struct OrderCreatedEventHandler;
+/// struct CapturingLogger;
+///
+/// impl Log for CapturingLogger {
+///     fn enabled(&self, _metadata: &Metadata) -> bool {
+///         true
+///     }
+///     fn log(&self, record: &Record) {
+///         LOGGED.lock().unwrap().push(record.args().to_string());
+///     }
+///     fn flush(&self) {}
+/// }
 ///
 /// #[derive(Serialize, Deserialize)]
 /// struct OrderCreated {
 ///     event_id: String,
-///     customer_id: String,
 /// }
 ///
 /// impl Display for OrderCreated {
 ///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-///         write!(f, "{} event with id {}",
-///                "OrderCreated", self.event_id)
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
 ///     }
 /// }
 ///
@@ -278,69 +640,137 @@ pub fn register(message_channel: MessageChannel, event_handler: impl EventHandle
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         todo!()
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
-///     event_id: String::from("event_id"),
-///     customer_id: String::from("customer_id"),
-/// };
+/// struct SlowOrderEventHandler;
 ///
-/// impl Display for OrderCreatedEventHandler {
+/// impl Display for SlowOrderEventHandler {
 ///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-///         write!(f, "{}", "OrderEventHandler")
+///         write!(f, "{}", "SlowOrderEventHandler")
 ///     }
 /// }
 ///
-/// impl model::EventHandler for OrderCreatedEventHandler {
+/// impl model::EventHandler for SlowOrderEventHandler {
 ///     fn handle(&self, event: &(dyn model::Event + '_)) {
-///         match event.as_any().downcast_ref::<OrderCreated>() {
-///             Some(order_create) => self.handle(order_create),
-///             None => println!("{}: not handling {}", "OrderCreatedEventHandler", event)
+///         if event.id() == "1" {
+///             thread::sleep(Duration::from_secs(1));
 ///         }
+///         HANDLED.lock().unwrap().push(event.id().to_string());
 ///     }
 ///
 ///     fn id(&self) -> String {
-///         String::from("OrderCreatedEventHandler")
+///         String::from("SlowOrderEventHandler")
 ///     }
 /// }
 ///
-/// impl OrderCreatedEventHandler {
-///     fn handle(&self, event: &OrderCreated) {
-///         println!("{}: handling {}","OrderCreatedEventHandler", event)
-///     }
+/// log::set_logger(&CapturingLogger).unwrap();
+/// log::set_max_level(LevelFilter::Info);
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// configuration.processing_deadline = Some(Duration::from_millis(200));
+/// configuration.pause_on_deadline = true;
+/// kafka::setup(configuration);
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), SlowOrderEventHandler).unwrap();
+///
+/// kafka::emit(&OrderCreated { event_id: String::from("1") });
+/// kafka::emit(&OrderCreated { event_id: String::from("2") });
+/// let overrun = || LOGGED.lock().unwrap().iter()
+///     .filter(|message| message.contains("exceeded processing deadline of 200ms"))
+///     .count();
+/// while overrun() == 0 {
+///     thread::sleep(Duration::from_millis(10));
 /// }
+/// kafka::shutdown();
 ///
-/// let order_created_handler = OrderCreatedEventHandler;
-/// kafka::register(handler_channel, order_created_handler);
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2"]);
+/// assert_eq!(overrun(), 1);
 ///
-/// let order_created_handler = OrderCreatedEventHandler;
-/// kafka::unregister(order_created_handler);
+/// let inspector: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .set("group.id", "consumer_group")
+///     .create().unwrap();
+/// let mut partitions = TopicPartitionList::new();
+/// partitions.add_partition("orders", 0);
+/// let committed = inspector.committed_offsets(partitions, Duration::from_secs(5)).unwrap();
+/// assert_eq!(committed.find_partition("orders", 0).unwrap().offset(), Offset::Offset(2));
 /// ```
-pub fn unregister(_event_handler: impl EventHandler + Send + 'static) {
-    // TODO: implement
+pub fn register(message_channel: MessageChannel, event_handler: impl EventHandler + Send + 'static) -> Result<(), KafkaError> {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let topic = message_channel.topic;
+    // offsets are stored (to be committed) only once the messages are handled and the chunks reassembled
+    let consumer: StreamConsumer<DefaultConsumerContext, SmolRuntime> =
+        consumer_config(&configuration, &message_channel.group_id, configuration.auto_commit_enabled)
+            .set("enable.auto.offset.store", "false")
+            .create()?;
+    consumer.subscribe(&[topic])?;
+    let quarantine = configuration.quarantine_topic.as_deref()
+        .map(|quarantine_topic| QuarantineProducer::new(&configuration, quarantine_topic))
+        .transpose()?;
+    let processing_deadline = configuration.processing_deadline;
+    let pause_on_deadline = configuration.pause_on_deadline;
+    drop(configuration);
+    let mut idle = IDLE_CALLBACK.lock().unwrap().clone().map(|callback| IdleWatch::new(&message_channel, callback));
+
+    if let Some(deadline) = processing_deadline {
+        spawn_consumer(&message_channel, move |stop| {
+            consume_with_deadline(consumer, event_handler, quarantine, deadline, pause_on_deadline, idle, stop)
+        });
+        return Ok(());
+    }
+    spawn_consumer(&message_channel, move |stop| {
+        smol::block_on(async {
+            let mut stream = consumer.stream();
+            let mut chunks = ChunkAssembler::default();
+            while let Some(message) = next_message(&mut stream, &stop, &mut idle, &mut chunks).await {
+                match message {
+                    Ok(message) => {
+                        handle_message(&message, &event_handler, &quarantine, &mut chunks).await;
+                        store_offset(&consumer, &message, &chunks);
+                    }
+                    Err(e) => error!(target: &common::format_target("KafkaConsumer"), "error receiving message: {}", e),
+                }
+            }
+        });
+    });
+    Ok(())
 }
 
-/// Emits Kafka event without specifying message channel.
+/// Registers Kafka event handler committing consumer offsets manually, consuming events on a dedicated thread.
+/// Offsets aren't committed automatically: the handler receives an `OffsetCommit` handle along with each event and
+/// decides when to commit (e.g. after a batch of external side effects). Committing the offset of a message commits
+/// all the preceding ones as well. Returns an error, if the consumer can't be created or subscribed to the topic.
 ///
 /// # Examples
 /// ```
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::mocking::MockCluster;
 /// use serde::{Deserialize, Serialize};
 /// use eventure::{kafka, model};
 ///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+/// static COMMITTED: AtomicUsize = AtomicUsize::new(0);
+///
 /// #[derive(Serialize, Deserialize)]
 /// struct OrderCreated {
 ///     event_id: String,
-///     customer_id: String,
 /// }
 ///
 /// impl Display for OrderCreated {
 ///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-///         write!(f, "{} event with id {}",
-///                "OrderCreated", self.event_id)
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
 ///     }
 /// }
 ///
@@ -356,69 +786,104 @@ pub fn unregister(_event_handler: impl EventHandler + Send + 'static) {
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         serde_json::to_string(&self).unwrap()
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
-///     event_id: String::from("event_id"),
-///     customer_id: String::from("customer_id"),
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// kafka::setup(configuration);
+///
+/// // side effects of two events are applied at once, so the offsets are committed once both are handled
+/// kafka::register_with_manual_commit(kafka::message_channel("orders", 0, "consumer_group"), |_event, commit| {
+///     if HANDLED.fetch_add(1, Ordering::SeqCst) + 1 == 2 {
+///         commit.commit().unwrap();
+///         COMMITTED.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }).unwrap();
+///
+/// let inspector: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .set("group.id", "consumer_group")
+///     .create().unwrap();
+/// let committed_offset = || {
+///     let mut partitions = TopicPartitionList::new();
+///     partitions.add_partition("orders", 0);
+///     inspector.committed_offsets(partitions, Duration::from_secs(5)).unwrap()
+///         .find_partition("orders", 0).unwrap()
+///         .offset()
 /// };
 ///
-/// kafka::emit(&order_created);
+/// kafka::emit(&OrderCreated { event_id: String::from("1") });
+/// while HANDLED.load(Ordering::SeqCst) < 1 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// assert_eq!(committed_offset(), Offset::Invalid);
+///
+/// kafka::emit(&OrderCreated { event_id: String::from("2") });
+/// while COMMITTED.load(Ordering::SeqCst) < 1 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// assert_eq!(committed_offset(), Offset::Offset(2));
 /// ```
-pub fn emit(event: &dyn Event) {
-    smol::block_on(async {
-        let configuration = BROKER_CONFIGURATION.lock().unwrap();
-        let topic = configuration.message_channel.topic;
-        let producer: FutureProducer<_, SmolRuntime> = ClientConfig::new()
-            .set("bootstrap.servers", configuration.bootstrap_servers)
-            .set("message.timeout.ms", configuration.timeout.to_string())
-            .create().expect("Producer creation error");
-
-        drop(configuration);
-
-        let delivery_status = producer
-            .send::<Vec<u8>, _, _>(
-                FutureRecord::to(topic).payload(&event.to_json()),
-                Duration::from_secs(0),
-            )
-            .await;
-        if let Err((e, _)) = delivery_status {
-            eprintln!("unable to send message: {}", e);
-            process::exit(1);
-        }
-
-        info!(target: &common::format_target("KafkaEmitter"), "event {} sent to the topic: {}", event, topic);
-    })
-}
-
-pub struct SmolRuntime;
-
-impl AsyncRuntime for SmolRuntime {
-    type Delay = future::Map<smol::Timer, fn(Instant)>;
-
-    fn spawn<T>(task: T)
-        where
-            T: Future<Output=()> + Send + 'static,
-    {
-        smol::spawn(task).detach()
-    }
+pub fn register_with_manual_commit(message_channel: MessageChannel,
+                                   event_handler: impl Fn(&dyn Event, &OffsetCommit) + Send + 'static) -> Result<(), KafkaError> {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let topic = message_channel.topic;
+    let consumer = create_consumer(&configuration, &message_channel.group_id, false)?;
+    consumer.subscribe(&[topic])?;
+    let quarantine = configuration.quarantine_topic.as_deref()
+        .map(|quarantine_topic| QuarantineProducer::new(&configuration, quarantine_topic))
+        .transpose()?;
+    drop(configuration);
 
-    fn delay_for(duration: Duration) -> Self::Delay {
-        FutureExt::map(smol::Timer::after(duration), |_| ())
-    }
+    spawn_consumer(&message_channel, move |stop| {
+        smol::block_on(async {
+            let mut stream = consumer.stream();
+            let mut chunks = ChunkAssembler::default();
+            while let Some(message) = next_message(&mut stream, &stop, &mut None, &mut chunks).await {
+                match message {
+                    Ok(message) => match chunk_header(&message) {
+                        Some(chunk) => {
+                            if let Some(message) = chunks.reassemble(&message, chunk) {
+                                handle_with_commit(&message, &consumer, &event_handler, &quarantine, &chunks).await;
+                            }
+                        }
+                        None => handle_with_commit(&message, &consumer, &event_handler, &quarantine, &chunks).await,
+                    },
+                    Err(e) => error!(target: &common::format_target("KafkaConsumer"), "error receiving message: {}", e),
+                }
+            }
+        });
+    });
+    Ok(())
 }
 
-/// Emits Kafka event to specific message channel.
+/// Registers Kafka event handler consuming events in batches. Up to `batch_size` events (or whatever arrived within
+/// `batch_timeout`) are passed to `EventHandler::handle_batch` at once, and consumer offsets are committed once per
+/// batch (`batch_size` of 0 is treated as 1). Each event is admitted into the batch through the middleware (see
+/// `common::set_middleware`), if set. If the handler panics, the messages of the batch are quarantined (if quarantine
+/// topic is configured) before committing; otherwise nothing is committed, and the batch is consumed again. Returns an
+/// error, if the consumer can't be created or subscribed to the topic.
 ///
 /// # Examples
-/// ```
+/// Producing 100 events and consuming them in batches of 10 (requires running broker):
+/// ```no_run
 /// use std::any::Any;
 /// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
 /// use serde::{Deserialize, Serialize};
 /// use eventure::{kafka, model};
 ///
+/// static BATCHES: AtomicUsize = AtomicUsize::new(0);
+/// static EVENTS: AtomicUsize = AtomicUsize::new(0);
+///
 /// #[derive(Serialize, Deserialize)]
 /// struct OrderCreated {
 ///     event_id: String,
@@ -444,44 +909,2944 @@ impl AsyncRuntime for SmolRuntime {
 ///         self
 ///     }
 ///     fn to_json(&self) -> String {
-///         todo!()
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
 ///     }
 /// }
 ///
-/// let order_created = OrderCreated{
-///     event_id: String::from("event_id"),
-///     customer_id: String::from("customer_id"),
-/// };
-/// kafka::emit_to_channel(&order_created, kafka::MessageChannel { topic: "Orders", partition: 0, group_id: "consumer_group" });
+/// struct OrderBatchHandler;
+///
+/// impl Display for OrderBatchHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderBatchHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderBatchHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         EVENTS.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderBatchHandler")
+///     }
+///
+///     fn handle_batch(&self, events: &[Box<dyn model::Event>]) {
+///         BATCHES.fetch_add(1, Ordering::SeqCst);
+///         events.iter().for_each(|event| self.handle(event.as_ref()));
+///     }
+/// }
+///
+/// kafka::setup(kafka::configuration("orders-batch", 0));
+/// for index in 0..100 {
+///     kafka::emit(&OrderCreated { event_id: index.to_string(), customer_id: String::from("customer_id") });
+/// }
+///
+/// let handler_channel = kafka::message_channel("orders-batch", 0, "batch_consumer_group");
+/// kafka::register_batch(handler_channel, OrderBatchHandler, 10, Duration::from_secs(5)).unwrap();
+///
+/// while EVENTS.load(Ordering::SeqCst) < 100 {
+///     thread::sleep(Duration::from_millis(100));
+/// }
+/// assert_eq!(BATCHES.load(Ordering::SeqCst), 10);
+/// ```
+///
+/// Batch the handler panicked on is consumed again (runs against in-process mock cluster):
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct FlakyBatchHandler;
+///
+/// impl Display for FlakyBatchHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "FlakyBatchHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for FlakyBatchHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("FlakyBatchHandler")
+///     }
+///
+///     fn handle_batch(&self, events: &[Box<dyn model::Event>]) {
+///         if ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+///             panic!("first batch failed");
+///         }
+///         events.iter().for_each(|event| self.handle(event.as_ref()));
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+/// for index in 1..=3 {
+///     kafka::emit(&OrderCreated { event_id: index.to_string() });
+/// }
+///
+/// kafka::register_batch(kafka::message_channel("orders", 0, "batch_consumer_group"), FlakyBatchHandler, 3,
+///     Duration::from_secs(1));
+/// while HANDLED.lock().unwrap().len() < 3 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["1", "2", "3"]);
+/// assert!(ATTEMPTS.load(Ordering::SeqCst) >= 2);
+/// ```
+pub fn register_batch(message_channel: MessageChannel, event_handler: impl EventHandler + Send + 'static,
+                      batch_size: usize, batch_timeout: Duration) -> Result<(), KafkaError> {
+    let batch_size = batch_size.max(1);
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let topic = message_channel.topic;
+    let consumer = create_consumer(&configuration, &message_channel.group_id, false)?;
+    consumer.subscribe(&[topic])?;
+    let quarantine = configuration.quarantine_topic.as_deref()
+        .map(|quarantine_topic| QuarantineProducer::new(&configuration, quarantine_topic))
+        .transpose()?;
+    drop(configuration);
+
+    spawn_consumer(&message_channel, move |stop| {
+        smol::block_on(async {
+            let mut stream = consumer.stream();
+            let mut chunks = ChunkAssembler::default();
+            let mut consumed = BTreeMap::new();
+            while !stop.load(Ordering::SeqCst) {
+                let mut events: Vec<Box<dyn Event>> = Vec::with_capacity(batch_size);
+                // messages of the events, kept for quarantine, and offsets to consume the batch again from
+                let mut messages: Vec<OwnedMessage> = Vec::new();
+                let mut batch_start = BTreeMap::new();
+                let deadline = Instant::now() + batch_timeout;
+                while events.len() < batch_size {
+                    match future::select(stream.next(), smol::Timer::at(deadline)).await {
+                        Either::Left((Some(Ok(message)), _)) => {
+                            let partition = (String::from(message.topic()), message.partition());
+                            batch_start.entry(partition.clone())
+                                .or_insert_with(|| chunks.committable(message.topic(), message.partition(), message.offset()));
+                            consumed.insert(partition, message.offset() + 1);
+                            match chunk_header(&message) {
+                                Some(chunk) => {
+                                    if let Some(message) = chunks.reassemble(&message, chunk) {
+                                        if collect_event(&message, &mut events, &quarantine).await && quarantine.is_some() {
+                                            messages.push(message);
+                                        }
+                                    }
+                                }
+                                None => {
+                                    if collect_event(&message, &mut events, &quarantine).await && quarantine.is_some() {
+                                        messages.push(message.detach());
+                                    }
+                                }
+                            }
+                        }
+                        Either::Left((Some(Err(e)), _)) => {
+                            error!(target: &common::format_target("KafkaConsumer"), "error receiving message: {}", e);
+                        }
+                        Either::Left((None, _)) => {
+                            warn!(target: &common::format_target("KafkaConsumer"), "consumer stream ended, stopping batch consumer of the topic: {}", topic);
+                            return;
+                        }
+                        Either::Right(_) => {
+                            chunks.sweep();
+                            break;
+                        }
+                    }
+                }
+                if events.is_empty() {
+                    continue;
+                }
+
+                info!(target: &common::format_target("KafkaConsumer"), "handling batch of {} events from the topic: {}", events.len(), topic);
+                let handled = panic::catch_unwind(AssertUnwindSafe(|| event_handler.handle_batch(&events)));
+                match (handled, &quarantine) {
+                    (Ok(()), _) => events.iter().for_each(|event| record_handled(&**event)),
+                    (Err(_), Some(quarantine)) => {
+                        error!(target: &common::format_target("KafkaConsumer"), "handler {} failed on batch of {} events", event_handler, events.len());
+                        for message in &messages {
+                            quarantine.send(message, "batch handler failed").await;
+                        }
+                    }
+                    (Err(_), None) => {
+                        error!(target: &common::format_target("KafkaConsumer"),
+                            "handler {} failed on batch of {} events, batch not committed", event_handler, events.len());
+                        rewind(&consumer, &batch_start);
+                        consumed.clear();
+                        continue;
+                    }
+                }
+                if let Err(e) = consumer.commit(&chunks.committable_offsets(&consumed), CommitMode::Async) {
+                    error!(target: &common::format_target("KafkaConsumer"), "unable to commit batch offsets: {}", e);
+                }
+                consumed.clear();
+            }
+        });
+    });
+    Ok(())
+}
+
+/// Consumes exactly `n` Kafka messages on the calling thread, dispatching them to the event handler, then commits
+/// consumer offsets and returns (instead of consuming forever, like `register`). Messages which can't be deserialized
+/// count as consumed (and get quarantined, if quarantine topic is configured), chunked ones count once reassembled.
+///
+/// # Examples
+/// Producing five events and consuming three of them (runs against in-process mock cluster):
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// for index in 0..5 {
+///     kafka::emit(&OrderCreated { event_id: index.to_string(), customer_id: String::from("customer_id") });
+/// }
+///
+/// let channel = || kafka::message_channel("orders", 0, "consume_group");
+/// kafka::consume_n(channel(), 3, OrderCreatedEventHandler).unwrap();
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["0", "1", "2"]);
+///
+/// // offsets were committed, so the group continues with the rest
+/// kafka::consume_n(channel(), 2, OrderCreatedEventHandler).unwrap();
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["0", "1", "2", "3", "4"]);
+/// ```
+pub fn consume_n(message_channel: MessageChannel, n: usize, event_handler: impl EventHandler) -> Result<(), KafkaError> {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let topic = message_channel.topic;
+    let consumer = create_consumer(&configuration, &message_channel.group_id, false)?;
+    let quarantine = configuration.quarantine_topic.as_deref()
+        .map(|quarantine_topic| QuarantineProducer::new(&configuration, quarantine_topic))
+        .transpose()?;
+    drop(configuration);
+
+    consumer.subscribe(&[topic])?;
+    let mut chunks = ChunkAssembler::default();
+    let mut consumed = BTreeMap::new();
+    smol::block_on(async {
+        let mut handled = 0;
+        while handled < n {
+            let message = consumer.recv().await?;
+            consumed.insert((String::from(message.topic()), message.partition()), message.offset() + 1);
+            if handle_message(&message, &event_handler, &quarantine, &mut chunks).await {
+                handled += 1;
+            }
+        }
+        Ok::<(), KafkaError>(())
+    })?;
+    if !consumed.is_empty() {
+        consumer.commit(&chunks.committable_offsets(&consumed), CommitMode::Sync)?;
+    }
+    info!(target: &common::format_target("KafkaConsumer"), "{} messages consumed from the topic: {}", n, topic);
+    Ok(())
+}
+
+/// Creates Kafka consumer subscribed to the message channel topic, to be polled manually (see `poll`). No thread is
+/// spawned; offsets are committed automatically, if enabled in the broker configuration.
+///
+/// # Examples
+/// ```no_run
+/// use eventure::kafka;
+///
+/// let consumer = kafka::consumer(kafka::message_channel("orders", 0, "consumer_group")).unwrap();
+/// ```
+pub fn consumer(message_channel: MessageChannel) -> Result<ConsumerHandle, KafkaError> {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let consumer: BaseConsumer<DefaultConsumerContext> =
+        consumer_config(&configuration, &message_channel.group_id, configuration.auto_commit_enabled)
+            .set("enable.auto.offset.store", "false")
+            .create()?;
+    let quarantine = configuration.quarantine_topic.as_deref()
+        .map(|quarantine_topic| QuarantineProducer::new(&configuration, quarantine_topic))
+        .transpose()?;
+    drop(configuration);
+
+    consumer.subscribe(&[message_channel.topic])?;
+    info!(target: &common::format_target("KafkaConsumer"), "consumer subscribed to the topic: {}", message_channel.topic);
+    Ok(ConsumerHandle { consumer, message_channel, quarantine, chunks: Mutex::new(ChunkAssembler::default()) })
+}
+
+/// Polls Kafka consumer once, waiting for a message at most the given time. Returns the event, or tells why there is
+/// none (timeout, duplicate or a chunk of an event being reassembled, see `Polled`). Messages which can't be
+/// deserialized are quarantined (if quarantine topic is configured) and reported as `PollError::Deserialization`.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+/// use eventure::kafka::Polled;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// kafka::emit(&OrderCreated { event_id: String::from("1") });
+///
+/// let consumer = kafka::consumer(kafka::message_channel("orders", 0, "consumer_group")).unwrap();
+/// // the first polls may time out, while the consumer joins the group
+/// let event = (0..30)
+///     .find_map(|_| match kafka::poll(&consumer, Duration::from_secs(1)).unwrap() {
+///         Polled::Event(event) => Some(event),
+///         _ => None,
+///     })
+///     .expect("event expected");
+/// assert_eq!(event.id(), "1");
+///
+/// assert!(matches!(kafka::poll(&consumer, Duration::from_millis(500)), Ok(Polled::Timeout)));
+/// ```
+pub fn poll(consumer: &ConsumerHandle, timeout: Duration) -> Result<Polled, PollError> {
+    let mut chunks = consumer.chunks.lock().unwrap();
+    chunks.sweep();
+    let message = match consumer.consumer.poll(timeout) {
+        Some(message) => message?,
+        None => return Ok(Polled::Timeout),
+    };
+    let reassembled = chunk_header(&message).map(|chunk| chunks.reassemble(&message, chunk));
+    store_offset(&consumer.consumer, &message, &chunks);
+    drop(chunks);
+    let message = match reassembled {
+        Some(Some(reassembled)) => reassembled,
+        Some(None) => return Ok(Polled::Chunk),
+        None => message.detach(),
+    };
+    match deserialize(&message) {
+        Ok(event) if is_duplicate(&*event) => Ok(Polled::Duplicate),
+        Ok(event) => {
+            check_sequence(&message, &*event);
+            // handled by the caller, so recorded once polled
+            record_handled(&*event);
+            Ok(Polled::Event(event))
+        }
+        Err(e) => {
+            warn!(target: &common::format_target("KafkaConsumer"),
+                "skipping message at offset {} of {}[{}], deserialization failed: {}",
+                message.offset(), message.topic(), message.partition(), e);
+            if let Some(quarantine) = &consumer.quarantine {
+                smol::block_on(quarantine.send(&message, &format!("deserialization failed: {}", e)));
+            }
+            Err(PollError::Deserialization(e))
+        }
+    }
+}
+
+/// Stops all Kafka consumers started by `register`, `register_batch` and `register_with_manual_commit`, waiting
+/// for the messages being handled (batch consumers stop once the current batch is handled). Returns number of stopped
+/// consumers. Emitted events are delivered before `emit` returns, so there is no producer to flush.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use rdkafka::mocking::MockCluster;
+/// use eventure::{kafka, model};
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         println!("{}: handling {}", "OrderEventHandler", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler).unwrap();
+/// assert_eq!(kafka::shutdown(), 1);
+/// assert_eq!(kafka::shutdown(), 0);
+/// ```
+pub fn shutdown() -> usize {
+    let consumers: Vec<RunningConsumer> = CONSUMERS.lock().unwrap().drain(..).collect();
+    consumers.iter().for_each(|consumer| consumer.stop.store(true, Ordering::SeqCst));
+    let stopped = consumers.len();
+    for consumer in consumers {
+        if consumer.thread.join().is_err() {
+            error!(target: &common::format_target("KafkaConsumer"), "consumer {} failed", consumer.description);
+        }
+    }
+    info!(target: &common::format_target("KafkaConsumer"), "{} consumers stopped", stopped);
+    stopped
+}
+
+/// Shuts Kafka message broker down: stops all consumers like `shutdown`, flushes (at most the configured timeout) and
+/// drops the shared producer, and clears the registered event types, expected schemas, cached partition counts, dedup
+/// store and last sequences. Returns number of stopped consumers and result of the flush (`None`, if no producer was
+/// created since setup).
+pub(crate) fn shutdown_flushing() -> (usize, Option<Result<(), KafkaError>>) {
+    let stopped = shutdown();
+    let producer = PRODUCER.lock().unwrap().take();
+    let flushed = producer.map(|producer| {
+        let timeout = Duration::from_millis(BROKER_CONFIGURATION.lock().unwrap().timeout as u64);
+        producer.flush(timeout)
+            .inspect_err(|e| error!(target: &common::format_target("KafkaEmitter"), "unable to flush producer: {}", e))
+    });
+    TYPE_MAP.lock().unwrap().clear();
+    EXPECTED_SCHEMAS.lock().unwrap().clear();
+    PARTITION_COUNTS.lock().unwrap().clear();
+    *DEDUP_STORE.lock().unwrap() = None;
+    *LAST_SEQUENCES.lock().unwrap() = LastSequences::new();
+    (stopped, flushed)
+}
+
+/// Unregisters Kafka event handler.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// let handler_channel = kafka::message_channel("orders", 0, "consumer_group");
+///
+/// struct OrderCreatedEventHandler;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         match event.as_any().downcast_ref::<OrderCreated>() {
+///             Some(order_create) => self.handle(order_create),
+///             None => println!("{}: not handling {}", "OrderCreatedEventHandler", event)
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// impl OrderCreatedEventHandler {
+///     fn handle(&self, event: &OrderCreated) {
+///         println!("{}: handling {}","OrderCreatedEventHandler", event)
+///     }
+/// }
+///
+/// kafka::setup(kafka::configuration("orders", 0));
+///
+/// let order_created_handler = OrderCreatedEventHandler;
+/// kafka::register(handler_channel, order_created_handler).unwrap();
+///
+/// let order_created_handler = OrderCreatedEventHandler;
+/// kafka::unregister(order_created_handler);
+/// ```
+pub fn unregister(_event_handler: impl EventHandler + Send + 'static) {
+    // TODO: implement
+}
+
+/// Emits Kafka event without specifying message channel. Events which can't be sent are logged (see `emit_batch` to
+/// get the delivery results instead).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         serde_json::to_string(&self).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+///
+/// kafka::emit(&order_created);
+/// ```
+pub fn emit(event: &dyn Event) {
+    smol::block_on(async {
+        let (producer, topic, timeout) = emitter();
+
+        let payload = event.to_json();
+        let fingerprint = schema::fingerprint(event);
+        let Ok(payload) = encode(payload, topic) else {
+            return;
+        };
+        let chunk_size = CHUNKING.lock().unwrap().map(|chunking| chunking.max_chunk_size);
+        if let Some(chunk_size) = chunk_size.filter(|chunk_size| payload.len() > *chunk_size) {
+            let sent = match send_chunks(&producer, event, topic, &payload, &fingerprint, chunk_size, timeout) {
+                Ok(deliveries) => delivered(deliveries).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = sent {
+                error!(target: &common::format_target("KafkaEmitter"), "unable to send event {} in chunks: {}", common::redacted(event), e);
+                return;
+            }
+            info!(target: &common::format_target("KafkaEmitter"), "event {} sent to the topic: {} in {} chunks",
+                common::redacted(event), topic, payload.len().div_ceil(chunk_size));
+            return;
+        }
+        let mut record = FutureRecord::to(topic).payload(&payload)
+            .headers(OwnedHeaders::new().insert(Header { key: SCHEMA_FINGERPRINT_HEADER, value: Some(&fingerprint) }));
+        if let Some(partition) = partition(&producer, event, topic, timeout) {
+            record = record.partition(partition);
+        }
+        let delivery_status = producer
+            .send::<Vec<u8>, _, _>(record, Duration::from_secs(0))
+            .await;
+        if let Err((e, _)) = delivery_status {
+            error!(target: &common::format_target("KafkaEmitter"), "unable to send event {}: {}", common::redacted(event), e);
+            return;
+        }
+
+        info!(target: &common::format_target("KafkaEmitter"), "event {} sent to the topic: {}", common::redacted(event), topic);
+    })
+}
+
+pub struct SmolRuntime;
+
+impl AsyncRuntime for SmolRuntime {
+    type Delay = future::Map<smol::Timer, fn(Instant)>;
+
+    fn spawn<T>(task: T)
+        where
+            T: Future<Output=()> + Send + 'static,
+    {
+        smol::spawn(task).detach()
+    }
+
+    fn delay_for(duration: Duration) -> Self::Delay {
+        FutureExt::map(smol::Timer::after(duration), |_| ())
+    }
+}
+
+/// Emits batch of Kafka events without specifying message channel. All events are enqueued on the producer (shared
+/// with `emit`) first and their deliveries awaited together (letting the producer batch them), returning per-event
+/// results in the batch order.
+///
+/// # Examples
+/// Emitting batch, one event of which exceeds the maximum message size (runs against in-process mock cluster):
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+///
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// kafka::setup(configuration);
+///
+/// let orders: Vec<OrderCreated> = (0..50)
+///     .map(|index| OrderCreated { event_id: index.to_string(), customer_id: String::from("customer_id") })
+///     .collect();
+/// let events: Vec<&dyn model::Event> = orders.iter().map(|order| order as &dyn model::Event).collect();
+/// let results = kafka::emit_batch(&events);
+/// assert_eq!(results.len(), 50);
+/// assert!(results.iter().all(Result::is_ok));
+///
+/// let oversized = OrderCreated { event_id: String::from("oversized"), customer_id: "x".repeat(2_000_000) };
+/// let results = kafka::emit_batch(&[&orders[0], &oversized, &orders[1]]);
+/// assert!(results[0].is_ok());
+/// assert!(results[1].is_err());
+/// assert!(results[2].is_ok());
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .set("group.id", "batch_inspector")
+///     .set("auto.offset.reset", "earliest")
+///     .create().unwrap();
+/// consumer.subscribe(&["orders"]).unwrap();
+/// let mut consumed = 0;
+/// while consumed < 52 {
+///     if let Some(Ok(_)) = consumer.poll(Duration::from_millis(100)) {
+///         consumed += 1;
+///     }
+/// }
+/// ```
+pub fn emit_batch(events: &[&dyn Event]) -> Vec<Result<(), KafkaError>> {
+    smol::block_on(async {
+        let (producer, topic, timeout) = emitter();
+
+        let payloads: Vec<(String, Result<Vec<u8>, KafkaError>)> = events.iter()
+            .map(|event| {
+                let payload = event.to_json();
+                (schema::fingerprint(*event), encode(payload, topic))
+            })
+            .collect();
+        let chunk_size = CHUNKING.lock().unwrap().map(|chunking| chunking.max_chunk_size);
+        let deliveries = events.iter().zip(payloads.iter()).map(|(event, (fingerprint, payload))| {
+            let deliveries = match payload {
+                Err(e) => Err(e.clone()),
+                Ok(payload) => match chunk_size.filter(|chunk_size| payload.len() > *chunk_size) {
+                    Some(chunk_size) => send_chunks(&producer, *event, topic, payload, fingerprint, chunk_size, timeout),
+                    None => {
+                        let mut record = FutureRecord::<Vec<u8>, _>::to(topic).payload(payload)
+                            .headers(OwnedHeaders::new().insert(Header { key: SCHEMA_FINGERPRINT_HEADER, value: Some(fingerprint) }));
+                        if let Some(partition) = partition(&producer, *event, topic, timeout) {
+                            record = record.partition(partition);
+                        }
+                        producer.send_result(record).map(|delivery| vec![delivery]).map_err(|(e, _)| e)
+                    }
+                },
+            };
+            async move { delivered(deliveries?).await }
+        });
+        let results = future::join_all(deliveries).await;
+
+        for (event, result) in events.iter().zip(results.iter()) {
+            match result {
+                Ok(()) => info!(target: &common::format_target("KafkaEmitter"), "event {} sent to the topic: {}", common::redacted(*event), topic),
+                Err(e) => error!(target: &common::format_target("KafkaEmitter"), "unable to send event {}: {}", common::redacted(*event), e),
+            }
+        }
+        results
+    })
+}
+
+/// Emits Kafka event to specific message channel.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         todo!()
+///     }
+/// }
+///
+/// let order_created = OrderCreated{
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+/// };
+/// kafka::emit_to_channel(&order_created, kafka::message_channel("Orders", 0, "consumer_group"));
 /// ```
 pub fn emit_to_channel(_event: &dyn Event, _channel: MessageChannel) {
     // TODO: implement
 }
 
+/// Sets custom partitioner, consulted by `emit` to choose target partition of the event explicitly (e.g. by region),
+/// instead of leaving it to the producer. Partitioner gets the event and partition count of the topic; partition
+/// out of range is ignored (producer chooses the partition then). Partition count is fetched once per topic and
+/// cached, fetched again if the partition chosen is out of range (e.g. after partitions were added) and on `setup`.
+///
+/// # Examples
+/// Emitting events to the partitions chosen by region (runs against in-process mock cluster):
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::message::Message;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     region: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 3, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+///
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// kafka::setup(configuration);
+///
+/// kafka::set_partitioner(|event, partition_count| {
+///     match event.as_any().downcast_ref::<OrderCreated>().map(|order| order.region.as_str()) {
+///         Some("eu") => 0,
+///         Some("us") => 1,
+///         _ => partition_count - 1,
+///     }
+/// });
+/// for (event_id, region) in [("1", "us"), ("2", "eu"), ("3", "apac"), ("4", "us")] {
+///     kafka::emit(&OrderCreated { event_id: String::from(event_id), region: String::from(region) });
+/// }
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .set("group.id", "partition_inspector")
+///     .set("auto.offset.reset", "earliest")
+///     .create().unwrap();
+/// consumer.subscribe(&["orders"]).unwrap();
+/// let mut partitions = Vec::new();
+/// while partitions.len() < 4 {
+///     if let Some(Ok(message)) = consumer.poll(Duration::from_millis(100)) {
+///         let event = kafka::deserialize_event(message.payload_view::<str>().unwrap().unwrap()).unwrap();
+///         partitions.push((event.id().to_string(), message.partition()));
+///     }
+/// }
+/// partitions.sort();
+///
+/// assert_eq!(partitions, vec![
+///     (String::from("1"), 1),
+///     (String::from("2"), 0),
+///     (String::from("3"), 2),
+///     (String::from("4"), 1),
+/// ]);
+/// ```
+pub fn set_partitioner(partitioner: impl Fn(&dyn Event, i32) -> i32 + Send + Sync + 'static) {
+    *PARTITIONER.lock().unwrap() = Some(Arc::new(partitioner));
+}
+
+/// Resets consumer group offsets of all topic partitions to the given position, so the events are replayed
+/// (or skipped) by the group consumers. The group shouldn't have active consumers while resetting, the broker
+/// rejects the commit otherwise.
+///
+/// # Examples
+/// Consuming events, resetting the group to the beginning and consuming them again (requires running broker):
+/// ```no_run
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+/// use eventure::kafka::ReplayPosition;
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// kafka::setup(kafka::configuration("orders-replay", 0));
+/// for index in 0..3 {
+///     kafka::emit(&OrderCreated { event_id: index.to_string(), customer_id: String::from("customer_id") });
+/// }
+///
+/// // consume everything and commit, as a regular consumer would
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", "localhost:9092")
+///     .set("group.id", "replay_group")
+///     .set("enable.auto.commit", "false")
+///     .set("auto.offset.reset", "earliest")
+///     .create().unwrap();
+/// consumer.subscribe(&["orders-replay"]).unwrap();
+/// let mut consumed = 0;
+/// while consumed < 3 {
+///     if let Some(Ok(message)) = consumer.poll(Duration::from_secs(1)) {
+///         consumer.commit_message(&message, CommitMode::Sync).unwrap();
+///         consumed += 1;
+///     }
+/// }
+/// drop(consumer);
+///
+/// kafka::reset_offsets("replay_group", "orders-replay", ReplayPosition::BEGINNING).unwrap();
+///
+/// kafka::register(kafka::message_channel("orders-replay", 0, "replay_group"), OrderCreatedEventHandler).unwrap();
+/// while HANDLED.load(Ordering::SeqCst) < 3 {
+///     thread::sleep(Duration::from_millis(100));
+/// }
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 3);
+/// ```
+pub fn reset_offsets(group_id: &str, topic: &str, position: ReplayPosition) -> Result<(), KafkaError> {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let timeout = Duration::from_millis(configuration.timeout as u64);
+    let consumer: BaseConsumer = client_config(&configuration, &[("group.id", group_id), ("enable.auto.commit", "false")])
+        .create()?;
+    drop(configuration);
+
+    let metadata = consumer.fetch_metadata(Some(topic), timeout)?;
+    let partitions: Vec<i32> = metadata.topics().iter()
+        .filter(|metadata_topic| metadata_topic.name() == topic)
+        .flat_map(|metadata_topic| metadata_topic.partitions().iter().map(|partition| partition.id()))
+        .collect();
+
+    let mut offsets = TopicPartitionList::new();
+    for partition in partitions {
+        let (low, high) = consumer.fetch_watermarks(topic, partition, timeout)?;
+        let offset = match position {
+            ReplayPosition::BEGINNING => low,
+            ReplayPosition::END => high,
+            ReplayPosition::TIMESTAMP(timestamp) => {
+                let mut timestamps = TopicPartitionList::new();
+                timestamps.add_partition_offset(topic, partition, Offset::Offset(timestamp))?;
+                match consumer.offsets_for_times(timestamps, timeout)?.find_partition(topic, partition)
+                    .map(|element| element.offset()) {
+                    Some(Offset::Offset(offset)) => offset,
+                    _ => high,
+                }
+            }
+        };
+        offsets.add_partition_offset(topic, partition, Offset::Offset(offset))?;
+    }
+
+    consumer.commit(&offsets, CommitMode::Sync)?;
+    info!(target: &common::format_target("KafkaConsumer"), "offsets of group {} for the topic {} reset to {:?}", group_id, topic, position);
+    Ok(())
+}
+
+/// Registers event type in the Kafka consumer type map, so the events tagged with `type_tag` are deserialized into
+/// `E`. Events are tagged the way `typetag` serializes them (`{"<type_tag>":{..}}`, as emitted by eventure), or by
+/// `"type"` field of the event JSON otherwise. Once the type map isn't empty, consumers deserialize events using the type map only
+/// (instead of global `typetag` registry), and events of unmapped types are reported as errors.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+/// use eventure::kafka::DeserializationError;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCanceled {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCanceled {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCanceled", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCanceled {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCanceled"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// kafka::register_type::<OrderCreated>("OrderCreated");
+/// kafka::register_type::<OrderCanceled>("OrderCanceled");
+///
+/// let created = OrderCreated { event_id: String::from("1"), customer_id: String::from("c") };
+/// let canceled = OrderCanceled { event_id: String::from("2"), customer_id: String::from("c") };
+/// let created = kafka::deserialize_event(&model::Event::to_json(&created)).unwrap();
+/// let canceled = kafka::deserialize_event(&model::Event::to_json(&canceled)).unwrap();
+/// assert_eq!(created.as_any().downcast_ref::<OrderCreated>().unwrap().event_id, "1");
+/// assert_eq!(canceled.as_any().downcast_ref::<OrderCanceled>().unwrap().event_id, "2");
+///
+/// // events tagged by "type" field
+/// let created = kafka::deserialize_event(r#"{"type":"OrderCreated","event_id":"3","customer_id":"c"}"#).unwrap();
+/// assert!(created.as_any().downcast_ref::<OrderCreated>().is_some());
+///
+/// let unmapped = kafka::deserialize_event(r#"{"OrderShipped":{"event_id":"4"}}"#);
+/// assert!(matches!(unmapped, Err(DeserializationError::UnmappedType(type_tag)) if type_tag == "OrderShipped"));
+/// ```
+pub fn register_type<E: Event + DeserializeOwned>(type_tag: &'static str) {
+    register_type_with(type_tag, |value| serde_json::from_value::<E>(value).map(|event| Box::new(event) as Box<dyn Event>));
+}
+
+/// Registers custom deserialization closure in the Kafka consumer type map, for events tagged with `type_tag`.
+/// The closure receives the event JSON without the outer `typetag` tag (or the whole event JSON, for events tagged by
+/// `"type"` field).
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use eventure::{kafka, model};
+///
+/// kafka::register_type_with("LegacyOrderCreated", |value| {
+///     serde_json::from_value::<Box<dyn model::Event>>(json!({ "OrderCreated": value }))
+/// });
+/// ```
+pub fn register_type_with(type_tag: &'static str,
+                          deserializer: impl Fn(Value) -> Result<Box<dyn Event>, serde_json::Error> + Send + 'static) {
+    info!(target: &common::format_target("KafkaConsumer"), "event type registered: {}", type_tag);
+    let mut type_map = TYPE_MAP.lock().unwrap();
+    type_map.retain(|(registered_tag, _)| *registered_tag != type_tag);
+    type_map.push((type_tag, Box::new(deserializer)));
+}
+
+/// Deserializes Kafka message payload into event, the same way consumers do: using the consumer type map
+/// (if any type is registered), or global `typetag` registry otherwise.
+///
+/// # Examples
+/// ```
+/// use eventure::kafka;
+///
+/// assert!(kafka::deserialize_event("{not an event").is_err());
+/// ```
+pub fn deserialize_event(payload: &str) -> Result<Box<dyn Event>, DeserializationError> {
+    let value: Value = serde_json::from_str(payload).map_err(DeserializationError::Malformed)?;
+    deserialize_value(value)
+}
+
+/// Computes schema fingerprint of the event: hash of the structure declared by its type (field names and value kinds,
+/// not the values). Every emitted message carries the fingerprint in the `schema-fingerprint` header. Options,
+/// sequences, maps and enums are described by their kind only, so that the fingerprint doesn't depend on whether an
+/// option is set, on the elements (if any) or on the variant. Fields skipped when serializing (e.g. with
+/// `skip_serializing_if`) count as missing.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderItem {
+///     sku: String,
+///     quantity: u32,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+///     coupon: Option<String>,
+///     items: Vec<OrderItem>,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCancelled {
+///     event_id: String,
+///     reason: String,
+/// }
+///
+/// impl Display for OrderCancelled {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCancelled", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCancelled {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCancelled"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// // the same fingerprint whether the coupon is set or not, and whatever the items
+/// let first = kafka::schema_fingerprint(&OrderCreated {
+///     event_id: String::from("1"),
+///     customer_id: String::from("a"),
+///     coupon: None,
+///     items: Vec::new(),
+/// });
+/// let second = kafka::schema_fingerprint(&OrderCreated {
+///     event_id: String::from("2"),
+///     customer_id: String::from("b"),
+///     coupon: Some(String::from("SUMMER")),
+///     items: vec![OrderItem { sku: String::from("sku-1"), quantity: 2 }],
+/// });
+/// assert_eq!(first, second);
+///
+/// let other = kafka::schema_fingerprint(&OrderCancelled { event_id: String::from("1"), reason: String::from("none") });
+/// assert_ne!(first, other);
+/// ```
+pub fn schema_fingerprint(event: &dyn Event) -> String {
+    schema::fingerprint(event)
+}
+
+/// Sets schema fingerprint consumers expect for events tagged with `type_tag`. Messages stamped with a different
+/// fingerprint are rejected with `DeserializationError::SchemaMismatch` (and quarantined, if quarantine topic
+/// is configured). Messages without fingerprint header are accepted.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::message::{Headers, Message};
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderCreatedEventHandler;
+///
+/// impl Display for OrderCreatedEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderCreatedEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderCreatedEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// cluster.create_topic("orders-quarantine", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// configuration.quarantine_topic = Some(String::from("orders-quarantine"));
+/// kafka::setup(configuration);
+///
+/// // consumer expects a different schema version than the producer stamps
+/// kafka::expect_schema("OrderCreated", "0123456789abcdef");
+/// kafka::emit(&OrderCreated { event_id: String::from("1"), customer_id: String::from("customer_id") });
+/// kafka::consume_n(kafka::message_channel("orders", 0, "consumer_group"), 1, OrderCreatedEventHandler).unwrap();
+/// assert!(HANDLED.lock().unwrap().is_empty());
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .set("group.id", "quarantine_inspector")
+///     .set("auto.offset.reset", "earliest")
+///     .create().unwrap();
+/// consumer.subscribe(&["orders-quarantine"]).unwrap();
+/// let message = (0..30)
+///     .find_map(|_| consumer.poll(Duration::from_secs(1)).and_then(Result::ok).map(|m| m.detach()))
+///     .expect("quarantined message expected");
+/// let reason = message.headers().unwrap().iter()
+///     .find(|header| header.key == "quarantine-reason")
+///     .and_then(|header| header.value)
+///     .map(|value| String::from_utf8_lossy(value).to_string())
+///     .unwrap();
+/// assert!(reason.contains("schema fingerprint"));
+/// ```
+pub fn expect_schema(type_tag: &'static str, fingerprint: &str) {
+    info!(target: &common::format_target("KafkaConsumer"), "expected schema of {}: {}", type_tag, fingerprint);
+    let mut expected_schemas = EXPECTED_SCHEMAS.lock().unwrap();
+    expected_schemas.retain(|(expected_tag, _)| *expected_tag != type_tag);
+    expected_schemas.push((type_tag, fingerprint.to_string()));
+}
+
+/// Sets serialization format of the Kafka message payloads on the topic, used by emitters and consumers of the topic.
+/// Topics not configured carry JSON. Events which can't be encoded in the format of the topic aren't sent (`emit` logs
+/// the error, `emit_batch` reports it for the event).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::message::Message;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderDraft;
+///
+/// impl Display for OrderDraft {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderDraft")
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderDraft {
+///     fn id(&self) -> &str {
+///         "draft"
+///     }
+///     fn name(&self) -> &str {
+///         "OrderDraft"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         String::from("{\"type\":\"OrderDraft\"")
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(event.id().to_string());
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders-external", 1, 1).unwrap();
+/// cluster.create_topic("orders-internal", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+///
+/// kafka::set_serialization_format("orders-external", kafka::SerializationFormat::JSON);
+/// kafka::set_serialization_format("orders-internal", kafka::SerializationFormat::MESSAGEPACK);
+///
+/// for topic in ["orders-external", "orders-internal"] {
+///     let mut configuration = kafka::configuration(topic, 0);
+///     configuration.bootstrap_servers = bootstrap_servers.clone();
+///     kafka::setup(configuration);
+///     kafka::emit(&OrderCreated { event_id: String::from(topic) });
+/// }
+///
+/// let payload = |topic: &str| {
+///     let consumer: BaseConsumer = ClientConfig::new()
+///         .set("bootstrap.servers", &bootstrap_servers)
+///         .set("group.id", format!("{}-inspector", topic))
+///         .set("auto.offset.reset", "earliest")
+///         .create().unwrap();
+///     consumer.subscribe(&[topic]).unwrap();
+///     (0..30)
+///         .find_map(|_| consumer.poll(Duration::from_secs(1)).and_then(Result::ok).map(|m| m.detach()))
+///         .and_then(|message| message.payload().map(<[u8]>::to_vec))
+///         .expect("message expected")
+/// };
+///
+/// let external = payload("orders-external");
+/// assert_eq!(serde_json::from_slice::<serde_json::Value>(&external).unwrap()["event_id"], "orders-external");
+///
+/// let internal = payload("orders-internal");
+/// assert!(serde_json::from_slice::<serde_json::Value>(&internal).is_err());
+/// assert_eq!(rmp_serde::from_slice::<serde_json::Value>(&internal).unwrap()["event_id"], "orders-internal");
+///
+/// // events which can't be encoded in the format of the topic aren't sent
+/// assert!(kafka::emit_batch(&[&OrderDraft])[0].is_err());
+///
+/// // consumers decode the payloads in the format of the topic
+/// kafka::consume_n(kafka::message_channel("orders-internal", 0, "consumer_group"), 1, OrderEventHandler).unwrap();
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["orders-internal"]);
+/// ```
+pub fn set_serialization_format(topic: &'static str, format: SerializationFormat) {
+    info!(target: &common::format_target("KafkaConsumer"), "serialization format of the topic {}: {:?}", topic, format);
+    let mut serialization_formats = SERIALIZATION_FORMATS.lock().unwrap();
+    serialization_formats.retain(|(format_topic, _)| *format_topic != topic);
+    serialization_formats.push((topic, format));
+}
+
+/// Sets deserialization fallback of the consumers: invoked with the raw event JSON and the error, when the payload
+/// is valid JSON (or MessagePack), but it can't be deserialized into an event (e.g. unknown type tag, version skew
+/// or schema mismatch), so that the application can handle (or log) unrecognized events instead of dropping them.
+/// The message is then skipped (and quarantined, if quarantine topic is configured) as usual.
+///
+/// # Examples
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::mocking::MockCluster;
+/// use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+/// use serde_json::Value;
+/// use eventure::{kafka, model};
+///
+/// static UNRECOGNIZED: Mutex<Vec<(Value, String)>> = Mutex::new(Vec::new());
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         panic!("unexpected event {}", event)
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// kafka::setup(configuration);
+/// kafka::set_deserialization_fallback(|value, error| {
+///     UNRECOGNIZED.lock().unwrap().push((value.clone(), error.to_string()));
+/// });
+///
+/// // event of a type this consumer doesn't know (yet)
+/// let producer: BaseProducer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .create().unwrap();
+/// let payload = r#"{"type":"OrderShipped","event_id":"1","carrier":"ups"}"#;
+/// producer.send(BaseRecord::<(), str>::to("orders").payload(payload)).unwrap();
+/// producer.flush(Duration::from_secs(10)).unwrap();
+///
+/// kafka::consume_n(kafka::message_channel("orders", 0, "consumer_group"), 1, OrderEventHandler).unwrap();
+///
+/// let unrecognized = UNRECOGNIZED.lock().unwrap();
+/// assert_eq!(unrecognized.len(), 1);
+/// assert_eq!(unrecognized[0].0, serde_json::from_str::<Value>(payload).unwrap());
+/// assert!(unrecognized[0].1.contains("OrderShipped"));
+/// ```
+pub fn set_deserialization_fallback(fallback: impl Fn(&Value, &DeserializationError) + Send + Sync + 'static) {
+    info!(target: &common::format_target("KafkaConsumer"), "deserialization fallback set");
+    *DESERIALIZATION_FALLBACK.lock().unwrap() = Some(Arc::new(fallback));
+}
+
+/// Enables consumer-side check of the event sequences: events carrying sequence number (see `Event::sequence`) are
+/// expected to arrive in non-decreasing order per topic partition and partition key (see `Event::partition_key`),
+/// without gaps. Duplicates skipped by the dedup store (see `set_dedup_store`) aren't checked, while redeliveries
+/// without it are reported as reordered. Violations are logged and reported to the alert callback. Events are handled
+/// regardless. The last sequences of up to 10 000 partition keys are kept, the oldest keys are evicted (and their
+/// next event isn't checked).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::{Arc, Mutex};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, kafka, model};
+/// use eventure::kafka::{SequenceViolation, SequenceViolationKind};
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+/// static VIOLATIONS: Mutex<Vec<SequenceViolation>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderUpdated {
+///     event_id: String,
+///     order_id: String,
+///     sequence: u64,
+/// }
+///
+/// impl Display for OrderUpdated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderUpdated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderUpdated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderUpdated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+///     fn sequence(&self) -> Option<u64> {
+///         Some(self.sequence)
+///     }
+///     fn partition_key(&self) -> Option<&str> {
+///         Some(&self.order_id)
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+/// kafka::set_dedup_store(Arc::new(common::InMemoryDedupStore::new(1024)));
+/// kafka::set_sequence_check(|violation| VIOLATIONS.lock().unwrap().push(violation.clone()));
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler).unwrap();
+///
+/// let order_updated = |event_id: &str, order_id: &str, sequence: u64| OrderUpdated {
+///     event_id: String::from(event_id),
+///     order_id: String::from(order_id),
+///     sequence,
+/// };
+/// kafka::emit(&order_updated("1", "order-1", 1));
+/// kafka::emit(&order_updated("2", "order-2", 1));
+/// kafka::emit(&order_updated("3", "order-1", 3));
+/// kafka::emit(&order_updated("4", "order-1", 2));
+/// kafka::emit(&order_updated("5", "order-2", 2));
+/// while HANDLED.load(Ordering::SeqCst) < 5 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// // redelivery, skipped by the dedup store before the check
+/// kafka::emit(&order_updated("4", "order-1", 2));
+/// kafka::emit(&order_updated("6", "order-2", 3));
+/// while HANDLED.load(Ordering::SeqCst) < 6 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+///
+/// let violations = VIOLATIONS.lock().unwrap();
+/// let summary: Vec<(&str, SequenceViolationKind, u64, u64)> = violations.iter()
+///     .map(|violation| (violation.partition_key.as_str(), violation.kind, violation.expected, violation.actual))
+///     .collect();
+/// assert_eq!(summary, vec![
+///     ("order-1", SequenceViolationKind::GAP, 2, 3),
+///     ("order-1", SequenceViolationKind::REORDERED, 3, 2),
+/// ]);
+/// kafka::shutdown();
+/// ```
+pub fn set_sequence_check(alert: impl Fn(&SequenceViolation) + Send + Sync + 'static) {
+    *SEQUENCE_CHECK.lock().unwrap() = Some(Arc::new(alert));
+    *LAST_SEQUENCES.lock().unwrap() = LastSequences::new();
+}
+
+/// Sets chunked transfer of the events whose serialized payload exceeds the maximum chunk size (e.g. broker's
+/// message size limit): `emit` and `emit_batch` split the payload into ordered chunks, sent to the same partition and
+/// sharing a message id (headers `chunk-message-id`, `chunk-index` and `chunk-count`). All the consumers (`register`
+/// and its variants, `consume_n` and `poll`) collect the chunks (in any order), each consumer on its own, and handle
+/// the reassembled event once complete; messages missing chunks are dropped after the reassembly timeout (with a
+/// warning). Offsets aren't committed past the first chunk of a message still being reassembled, so that the
+/// chunks are consumed again after a restart. Consumers reassemble chunked messages also when chunking isn't set,
+/// dropping incomplete ones after 60 seconds.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static HANDLED: Mutex<Vec<(&str, String)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct DocumentUploaded {
+///     event_id: String,
+///     content: String,
+/// }
+///
+/// impl Display for DocumentUploaded {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "DocumentUploaded", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for DocumentUploaded {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "DocumentUploaded"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct DocumentEventHandler(&'static str);
+///
+/// impl Display for DocumentEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "DocumentEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for DocumentEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         let document = event.as_any().downcast_ref::<DocumentUploaded>().unwrap();
+///         HANDLED.lock().unwrap().push((self.0, document.content.clone()));
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("DocumentEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("documents", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("documents", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+/// kafka::set_chunking(1024, Duration::from_secs(10));
+/// // each consumer group reassembles its own copy of the chunks
+/// kafka::register(kafka::message_channel("documents", 0, "indexer"), DocumentEventHandler("indexer")).unwrap();
+/// kafka::register(kafka::message_channel("documents", 0, "archiver"), DocumentEventHandler("archiver")).unwrap();
+///
+/// let content: String = (0..10_000).map(|index| char::from(b'a' + (index % 26) as u8)).collect();
+/// kafka::emit(&DocumentUploaded { event_id: String::from("1"), content: content.clone() });
+/// let results = kafka::emit_batch(&[&DocumentUploaded { event_id: String::from("2"), content: content.clone() }]);
+/// assert!(results.iter().all(Result::is_ok));
+/// while HANDLED.lock().unwrap().len() < 4 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+///
+/// let mut handled = HANDLED.lock().unwrap().clone();
+/// handled.sort();
+/// assert_eq!(handled, vec![
+///     ("archiver", content.clone()),
+///     ("archiver", content.clone()),
+///     ("indexer", content.clone()),
+///     ("indexer", content),
+/// ]);
+/// kafka::shutdown();
+/// ```
+pub fn set_chunking(max_chunk_size: usize, reassembly_timeout: Duration) {
+    info!(target: &common::format_target("KafkaEmitter"), "chunking set: [max_chunk_size:{},reassembly_timeout:{:?}]",
+        max_chunk_size, reassembly_timeout);
+    *CHUNKING.lock().unwrap() = Some(Chunking { max_chunk_size: max_chunk_size.max(1), reassembly_timeout });
+}
+
+/// Sets callback invoked by the consumers registered with `register` (afterwards) when no messages arrive for the
+/// idle period (e.g. to alert monitoring of a possible upstream outage), passing the channel and the time since the
+/// last message (or registration). While the topic stays quiet, the callback is invoked again every idle period;
+/// each received message resets the timer.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+/// static IDLE: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         IDLE.lock().unwrap().clear();
+///         HANDLED.fetch_add(1, Ordering::SeqCst);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+/// kafka::set_on_idle(Duration::from_millis(500), |channel, idle_for| {
+///     IDLE.lock().unwrap().push((String::from(channel.topic), idle_for));
+/// });
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler).unwrap();
+///
+/// kafka::emit(&OrderCreated { event_id: String::from("1") });
+/// while HANDLED.load(Ordering::SeqCst) < 1 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+///
+/// // the topic goes silent past the idle period
+/// while IDLE.lock().unwrap().is_empty() {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// let idle = IDLE.lock().unwrap();
+/// assert_eq!(idle[0].0, "orders");
+/// assert!(idle[0].1 >= Duration::from_millis(500));
+/// drop(idle);
+/// kafka::shutdown();
+/// ```
+pub fn set_on_idle(idle_period: Duration, on_idle: impl Fn(&MessageChannel, Duration) + Send + Sync + 'static) {
+    info!(target: &common::format_target("KafkaConsumer"), "idle callback set: [idle_period:{:?}]", idle_period);
+    *IDLE_CALLBACK.lock().unwrap() = Some(IdleCallback { idle_period, on_idle: Arc::new(on_idle) });
+}
+
+/// Sets dedup store consulted by the consumers: events whose id is already recorded in the store are skipped (and
+/// their offsets committed as usual), the rest are recorded once handled successfully (so that events whose handler
+/// failed are processed again when redelivered), or once returned by `poll`. Sharing the store with
+/// `in_memory::set_dedup_store` makes events processed only once across both backends, as both key them by the
+/// event id alone.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::{Arc, Mutex};
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{common, in_memory, kafka, model};
+///
+/// static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler(&'static str);
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         HANDLED.lock().unwrap().push(format!("{}:{}", self.0, event.id()));
+///     }
+///
+///     fn id(&self) -> String {
+///         format!("OrderEventHandler-{}", self.0)
+///     }
+/// }
+///
+/// let dedup_store = Arc::new(common::InMemoryDedupStore::new(1024));
+/// in_memory::set_dedup_store(dedup_store.clone());
+/// kafka::set_dedup_store(dedup_store);
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+///
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler("in_memory"));
+/// in_memory::emit_to_channel(&OrderCreated { event_id: String::from("1") },
+///     in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"));
+///
+/// // the same event also arrives via Kafka, along with a new one
+/// kafka::emit(&OrderCreated { event_id: String::from("1") });
+/// kafka::emit(&OrderCreated { event_id: String::from("2") });
+/// kafka::consume_n(kafka::message_channel("orders", 0, "consumer_group"), 2, OrderEventHandler("kafka")).unwrap();
+///
+/// assert_eq!(*HANDLED.lock().unwrap(), vec!["in_memory:1", "kafka:2"]);
+/// ```
+pub fn set_dedup_store(dedup_store: Arc<dyn DedupStore>) {
+    info!(target: &common::format_target("KafkaConsumer"), "dedup store set");
+    *DEDUP_STORE.lock().unwrap() = Some(dedup_store);
+}
+
+/// Returns headers of the Kafka message being handled on the current thread (e.g. trace context or tenant), with values
+/// decoded as UTF-8 (lossy). Returns `None` outside of event handling, as well as in batch handlers.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::Mutex;
+/// use std::time::Duration;
+/// use rdkafka::ClientConfig;
+/// use rdkafka::message::{Header, OwnedHeaders};
+/// use rdkafka::mocking::MockCluster;
+/// use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{kafka, model};
+///
+/// static TRACE_IDS: Mutex<Vec<Option<String>>> = Mutex::new(Vec::new());
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}", "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, _event: &(dyn model::Event + '_)) {
+///         let trace_id = kafka::current_headers().and_then(|headers| headers.get("trace-id").cloned());
+///         TRACE_IDS.lock().unwrap().push(trace_id);
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let bootstrap_servers = cluster.bootstrap_servers();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = bootstrap_servers.clone();
+/// kafka::setup(configuration);
+///
+/// let producer: BaseProducer = ClientConfig::new()
+///     .set("bootstrap.servers", &bootstrap_servers)
+///     .create().unwrap();
+/// let payload = model::Event::to_json(&OrderCreated { event_id: String::from("1") });
+/// let headers = OwnedHeaders::new().insert(Header { key: "trace-id", value: Some("4bf92f3577b34da6") });
+/// producer.send(BaseRecord::<(), str>::to("orders").payload(&payload).headers(headers)).unwrap();
+/// producer.flush(Duration::from_secs(10)).unwrap();
+///
+/// kafka::consume_n(kafka::message_channel("orders", 0, "consumer_group"), 1, OrderEventHandler).unwrap();
+///
+/// assert_eq!(*TRACE_IDS.lock().unwrap(), vec![Some(String::from("4bf92f3577b34da6"))]);
+/// assert_eq!(kafka::current_headers(), None);
+/// ```
+pub fn current_headers() -> Option<HashMap<String, String>> {
+    CURRENT_HEADERS.with(|current| current.borrow().clone())
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Spawns consumer thread, stopped by `shutdown` through the flag passed to the consumer loop.
+fn spawn_consumer(message_channel: &MessageChannel, consumer_loop: impl FnOnce(Arc<AtomicBool>) + Send + 'static) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let loop_stop = Arc::clone(&stop);
+    let thread = thread::spawn(move || consumer_loop(loop_stop));
+    let description = format!("{} in group {}", message_channel, message_channel.group_id);
+    info!(target: &common::format_target("KafkaConsumer"), "consumer of {} started", description);
+    CONSUMERS.lock().unwrap().push(RunningConsumer { description, stop, thread });
+}
+
+/// Waits for the next message, checking the stop flag (and the idle period, if watched) and dropping the expired
+/// chunks every `STOP_CHECK_INTERVAL`. Returns `None` once stopped.
+async fn next_message<S: Stream + Unpin>(stream: &mut S, stop: &AtomicBool, idle: &mut Option<IdleWatch>,
+                                         chunks: &mut ChunkAssembler) -> Option<S::Item> {
+    while !stop.load(Ordering::SeqCst) {
+        match future::select(stream.next(), smol::Timer::after(STOP_CHECK_INTERVAL)).await {
+            Either::Left((Some(message), _)) => {
+                if let Some(idle) = idle {
+                    idle.reset();
+                }
+                return Some(message);
+            }
+            Either::Left((None, _)) => {
+                warn!(target: &common::format_target("KafkaConsumer"), "consumer stream ended");
+                return None;
+            }
+            Either::Right(_) => {
+                if let Some(idle) = idle {
+                    idle.check();
+                }
+                chunks.sweep();
+            }
+        }
+    }
+    None
+}
+
+/// Handles consumed message: deserializes the event and passes it to the handler, quarantining the message (if the
+/// quarantine topic is configured) when it can't be deserialized or handled. Chunks of an oversized message are
+/// collected, until the message can be reassembled and handled. Returns whether the message was handled (i.e. it
+/// isn't a chunk of one still being reassembled).
+async fn handle_message(message: &impl Message, event_handler: &impl EventHandler, quarantine: &Option<QuarantineProducer>,
+                        chunks: &mut ChunkAssembler) -> bool {
+    match chunk_header(message) {
+        Some(chunk) => match chunks.reassemble(message, chunk) {
+            Some(message) => {
+                handle_complete_message(&message, event_handler, quarantine).await;
+                true
+            }
+            None => false,
+        },
+        None => {
+            handle_complete_message(message, event_handler, quarantine).await;
+            true
+        }
+    }
+}
+
+async fn handle_complete_message(message: &impl Message, event_handler: &impl EventHandler, quarantine: &Option<QuarantineProducer>) {
+    match deserialize(message) {
+        Ok(event) if is_duplicate(&*event) => {}
+        Ok(event) => {
+            check_sequence(message, &*event);
+            let handled = panic::catch_unwind(AssertUnwindSafe(|| with_headers(message, || handle_with_middleware(event_handler, &*event))));
+            match (handled, quarantine) {
+                (Ok(Ok(())), _) => record_handled(&*event),
+                (_, Some(quarantine)) => quarantine.send(message, "handler failed").await,
+                _ => {}
+            }
+        }
+        Err(e) => {
+            warn!(target: &common::format_target("KafkaConsumer"),
+                "skipping message at offset {} of {}[{}], deserialization failed: {}",
+                message.offset(), message.topic(), message.partition(), e);
+            if let Some(quarantine) = quarantine {
+                quarantine.send(message, &format!("deserialization failed: {}", e)).await;
+            }
+        }
+    }
+}
+
+/// Handles the event, wrapped in the middleware (see `common::set_middleware`), if set. Fails, if the middleware
+/// reports the event as not handled.
+fn handle_with_middleware(event_handler: &impl EventHandler, event: &dyn Event) -> Result<(), HandlerError> {
+    common::with_middleware(event, &|event| {
+        event_handler.handle(event);
+        Ok(())
+    })
+}
+
+/// Handles the message by the handler registered with `register_with_manual_commit`, passing it the handle committing
+/// the offset of the message (held back by the messages being reassembled).
+async fn handle_with_commit(message: &impl Message, consumer: &StreamConsumer<DefaultConsumerContext, SmolRuntime>,
+                            event_handler: &impl Fn(&dyn Event, &OffsetCommit), quarantine: &Option<QuarantineProducer>,
+                            chunks: &ChunkAssembler) {
+    match deserialize(message) {
+        Ok(event) if is_duplicate(&*event) => {}
+        Ok(event) => {
+            check_sequence(message, &*event);
+            let commit = OffsetCommit {
+                consumer,
+                topic: message.topic().to_string(),
+                partition: message.partition(),
+                offset: chunks.committable(message.topic(), message.partition(), message.offset() + 1) - 1,
+            };
+            let handled = panic::catch_unwind(AssertUnwindSafe(|| with_headers(message, || {
+                common::with_middleware(&*event, &|event| {
+                    event_handler(event, &commit);
+                    Ok(())
+                })
+            })));
+            match (handled, quarantine) {
+                (Ok(Ok(())), _) => record_handled(&*event),
+                (_, Some(quarantine)) => quarantine.send(message, "handler failed").await,
+                _ => {}
+            }
+        }
+        Err(e) => {
+            warn!(target: &common::format_target("KafkaConsumer"),
+                "skipping message at offset {} of {}[{}], deserialization failed: {}",
+                message.offset(), message.topic(), message.partition(), e);
+            if let Some(quarantine) = quarantine {
+                quarantine.send(message, &format!("deserialization failed: {}", e)).await;
+            }
+        }
+    }
+}
+
+/// Deserializes the event of the message consumed by a batch handler, quarantining the message (if the quarantine
+/// topic is configured) when it can't be deserialized.
+async fn collect_event(message: &impl Message, events: &mut Vec<Box<dyn Event>>, quarantine: &Option<QuarantineProducer>) -> bool {
+    match deserialize(message) {
+        Ok(event) if is_duplicate(&*event) => false,
+        Ok(event) => {
+            check_sequence(message, &*event);
+            match admit(event) {
+                Some(event) => {
+                    events.push(event);
+                    true
+                }
+                None => false,
+            }
+        }
+        Err(e) => {
+            warn!(target: &common::format_target("KafkaConsumer"),
+                "skipping message at offset {} of {}[{}], deserialization failed: {}",
+                message.offset(), message.topic(), message.partition(), e);
+            if let Some(quarantine) = quarantine {
+                quarantine.send(message, &format!("deserialization failed: {}", e)).await;
+            }
+            false
+        }
+    }
+}
+
+/// Admits the event into the batch through the middleware (see `common::set_middleware`), if set: the event passed on
+/// by the middleware is admitted (replacing the consumed one, if it's another event), while the event not passed on,
+/// or failed by the middleware, is left out.
+fn admit(event: Box<dyn Event>) -> Option<Box<dyn Event>> {
+    if !common::has_middleware() {
+        return Some(event);
+    }
+    // passed on unchanged (None) or replaced
+    let passed: RefCell<Option<Option<Box<dyn Event>>>> = RefCell::new(None);
+    let admitted = common::with_middleware(&*event, &|passed_event| {
+        let replacement = match ptr::addr_eq(passed_event, &*event) {
+            true => None,
+            false => Some(serde_json::from_str::<Box<dyn Event>>(&passed_event.to_json())
+                .map_err(|e| HandlerError::new(format!("replacing event not deserializable: {}", e)))?),
+        };
+        *passed.borrow_mut() = Some(replacement);
+        Ok(())
+    });
+    match (admitted, passed.into_inner()) {
+        (Ok(()), Some(None)) => Some(event),
+        (Ok(()), Some(Some(replacement))) => Some(replacement),
+        (Ok(()), None) => {
+            debug!(target: &common::format_target("KafkaConsumer"), "event {} left out of the batch by the middleware", common::redacted(&*event));
+            None
+        }
+        (Err(e), _) => {
+            warn!(target: &common::format_target("KafkaConsumer"), "event {} left out of the batch, middleware failed: {}", common::redacted(&*event), e);
+            None
+        }
+    }
+}
+
+/// Consumer loop of the handlers with processing deadline: messages are handled (in order) on a worker thread, while
+/// the consumer keeps polling and checks the message being handled against the deadline. Messages wait for the worker
+/// in a bounded hand-off (and a backlog of the ones received meanwhile, while the partitions are paused). Offsets are
+/// stored once the messages are handled. Once stopped, the messages received are handed off and the worker is joined.
+fn consume_with_deadline(consumer: StreamConsumer<DefaultConsumerContext, SmolRuntime>, event_handler: impl EventHandler + Send + 'static,
+                         quarantine: Option<QuarantineProducer>, deadline: Duration, pause_on_deadline: bool,
+                         mut idle: Option<IdleWatch>, stop: Arc<AtomicBool>) {
+    let consumer = Arc::new(consumer);
+    let in_progress: Arc<Mutex<Option<InProgress>>> = Arc::new(Mutex::new(None));
+    let (sender, receiver) = mpsc::sync_channel::<OwnedMessage>(HANDOFF_CAPACITY);
+    let worker_consumer = Arc::clone(&consumer);
+    let worker_in_progress = Arc::clone(&in_progress);
+    let worker = thread::spawn(move || {
+        let mut chunks = ChunkAssembler::default();
+        loop {
+            let message = match receiver.recv_timeout(STOP_CHECK_INTERVAL) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => {
+                    chunks.sweep();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            *worker_in_progress.lock().unwrap() = Some(InProgress::new(&message));
+            smol::block_on(handle_message(&message, &event_handler, &quarantine, &mut chunks));
+            store_offset(&*worker_consumer, &message, &chunks);
+            if let Some(finished) = worker_in_progress.lock().unwrap().take().filter(|finished| finished.overrun) {
+                info!(target: &common::format_target("KafkaConsumer"), "message at offset {} of {}[{}] handled after {:?}",
+                    finished.offset, finished.topic, finished.partition, finished.started.elapsed());
+            }
+        }
+    });
+
+    let mut backlog: VecDeque<OwnedMessage> = VecDeque::new();
+    let mut paused = None;
+    smol::block_on(async {
+        let mut stream = consumer.stream();
+        while !stop.load(Ordering::SeqCst) {
+            match future::select(stream.next(), smol::Timer::after(STOP_CHECK_INTERVAL)).await {
+                Either::Left((Some(Ok(message)), _)) => {
+                    if let Some(idle) = &mut idle {
+                        idle.reset();
+                    }
+                    backlog.push_back(message.detach());
+                }
+                Either::Left((Some(Err(e)), _)) => error!(target: &common::format_target("KafkaConsumer"), "error receiving message: {}", e),
+                Either::Left((None, _)) => {
+                    warn!(target: &common::format_target("KafkaConsumer"), "consumer stream ended");
+                    break;
+                }
+                Either::Right(_) => {
+                    if let Some(idle) = &mut idle {
+                        idle.check();
+                    }
+                }
+            }
+            while let Some(message) = backlog.pop_front() {
+                if let Err(TrySendError::Full(message)) = sender.try_send(message) {
+                    backlog.push_front(message);
+                    break;
+                }
+            }
+            check_deadline(&consumer, &in_progress, deadline, pause_on_deadline, !backlog.is_empty(), &mut paused);
+        }
+    });
+
+    backlog.into_iter().for_each(|message| {
+        let _ = sender.send(message);
+    });
+    drop(sender);
+    if worker.join().is_err() {
+        error!(target: &common::format_target("KafkaConsumer"), "worker handling messages with processing deadline failed");
+    }
+}
+
+/// Logs handler overrunning the processing deadline (once per message). Pauses all the assigned partitions while
+/// the hand-off to the worker is full (so the backlog stays bounded), otherwise the partition of the overrunning
+/// handler (if configured), and resumes them once neither holds.
+fn check_deadline(consumer: &StreamConsumer<DefaultConsumerContext, SmolRuntime>, in_progress: &Mutex<Option<InProgress>>,
+                  deadline: Duration, pause_on_deadline: bool, backlogged: bool, paused: &mut Option<TopicPartitionList>) {
+    let mut in_progress = in_progress.lock().unwrap();
+    if let Some(current) = in_progress.as_mut().filter(|current| !current.overrun && current.started.elapsed() > deadline) {
+        current.overrun = true;
+        warn!(target: &common::format_target("KafkaConsumer"),
+            "handler exceeded processing deadline of {:?} at offset {} of {}[{}], consumer keeps polling",
+            deadline, current.offset, current.topic, current.partition);
+    }
+    let to_pause = match in_progress.as_ref() {
+        _ if backlogged => consumer.assignment().ok(),
+        Some(current) if pause_on_deadline && current.overrun => {
+            let mut partitions = TopicPartitionList::new();
+            partitions.add_partition(&current.topic, current.partition);
+            Some(partitions)
+        }
+        _ => None,
+    };
+    drop(in_progress);
+    if *paused == to_pause {
+        return;
+    }
+    if let Some(partitions) = paused.take() {
+        if let Err(e) = consumer.resume(&partitions) {
+            warn!(target: &common::format_target("KafkaConsumer"), "unable to resume paused partitions: {}", e);
+        }
+    }
+    if let Some(partitions) = to_pause {
+        match consumer.pause(&partitions) {
+            Ok(()) => *paused = Some(partitions),
+            Err(e) => warn!(target: &common::format_target("KafkaConsumer"), "unable to pause partitions: {}", e),
+        }
+    }
+}
+
+/// Stores offset of the handled message (held back by the messages being reassembled), to be committed by the
+/// consumer (with auto-commit enabled).
+fn store_offset(consumer: &impl Consumer, message: &impl Message, chunks: &ChunkAssembler) {
+    let offset = chunks.committable(message.topic(), message.partition(), message.offset() + 1);
+    let mut offsets = TopicPartitionList::new();
+    let stored = offsets.add_partition_offset(message.topic(), message.partition(), Offset::Offset(offset))
+        .and_then(|()| consumer.store_offsets(&offsets));
+    if let Err(e) = stored {
+        warn!(target: &common::format_target("KafkaConsumer"), "unable to store offset {} of {}[{}]: {}",
+            offset, message.topic(), message.partition(), e);
+    }
+}
+
+/// Creates client config of the settings given, overridden by the extra properties (see
+/// `MessageBrokerConfiguration::extra_properties`).
+fn client_config(configuration: &MessageBrokerConfigurationInternal, settings: &[(&str, &str)]) -> ClientConfig {
+    let mut client_config = ClientConfig::new();
+    client_config.set("bootstrap.servers", &*configuration.bootstrap_servers);
+    settings.iter().for_each(|(key, value)| {
+        client_config.set(*key, *value);
+    });
+    configuration.extra_properties.iter().for_each(|(key, value)| {
+        client_config.set(key, value);
+    });
+    client_config
+}
+
+fn create_consumer(configuration: &MessageBrokerConfigurationInternal, group_id: &str, auto_commit_enabled: bool)
+                   -> Result<StreamConsumer<DefaultConsumerContext, SmolRuntime>, KafkaError> {
+    consumer_config(configuration, group_id, auto_commit_enabled).create()
+}
+
+fn consumer_config(configuration: &MessageBrokerConfigurationInternal, group_id: &str, auto_commit_enabled: bool) -> ClientConfig {
+    if group_id == DEFAULT_GROUP_ID {
+        warn!(target: &common::format_target("KafkaConsumer"), "consumer group \"{}\" used, shared by all the services not \
+            setting the group id (or application name, see set_application_name) explicitly", DEFAULT_GROUP_ID);
+    }
+    client_config(configuration, &[
+        ("session.timeout.ms", &configuration.timeout.to_string()),
+        ("enable.auto.commit", &auto_commit_enabled.to_string()),
+        ("group.id", group_id),
+        ("auto.offset.reset", "earliest"),
+    ])
+}
+
+/// Enqueues oversized payload as ordered chunks sharing a message id, also used as the record key, so that the chunks
+/// end up in the same partition. Returns deliveries of the chunks.
+fn send_chunks(producer: &FutureProducer<DefaultClientContext, SmolRuntime>, event: &dyn Event, topic: &str, payload: &[u8],
+               fingerprint: &str, chunk_size: usize, timeout: Duration) -> Result<Vec<DeliveryFuture>, KafkaError> {
+    let message_id = Uuid::new_v4().to_string();
+    let partition = partition(producer, event, topic, timeout);
+    let chunk_count = payload.len().div_ceil(chunk_size).to_string();
+    let mut deliveries = Vec::new();
+    for (index, chunk) in payload.chunks(chunk_size).enumerate() {
+        let index = index.to_string();
+        let headers = OwnedHeaders::new()
+            .insert(Header { key: SCHEMA_FINGERPRINT_HEADER, value: Some(fingerprint) })
+            .insert(Header { key: CHUNK_ID_HEADER, value: Some(&message_id) })
+            .insert(Header { key: CHUNK_INDEX_HEADER, value: Some(&index) })
+            .insert(Header { key: CHUNK_COUNT_HEADER, value: Some(&chunk_count) });
+        let mut record = FutureRecord::to(topic).key(&message_id).payload(chunk).headers(headers);
+        if let Some(partition) = partition {
+            record = record.partition(partition);
+        }
+        deliveries.push(producer.send_result(record).map_err(|(e, _)| e)?);
+    }
+    Ok(deliveries)
+}
+
+/// Waits for the deliveries (e.g. of the chunks of a message), failing on the first one not delivered.
+async fn delivered(deliveries: Vec<DeliveryFuture>) -> Result<(), KafkaError> {
+    for delivery in deliveries {
+        match delivery.await {
+            Ok(Ok(_)) => {}
+            Ok(Err((e, _))) => return Err(e),
+            Err(_) => return Err(KafkaError::Canceled),
+        }
+    }
+    Ok(())
+}
+
+/// Returns chunk header (message id, chunk index and count) of the message, if it's a chunk of an oversized one.
+fn chunk_header(message: &impl Message) -> Option<ChunkHeader> {
+    let headers = message.headers()?;
+    let header = |key: &str| headers.iter()
+        .find(|header| header.key == key)
+        .and_then(|header| header.value)
+        .and_then(|value| std::str::from_utf8(value).ok())
+        .map(String::from);
+    Some(ChunkHeader {
+        message_id: header(CHUNK_ID_HEADER)?,
+        index: header(CHUNK_INDEX_HEADER)?.parse().ok()?,
+        count: header(CHUNK_COUNT_HEADER)?.parse().ok()?,
+    })
+}
+
+/// Chooses target partition of the event using the custom partitioner, if set. Partition count of the topic is
+/// cached, and fetched again only if the chosen partition is out of range of the cached one.
+fn partition(producer: &FutureProducer<DefaultClientContext, SmolRuntime>, event: &dyn Event, topic: &str, timeout: Duration)
+             -> Option<i32> {
+    let partitioner = PARTITIONER.lock().unwrap().clone()?;
+    let cached = PARTITION_COUNTS.lock().unwrap().get(topic).copied();
+    let mut partition_count = match cached {
+        Some(partition_count) => partition_count,
+        None => fetch_partition_count(producer, topic, timeout)?,
+    };
+    let mut partition = partitioner(event, partition_count);
+    if cached.is_some() && !(0..partition_count).contains(&partition) {
+        // partitions may have been added since the count was cached
+        partition_count = fetch_partition_count(producer, topic, timeout)?;
+        partition = partitioner(event, partition_count);
+    }
+    if (0..partition_count).contains(&partition) {
+        Some(partition)
+    } else {
+        warn!(target: &common::format_target("KafkaEmitter"),
+            "partition {} chosen for event {} out of range of the topic {} ({} partitions)", partition, common::redacted(event), topic, partition_count);
+        None
+    }
+}
+
+/// Fetches partition count of the topic from the broker and caches it. Returns `None`, if it can't be fetched or the
+/// topic has no partitions.
+fn fetch_partition_count(producer: &FutureProducer<DefaultClientContext, SmolRuntime>, topic: &str, timeout: Duration)
+                         -> Option<i32> {
+    let partition_count = match producer.client().fetch_metadata(Some(topic), timeout) {
+        Ok(metadata) => metadata.topics().iter()
+            .find(|metadata_topic| metadata_topic.name() == topic)
+            .map_or(0, |metadata_topic| metadata_topic.partitions().len() as i32),
+        Err(e) => {
+            warn!(target: &common::format_target("KafkaEmitter"), "unable to fetch partitions of the topic {}: {}", topic, e);
+            return None;
+        }
+    };
+    if partition_count == 0 {
+        return None;
+    }
+    PARTITION_COUNTS.lock().unwrap().insert(String::from(topic), partition_count);
+    Some(partition_count)
+}
+
+/// Parses `.properties` content: `key=value`, `key: value` or `key value` lines, `#`/`!` comments and lines continued
+/// with trailing backslash.
+fn parse_properties(content: &str) -> Vec<(String, String)> {
+    let mut properties = Vec::new();
+    let mut logical_line = String::new();
+    for line in content.lines() {
+        let line = line.trim_start();
+        if logical_line.is_empty() && (line.is_empty() || line.starts_with('#') || line.starts_with('!')) {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(continued) => logical_line.push_str(continued),
+            None => {
+                logical_line.push_str(line);
+                let entry = std::mem::take(&mut logical_line);
+                let separator = entry.find(['=', ':', ' ', '\t']).unwrap_or(entry.len());
+                let (key, value) = entry.split_at(separator);
+                let value = value.trim_start();
+                let value = value.strip_prefix(['=', ':']).unwrap_or(value);
+                properties.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    properties
+}
+
+fn parse_property<T: FromStr>(key: &str, value: &str) -> io::Result<T> {
+    value.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid value of {}: {}", key, value)))
+}
+
+/// Exposes headers of the message (see `current_headers`) for the duration of its handling.
+fn with_headers<T>(message: &impl Message, handle: impl FnOnce() -> T) -> T {
+    let headers = message.headers()
+        .map(|headers| headers.iter()
+            .map(|header| (String::from(header.key), String::from_utf8_lossy(header.value.unwrap_or_default()).into_owned()))
+            .collect())
+        .unwrap_or_default();
+    let _previous = PreviousHeaders(CURRENT_HEADERS.with(|current| current.replace(Some(headers))));
+    handle()
+}
+
+/// Checks the dedup store (if set) for the consumed event, skipping it if already handled (see `record_handled`).
+fn is_duplicate(event: &dyn Event) -> bool {
+    let dedup_store = DEDUP_STORE.lock().unwrap().clone();
+    let duplicate = dedup_store.is_some_and(|dedup_store| dedup_store.contains(event.id()));
+    if duplicate {
+        info!(target: &common::format_target("KafkaConsumer"), "duplicate event {} skipped", common::redacted(event));
+    }
+    duplicate
+}
+
+/// Records the handled event in the dedup store (if set), so that its redeliveries are skipped.
+fn record_handled(event: &dyn Event) {
+    if let Some(dedup_store) = DEDUP_STORE.lock().unwrap().clone() {
+        dedup_store.insert(event.id());
+    }
+}
+
+fn deserialize(message: &impl Message) -> Result<Box<dyn Event>, DeserializationError> {
+    let value: Value = match serialization_format(message.topic()) {
+        SerializationFormat::JSON => {
+            let message_str = match message.payload_view::<str>() {
+                None => "",
+                Some(Ok(s)) => s,
+                Some(Err(_)) => "<invalid utf-8>",
+            };
+            serde_json::from_str(message_str).map_err(DeserializationError::Malformed)?
+        }
+        SerializationFormat::MESSAGEPACK => rmp_serde::from_slice(message.payload().unwrap_or_default())
+            .map_err(DeserializationError::MalformedMessagePack)?,
+    };
+    let actual = message.headers()
+        .and_then(|headers| headers.iter().find(|header| header.key == SCHEMA_FINGERPRINT_HEADER))
+        .and_then(|header| header.value)
+        .and_then(|value| std::str::from_utf8(value).ok());
+    let checked = match actual {
+        Some(actual) => check_schema(&value, actual),
+        None => Ok(()),
+    };
+    let fallback_value = DESERIALIZATION_FALLBACK.lock().unwrap().clone().map(|fallback| (fallback, value.clone()));
+    checked.and_then(|_| deserialize_value(value))
+        .inspect_err(|e| {
+            if let Some((fallback, value)) = fallback_value {
+                fallback(&value, e);
+            }
+        })
+}
+
+/// Checks sequence number of the consumed event (if the check is enabled) against the last one of its partition key,
+/// once the event passed the dedup store.
+fn check_sequence(message: &impl Message, event: &dyn Event) {
+    let (Some(alert), Some(actual)) = (SEQUENCE_CHECK.lock().unwrap().clone(), event.sequence()) else {
+        return;
+    };
+    let partition_key = String::from(event.partition_key().unwrap_or_default());
+    let key = (String::from(message.topic()), message.partition(), partition_key.clone());
+    let mut last_sequences = LAST_SEQUENCES.lock().unwrap();
+    let last = last_sequences.sequences.get(&key).copied();
+    let violation = match last {
+        Some(last) if actual < last => Some((SequenceViolationKind::REORDERED, last)),
+        Some(last) if actual > last + 1 => Some((SequenceViolationKind::GAP, last + 1)),
+        _ => None,
+    };
+    if last.is_none_or(|last| actual >= last) {
+        last_sequences.record(key, actual);
+    }
+    drop(last_sequences);
+    if let Some((kind, expected)) = violation {
+        let violation = SequenceViolation {
+            topic: String::from(message.topic()),
+            partition: message.partition(),
+            partition_key,
+            kind,
+            expected,
+            actual,
+        };
+        warn!(target: &common::format_target("KafkaConsumer"), "event {} out of sequence: {}", common::redacted(event), violation);
+        alert(&violation);
+    }
+}
+
+/// Deserializes event JSON using the consumer type map (if any type is registered), or global `typetag` registry.
+fn deserialize_value(mut value: Value) -> Result<Box<dyn Event>, DeserializationError> {
+    let type_map = TYPE_MAP.lock().unwrap();
+    if type_map.is_empty() {
+        return serde_json::from_value::<Box<dyn Event>>(value).map_err(DeserializationError::Malformed);
+    }
+    let (type_tag, fields) = match outer_type_tag(&value).map(str::to_string) {
+        Some(type_tag) => {
+            let fields = value[type_tag.as_str()].take();
+            (type_tag, fields)
+        }
+        None => (type_tag(&value).to_string(), value),
+    };
+    match type_map.iter().find(|(registered_tag, _)| *registered_tag == type_tag) {
+        Some((_, deserializer)) => deserializer(fields).map_err(DeserializationError::Malformed),
+        None => Err(DeserializationError::UnmappedType(type_tag)),
+    }
+}
+
+/// Returns type tag of event JSON: the single outer key in `typetag` representation (`{"<type_tag>":{..}}`), or
+/// `"type"` field otherwise.
+fn type_tag(value: &Value) -> &str {
+    outer_type_tag(value).unwrap_or_else(|| value.get("type").and_then(Value::as_str).unwrap_or_default())
+}
+
+fn outer_type_tag(value: &Value) -> Option<&str> {
+    value.as_object()
+        .filter(|object| object.len() == 1 && !object.contains_key("type"))
+        .and_then(|object| object.keys().next())
+        .map(String::as_str)
+}
+
+fn serialization_format(topic: &str) -> SerializationFormat {
+    SERIALIZATION_FORMATS.lock().unwrap().iter()
+        .find(|(format_topic, _)| *format_topic == topic)
+        .map_or(SerializationFormat::JSON, |(_, format)| *format)
+}
+
+/// Encodes event JSON in the serialization format of the topic. Events which can't be encoded are logged and reported
+/// as invalid messages (rather than sent in another format, which consumers of the topic couldn't decode).
+fn encode(payload: String, topic: &str) -> Result<Vec<u8>, KafkaError> {
+    match serialization_format(topic) {
+        SerializationFormat::JSON => Ok(payload.into_bytes()),
+        SerializationFormat::MESSAGEPACK => {
+            let encoded = serde_json::from_str::<Value>(&payload)
+                .map_err(|e| e.to_string())
+                .and_then(|value| rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()));
+            encoded.map_err(|e| {
+                error!(target: &common::format_target("KafkaEmitter"), "unable to encode event {} as MessagePack, not sent: {}",
+                    common::redacted_payload(None, payload.as_bytes()), e);
+                KafkaError::MessageProduction(RDKafkaErrorCode::InvalidMessage)
+            })
+        }
+    }
+}
+
+/// Seeks the partitions back to the offsets (by topic and partition), so that the messages from there on are consumed
+/// again.
+fn rewind(consumer: &StreamConsumer<DefaultConsumerContext, SmolRuntime>, offsets: &BTreeMap<(String, i32), i64>) {
+    for ((topic, partition), offset) in offsets {
+        if let Err(e) = consumer.seek(topic, *partition, Offset::Offset(*offset), SEEK_TIMEOUT) {
+            error!(target: &common::format_target("KafkaConsumer"), "unable to seek {}[{}] back to offset {}: {}", topic, partition, offset, e);
+        }
+    }
+}
+
+/// Returns the producer shared by the emitters (created on first use after setup), along with the topic and timeout.
+fn emitter() -> (FutureProducer<DefaultClientContext, SmolRuntime>, &'static str, Duration) {
+    let configuration = BROKER_CONFIGURATION.lock().unwrap();
+    let producer = PRODUCER.lock().unwrap()
+        .get_or_insert_with(|| client_config(&configuration, &[("message.timeout.ms", &configuration.timeout.to_string())])
+            .create().expect("Producer creation error"))
+        .clone();
+    (producer, configuration.message_channel.topic, Duration::from_millis(configuration.timeout as u64))
+}
+
+/// Checks fingerprint stamped on the message against the one expected for its event type (if any).
+fn check_schema(value: &Value, actual: &str) -> Result<(), DeserializationError> {
+    let expected_schemas = EXPECTED_SCHEMAS.lock().unwrap();
+    let type_tag = type_tag(value);
+    match expected_schemas.iter().find(|(expected_tag, _)| *expected_tag == type_tag) {
+        Some((_, expected)) if expected != actual => Err(DeserializationError::SchemaMismatch {
+            type_tag: type_tag.to_string(),
+            expected: expected.clone(),
+            actual: actual.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Private statics
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
 static BROKER_CONFIGURATION: Mutex<MessageBrokerConfigurationInternal> = Mutex::new(MessageBrokerConfigurationInternal::new());
+static PRODUCER: Mutex<Option<FutureProducer<DefaultClientContext, SmolRuntime>>> = Mutex::new(None);
+static TYPE_MAP: Mutex<Vec<(&'static str, EventDeserializer)>> = Mutex::new(Vec::new());
+static PARTITIONER: Mutex<Option<Partitioner>> = Mutex::new(None);
+static PARTITION_COUNTS: Mutex<BTreeMap<String, i32>> = Mutex::new(BTreeMap::new());
+static EXPECTED_SCHEMAS: Mutex<Vec<(&'static str, String)>> = Mutex::new(Vec::new());
+static DEDUP_STORE: Mutex<Option<Arc<dyn DedupStore>>> = Mutex::new(None);
+static SERIALIZATION_FORMATS: Mutex<Vec<(&'static str, SerializationFormat)>> = Mutex::new(Vec::new());
+static DESERIALIZATION_FALLBACK: Mutex<Option<Arc<DeserializationFallback>>> = Mutex::new(None);
+static CONSUMERS: Mutex<Vec<RunningConsumer>> = Mutex::new(Vec::new());
+static SEQUENCE_CHECK: Mutex<Option<Arc<SequenceAlert>>> = Mutex::new(None);
+static LAST_SEQUENCES: Mutex<LastSequences> = Mutex::new(LastSequences::new());
+static APPLICATION_NAME: Mutex<Option<&'static str>> = Mutex::new(None);
+static CHUNKING: Mutex<Option<Chunking>> = Mutex::new(None);
+static IDLE_CALLBACK: Mutex<Option<IdleCallback>> = Mutex::new(None);
+
+thread_local! {
+    static CURRENT_HEADERS: RefCell<Option<HashMap<String, String>>> = const { RefCell::new(None) };
+}
+
+const SCHEMA_FINGERPRINT_HEADER: &str = "schema-fingerprint";
+const DEFAULT_GROUP_ID: &str = "default";
+const CHUNK_ID_HEADER: &str = "chunk-message-id";
+const CHUNK_INDEX_HEADER: &str = "chunk-index";
+const CHUNK_COUNT_HEADER: &str = "chunk-count";
+const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+const HANDOFF_CAPACITY: usize = 16;
+const SEQUENCE_KEYS_CAPACITY: usize = 10_000;
+
+const SEEK_TIMEOUT: Duration = Duration::from_secs(5);
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Private structs
 // -----------------------------------------------------------------------------------------------------------------------------------------
 
+/// Message channel set up: its topic is the one emitted to, its group id the default one of the consumers.
 pub struct MessageChannelInternal {
     pub topic: &'static str,
-    pub partition: u16,
-    pub group_id: &'static str
+    pub group_id: Cow<'static, str>
 }
 
 struct MessageBrokerConfigurationInternal {
     message_channel: MessageChannelInternal,
-    bootstrap_servers: &'static str,
+    bootstrap_servers: Cow<'static, str>,
     topic_auto_create_enabled: bool,
     auto_commit_enabled: bool,
     timeout: u32,
+    quarantine_topic: Option<String>,
+    extra_properties: Vec<(String, String)>,
+    processing_deadline: Option<Duration>,
+    pause_on_deadline: bool,
+}
+
+type EventDeserializer = Box<dyn Fn(Value) -> Result<Box<dyn Event>, serde_json::Error> + Send>;
+
+type Partitioner = Arc<dyn Fn(&dyn Event, i32) -> i32 + Send + Sync>;
+
+type DeserializationFallback = dyn Fn(&Value, &DeserializationError) + Send + Sync;
+
+type SequenceAlert = dyn Fn(&SequenceViolation) + Send + Sync;
+
+type IdleAlert = dyn Fn(&MessageChannel, Duration) + Send + Sync;
+
+struct QuarantineProducer {
+    producer: FutureProducer<DefaultClientContext, SmolRuntime>,
+    topic: String,
+}
+
+struct InProgress {
+    topic: String,
+    partition: i32,
+    offset: i64,
+    started: Instant,
+    overrun: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Chunking {
+    max_chunk_size: usize,
+    reassembly_timeout: Duration,
+}
+
+struct ChunkHeader {
+    message_id: String,
+    index: usize,
+    count: usize,
+}
+
+/// Chunks of an oversized message received so far, by chunk index, and offset of the earliest one.
+struct PendingChunks {
+    started: Instant,
+    first_offset: i64,
+    count: usize,
+    chunks: BTreeMap<usize, Vec<u8>>,
+}
+
+/// Oversized messages being reassembled by a consumer, by topic, partition and message id.
+#[derive(Default)]
+struct ChunkAssembler {
+    pending: BTreeMap<(String, i32, String), PendingChunks>,
+}
+
+#[derive(Clone)]
+struct IdleCallback {
+    idle_period: Duration,
+    on_idle: Arc<IdleAlert>,
+}
+
+/// Last sequence number by topic, partition and partition key, along with the recording order (for eviction).
+struct LastSequences {
+    sequences: BTreeMap<(String, i32, String), u64>,
+    order: VecDeque<(String, i32, String)>,
+}
+
+/// Idle timer of a consumer: time of the last message (or registration), and when the callback is due next.
+struct IdleWatch {
+    message_channel: MessageChannel,
+    callback: IdleCallback,
+    last_message: Instant,
+    due: Instant,
+}
+
+struct RunningConsumer {
+    description: String,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
 }
 
+/// Headers current before `with_headers`, restored when dropped (also if handling panics).
+struct PreviousHeaders(Option<HashMap<String, String>>);
+
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Implementation
 // -----------------------------------------------------------------------------------------------------------------------------------------
@@ -492,6 +3857,23 @@ impl Display for MessageChannel {
     }
 }
 
+impl OffsetCommit<'_> {
+    /// Commits offsets up to (and including) the message being handled, synchronously.
+    pub fn commit(&self) -> Result<(), KafkaError> {
+        let mut offsets = TopicPartitionList::new();
+        offsets.add_partition_offset(&self.topic, self.partition, Offset::Offset(self.offset + 1))?;
+        self.consumer.commit(&offsets, CommitMode::Sync)?;
+        info!(target: &common::format_target("KafkaConsumer"), "offset {} of {}[{}] committed", self.offset, self.topic, self.partition);
+        Ok(())
+    }
+}
+
+impl Display for ConsumerHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "consumer of {} in group {}", self.message_channel, self.message_channel.group_id)
+    }
+}
+
 impl Display for MessageBrokerConfiguration {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "[default-channel:{},topic-auto-create:{},timeout:{}]",
@@ -499,20 +3881,67 @@ impl Display for MessageBrokerConfiguration {
     }
 }
 
+impl Display for SequenceViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SequenceViolationKind::REORDERED => write!(f, "sequence {} after {} of key {:?} in {}[{}]",
+                                                       self.actual, self.expected, self.partition_key, self.topic, self.partition),
+            SequenceViolationKind::GAP => write!(f, "sequence {} instead of {} of key {:?} in {}[{}]",
+                                                 self.actual, self.expected, self.partition_key, self.topic, self.partition),
+        }
+    }
+}
+
+impl Display for DeserializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeserializationError::Malformed(e) => write!(f, "malformed event: {}", e),
+            DeserializationError::UnmappedType(type_tag) => write!(f, "event type {:?} not registered in the type map", type_tag),
+            DeserializationError::SchemaMismatch { type_tag, expected, actual } =>
+                write!(f, "schema fingerprint {} of event type {:?} doesn't match expected {}", actual, type_tag, expected),
+            DeserializationError::MalformedMessagePack(e) => write!(f, "malformed MessagePack event: {}", e),
+        }
+    }
+}
+
+impl Error for DeserializationError {}
+
+impl Display for PollError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Kafka(e) => write!(f, "polling failed: {}", e),
+            PollError::Deserialization(e) => write!(f, "deserialization failed: {}", e),
+        }
+    }
+}
+
+impl Error for PollError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PollError::Kafka(e) => Some(e),
+            PollError::Deserialization(e) => Some(e),
+        }
+    }
+}
+
+impl From<KafkaError> for PollError {
+    fn from(e: KafkaError) -> Self {
+        PollError::Kafka(e)
+    }
+}
+
 impl MessageChannelInternal {
     const fn new() -> Self {
         MessageChannelInternal {
             topic: "default",
-            partition: 0,
-            group_id: "default"
+            group_id: Cow::Borrowed(DEFAULT_GROUP_ID)
         }
     }
 
     fn from(message_channel: MessageChannel) -> Self {
         MessageChannelInternal {
             topic: message_channel.topic,
-            partition: message_channel.partition,
-            group_id: message_channel.group_id
+            group_id: Cow::Owned(message_channel.group_id)
         }
     }
 }
@@ -521,20 +3950,28 @@ impl MessageBrokerConfigurationInternal {
     const fn new() -> Self {
         MessageBrokerConfigurationInternal {
             message_channel: MessageChannelInternal::new(),
-            bootstrap_servers: "localhost:9092",
+            bootstrap_servers: Cow::Borrowed("localhost:9092"),
             topic_auto_create_enabled: false,
             auto_commit_enabled: true,
             timeout: 0,
+            quarantine_topic: None,
+            extra_properties: Vec::new(),
+            processing_deadline: None,
+            pause_on_deadline: false,
         }
     }
 
     fn from(configuration: MessageBrokerConfiguration) -> Self {
         MessageBrokerConfigurationInternal {
             message_channel: MessageChannelInternal::from(configuration.message_channel),
-            bootstrap_servers: configuration.bootstrap_servers,
+            bootstrap_servers: Cow::Owned(configuration.bootstrap_servers),
             topic_auto_create_enabled: configuration.topic_auto_create_enabled,
             auto_commit_enabled: configuration.auto_commit_enabled,
             timeout: configuration.timeout,
+            quarantine_topic: configuration.quarantine_topic,
+            extra_properties: configuration.extra_properties,
+            processing_deadline: configuration.processing_deadline,
+            pause_on_deadline: configuration.pause_on_deadline,
         }
     }
 
@@ -542,6 +3979,179 @@ impl MessageBrokerConfigurationInternal {
         self.message_channel = configuration.message_channel;
         self.bootstrap_servers = configuration.bootstrap_servers;
         self.topic_auto_create_enabled = configuration.topic_auto_create_enabled;
+        self.auto_commit_enabled = configuration.auto_commit_enabled;
         self.timeout = configuration.timeout;
+        self.quarantine_topic = configuration.quarantine_topic;
+        self.extra_properties = configuration.extra_properties;
+        self.processing_deadline = configuration.processing_deadline;
+        self.pause_on_deadline = configuration.pause_on_deadline;
+    }
+}
+
+impl InProgress {
+    fn new(message: &impl Message) -> Self {
+        InProgress {
+            topic: String::from(message.topic()),
+            partition: message.partition(),
+            offset: message.offset(),
+            started: Instant::now(),
+            overrun: false,
+        }
+    }
+}
+
+impl ChunkAssembler {
+    /// Collects the chunk (in any order), returning the reassembled message (carrying metadata of the last chunk
+    /// received) once all the chunks of the message arrived.
+    fn reassemble(&mut self, message: &impl Message, chunk: ChunkHeader) -> Option<OwnedMessage> {
+        self.sweep();
+        let key = (String::from(message.topic()), message.partition(), chunk.message_id);
+        let pending = self.pending.entry(key.clone()).or_insert_with(|| PendingChunks {
+            started: Instant::now(),
+            first_offset: message.offset(),
+            count: chunk.count,
+            chunks: BTreeMap::new(),
+        });
+        pending.first_offset = pending.first_offset.min(message.offset());
+        pending.chunks.insert(chunk.index, message.payload().unwrap_or_default().to_vec());
+        if pending.chunks.len() < pending.count {
+            return None;
+        }
+        let pending = self.pending.remove(&key)?;
+
+        let payload: Vec<u8> = pending.chunks.into_values().flatten().collect();
+        let headers = message.headers().map(|headers| headers.iter()
+            .fold(OwnedHeaders::new(), |owned, header| owned.insert(header)));
+        info!(target: &common::format_target("KafkaConsumer"), "chunked message {} of {}[{}] reassembled from {} chunks",
+            key.2, key.0, key.1, pending.count);
+        Some(OwnedMessage::new(Some(payload), message.key().map(<[u8]>::to_vec), key.0, message.timestamp(), key.1,
+            message.offset(), headers))
+    }
+
+    /// Drops the messages not reassembled within the reassembly timeout.
+    fn sweep(&mut self) {
+        let timeout = CHUNKING.lock().unwrap().map_or(DEFAULT_REASSEMBLY_TIMEOUT, |chunking| chunking.reassembly_timeout);
+        self.pending.retain(|(topic, partition, message_id), pending| {
+            let expired = pending.started.elapsed() > timeout;
+            if expired {
+                warn!(target: &common::format_target("KafkaConsumer"),
+                    "chunked message {} of {}[{}] dropped, {} of {} chunks received within {:?}",
+                    message_id, topic, partition, pending.chunks.len(), pending.count, timeout);
+            }
+            !expired
+        });
+    }
+
+    /// Returns offset of the partition to commit once the messages before the next offset are handled, held back by
+    /// the first chunk of the messages still being reassembled.
+    fn committable(&self, topic: &str, partition: i32, next_offset: i64) -> i64 {
+        self.pending.iter()
+            .filter(|((pending_topic, pending_partition, _), _)| pending_topic == topic && *pending_partition == partition)
+            .map(|(_, pending)| pending.first_offset)
+            .fold(next_offset, i64::min)
+    }
+
+    /// Returns offsets to commit (see `committable`) once the messages before the next offsets (by topic and
+    /// partition) are handled.
+    fn committable_offsets(&self, next_offsets: &BTreeMap<(String, i32), i64>) -> TopicPartitionList {
+        let mut offsets = TopicPartitionList::new();
+        for ((topic, partition), next_offset) in next_offsets {
+            let offset = Offset::Offset(self.committable(topic, *partition, *next_offset));
+            if let Err(e) = offsets.add_partition_offset(topic, *partition, offset) {
+                warn!(target: &common::format_target("KafkaConsumer"), "unable to commit offset of {}[{}]: {}", topic, partition, e);
+            }
+        }
+        offsets
+    }
+}
+
+impl LastSequences {
+    const fn new() -> Self {
+        LastSequences {
+            sequences: BTreeMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Records the last sequence of the key. Once the capacity is reached, the oldest keys are evicted.
+    fn record(&mut self, key: (String, i32, String), sequence: u64) {
+        if self.sequences.insert(key.clone(), sequence).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > SEQUENCE_KEYS_CAPACITY {
+            if let Some(evicted) = self.order.pop_front() {
+                self.sequences.remove(&evicted);
+            }
+        }
+    }
+}
+
+impl IdleWatch {
+    fn new(message_channel: &MessageChannel, callback: IdleCallback) -> Self {
+        let now = Instant::now();
+        IdleWatch {
+            message_channel: MessageChannel {
+                topic: message_channel.topic,
+                partition: message_channel.partition,
+                group_id: message_channel.group_id.clone(),
+            },
+            due: now + callback.idle_period,
+            callback,
+            last_message: now,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_message = Instant::now();
+        self.due = self.last_message + self.callback.idle_period;
+    }
+
+    /// Invokes the callback, if it's due, scheduling the next one an idle period later.
+    fn check(&mut self) {
+        if Instant::now() < self.due {
+            return;
+        }
+        let idle_for = self.last_message.elapsed();
+        warn!(target: &common::format_target("KafkaConsumer"), "no messages received from {} in group {} for {:?}",
+            self.message_channel, self.message_channel.group_id, idle_for);
+        self.due = Instant::now() + self.callback.idle_period;
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| (self.callback.on_idle)(&self.message_channel, idle_for)));
+    }
+}
+
+impl QuarantineProducer {
+    fn new(configuration: &MessageBrokerConfigurationInternal, topic: &str) -> Result<Self, KafkaError> {
+        let producer = client_config(configuration, &[("message.timeout.ms", &configuration.timeout.to_string())])
+            .create()?;
+        Ok(QuarantineProducer { producer, topic: String::from(topic) })
+    }
+
+    async fn send(&self, message: &impl Message, reason: &str) {
+        let headers = OwnedHeaders::new()
+            .insert(Header { key: "original-topic", value: Some(message.topic()) })
+            .insert(Header { key: "original-partition", value: Some(&message.partition().to_string()) })
+            .insert(Header { key: "original-offset", value: Some(&message.offset().to_string()) })
+            .insert(Header { key: "quarantine-reason", value: Some(reason) });
+        let payload = message.payload().unwrap_or_default();
+        let delivery_status = self.producer
+            .send::<Vec<u8>, _, _>(
+                FutureRecord::to(&self.topic).payload(payload).headers(headers),
+                Duration::from_secs(0),
+            )
+            .await;
+        match delivery_status {
+            Ok(_) => warn!(target: &common::format_target("KafkaConsumer"),
+                "message at offset {} of {}[{}] quarantined to the topic {}: {}",
+                message.offset(), message.topic(), message.partition(), self.topic, reason),
+            Err((e, _)) => error!(target: &common::format_target("KafkaConsumer"),
+                "unable to quarantine message at offset {} of {}[{}]: {}",
+                message.offset(), message.topic(), message.partition(), e),
+        }
+    }
+}
+
+impl Drop for PreviousHeaders {
+    fn drop(&mut self) {
+        CURRENT_HEADERS.with(|current| current.replace(self.0.take()));
     }
 }
@@ -0,0 +1,379 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+                 SerializeTupleStruct, SerializeTupleVariant, Serializer};
+use serde_json::Value;
+use crate::model::Event;
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Crate functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Computes schema fingerprint of the event: FNV-1a hash of the structure declared by its type (see
+/// `kafka::schema_fingerprint`), traced while serializing the event.
+pub(super) fn fingerprint(event: &dyn Event) -> String {
+    let mut structure = String::new();
+    if event.serialize(StructureSerializer { structure: &mut structure, root: true }).is_err() {
+        structure = String::from("?");
+    }
+    let hash = structure.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Serializer describing structure of the value instead of the value itself: field names and value kinds. Values
+/// whose structure depends on the data rather than the type (options, sequences, maps, enum variants) are described
+/// by their kind only, so that e.g. unset options and empty sequences count the same as the set and non-empty ones.
+/// The root map is described by its keys, as it's the event struct (serialized as map along with its type tag).
+struct StructureSerializer<'a> {
+    structure: &'a mut String,
+    root: bool,
+}
+
+/// Compound value being described: its elements (tuples) or fields (structs and the root map), or none (values
+/// described by their kind only).
+struct Compound<'a> {
+    structure: &'a mut String,
+    kind: CompoundKind,
+    elements: Vec<(String, String)>,
+    key: Option<String>,
+}
+
+enum CompoundKind {
+    Ignored,
+    Tuple,
+    Object,
+}
+
+#[derive(Debug)]
+struct StructureError(String);
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Private functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+fn describe(value: &(impl Serialize + ?Sized)) -> Result<String, StructureError> {
+    let mut structure = String::new();
+    value.serialize(StructureSerializer { structure: &mut structure, root: false })?;
+    Ok(structure)
+}
+
+fn key_name(key: &(impl Serialize + ?Sized)) -> Result<String, StructureError> {
+    match serde_json::to_value(key).map_err(|e| StructureError(e.to_string()))? {
+        Value::String(name) => Ok(name),
+        key => Ok(key.to_string()),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl<'a> StructureSerializer<'a> {
+    fn kind(self, kind: &str) -> Result<(), StructureError> {
+        self.structure.push_str(kind);
+        Ok(())
+    }
+
+    fn compound(self, kind: CompoundKind) -> Compound<'a> {
+        Compound { structure: self.structure, kind, elements: Vec::new(), key: None }
+    }
+
+    fn ignored(self, kind: &str) -> Compound<'a> {
+        self.structure.push_str(kind);
+        self.compound(CompoundKind::Ignored)
+    }
+}
+
+impl<'a> Serializer for StructureSerializer<'a> {
+    type Ok = ();
+    type Error = StructureError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), StructureError> {
+        self.kind("b")
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), StructureError> {
+        self.kind("n")
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), StructureError> {
+        self.kind("s")
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), StructureError> {
+        self.kind("s")
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), StructureError> {
+        self.kind("[]")
+    }
+
+    fn serialize_none(self) -> Result<(), StructureError> {
+        self.kind("o")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), StructureError> {
+        self.kind("o")
+    }
+
+    fn serialize_unit(self) -> Result<(), StructureError> {
+        self.kind("0")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), StructureError> {
+        self.kind("0")
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<(), StructureError> {
+        self.kind("e")
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), StructureError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, _variant: &'static str,
+                                                        _value: &T) -> Result<(), StructureError> {
+        self.kind("e")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, StructureError> {
+        Ok(self.ignored("[]"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, StructureError> {
+        Ok(self.compound(CompoundKind::Tuple))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, StructureError> {
+        Ok(self.compound(CompoundKind::Tuple))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+                               -> Result<Compound<'a>, StructureError> {
+        Ok(self.ignored("e"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, StructureError> {
+        match self.root {
+            true => Ok(self.compound(CompoundKind::Object)),
+            false => Ok(self.ignored("{}")),
+        }
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, StructureError> {
+        Ok(self.compound(CompoundKind::Object))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+                                -> Result<Compound<'a>, StructureError> {
+        Ok(self.ignored("e"))
+    }
+}
+
+impl Compound<'_> {
+    fn element(&mut self, name: String, value: &(impl Serialize + ?Sized)) -> Result<(), StructureError> {
+        if !matches!(self.kind, CompoundKind::Ignored) {
+            self.elements.push((name, describe(value)?));
+        }
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<(), StructureError> {
+        match self.kind {
+            CompoundKind::Ignored => {}
+            CompoundKind::Tuple => {
+                self.structure.push('(');
+                self.elements.iter().for_each(|(_, element)| {
+                    self.structure.push_str(element);
+                    self.structure.push(',');
+                });
+                self.structure.push(')');
+            }
+            CompoundKind::Object => {
+                self.elements.sort();
+                self.structure.push('{');
+                self.elements.iter().for_each(|(name, field)| {
+                    self.structure.push_str(name);
+                    self.structure.push(':');
+                    self.structure.push_str(field);
+                    self.structure.push(',');
+                });
+                self.structure.push('}');
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), StructureError> {
+        self.element(String::new(), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), StructureError> {
+        self.element(String::new(), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), StructureError> {
+        self.element(String::new(), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), StructureError> {
+        self.element(String::new(), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), StructureError> {
+        if !matches!(self.kind, CompoundKind::Ignored) {
+            self.key = Some(key_name(key)?);
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), StructureError> {
+        let key = self.key.take().unwrap_or_default();
+        self.element(key, value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), StructureError> {
+        self.element(String::from(key), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = StructureError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), StructureError> {
+        self.element(String::from(key), value)
+    }
+
+    fn end(self) -> Result<(), StructureError> {
+        Compound::end(self)
+    }
+}
+
+impl Display for StructureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for StructureError {}
+
+impl ser::Error for StructureError {
+    fn custom<T: Display>(message: T) -> Self {
+        StructureError(message.to_string())
+    }
+}
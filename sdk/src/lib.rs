@@ -13,5 +13,10 @@ pub mod model;
 pub mod in_memory;
 pub mod kafka;
 pub mod iggy;
-mod common;
+pub mod file_source;
+pub mod common;
+mod shutdown;
+
+pub use self::shutdown::ShutdownReport;
+pub use self::shutdown::shutdown_all;
 
@@ -5,8 +5,12 @@
 //! Core abstractions shared amongst different implementations/integrations.
 
 use std::any::Any;
-use std::fmt::Display;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::SystemTime;
 use mopa::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 // -----------------------------------------------------------------------------------------------------------------------------------------
 // Public traits
@@ -58,6 +62,22 @@ pub trait Event: Display + mopa::Any {
     fn name(&self) -> &str;
     fn as_any(&self) -> &dyn Any;
     fn to_json(&self) -> String;
+
+    /// Time the event occurred at, if known. By default, events carry no timestamp.
+    fn occurred_at(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Sequence number of the event within its partition key, if assigned by the producer. By default, events carry
+    /// no sequence number.
+    fn sequence(&self) -> Option<u64> {
+        None
+    }
+
+    /// Key the event sequence is numbered within (e.g. aggregate id). By default, events share a single sequence.
+    fn partition_key(&self) -> Option<&str> {
+        None
+    }
 }
 
 mopafy!(Event);
@@ -113,4 +133,155 @@ mopafy!(Event);
 pub trait EventHandler: Display {
     fn handle(&self, event: &dyn Event);
     fn id(&self) -> String;
+
+    /// Handles event, reporting failure to the caller. By default, it delegates to `handle` and always succeeds.
+    fn try_handle(&self, event: &dyn Event) -> Result<(), HandlerError> {
+        self.handle(event);
+        Ok(())
+    }
+
+    /// Handles batch of events at once (used by batched consumers). By default, it handles events one by one.
+    fn handle_batch(&self, events: &[Box<dyn Event>]) {
+        events.iter().for_each(|event| self.handle(event.as_ref()));
+    }
+}
+
+/// Stage of the event handler pipeline (see `in_memory::register_pipeline`), transforming the event for the next
+/// stage: returns the modified event, or `None` to pass the received one on unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use std::fmt::{Display, Formatter};
+/// use eventure::model;
+///
+/// struct AuditStage;
+///
+/// impl Display for AuditStage {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "AuditStage")
+///     }
+/// }
+///
+/// impl model::TransformHandler for AuditStage {
+///     fn transform(&self, event: &dyn model::Event) -> Option<Box<dyn model::Event>> {
+///         println!("auditing {}", event);
+///         None
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("AuditStage")
+///     }
+/// }
+/// ```
+pub trait TransformHandler: Display {
+    fn transform(&self, event: &dyn Event) -> Option<Box<dyn Event>>;
+    fn id(&self) -> String;
+}
+
+/// Compact binary (bincode) encoding of concrete event types, for durable paths (file logs, write-ahead logs)
+/// where JSON is too slow or too big. Implemented for every serializable event.
+///
+/// Unlike JSON, bincode isn't self-describing: bytes carry neither field names nor the event type, so they can only
+/// be decoded into the very same type (same schema) they were encoded from, and `dyn Event` can't be decoded at all.
+///
+/// # Examples
+///
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use serde::{Deserialize, Serialize};
+/// use eventure::model::{self, BincodeEvent};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// pub struct OrderCreated {
+///     event_id: String,
+///     customer_id: String,
+///     amount: u64,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// let order_created = OrderCreated {
+///     event_id: String::from("event_id"),
+///     customer_id: String::from("customer_id"),
+///     amount: 1250,
+/// };
+///
+/// let bytes = order_created.to_bincode().unwrap();
+/// assert_eq!(OrderCreated::from_bincode(&bytes).unwrap(), order_created);
+/// assert!(bytes.len() < model::Event::to_json(&order_created).len());
+/// ```
+pub trait BincodeEvent: Sized {
+    fn to_bincode(&self) -> Result<Vec<u8>, bincode::Error>;
+    fn from_bincode(bytes: &[u8]) -> Result<Self, bincode::Error>;
 }
+
+impl<E: Event + Serialize + DeserializeOwned> BincodeEvent for E {
+    fn to_bincode(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    fn from_bincode(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Error reported by event handler, when event couldn't be handled.
+///
+/// # Examples
+///
+/// ```
+/// use eventure::model;
+///
+/// let error = model::HandlerError::new("customer not found");
+/// assert_eq!(error.to_string(), "customer not found");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerError {
+    pub message: String,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl HandlerError {
+    pub fn new(message: impl Into<String>) -> Self {
+        HandlerError { message: message.into() }
+    }
+}
+
+impl Display for HandlerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for HandlerError {}
@@ -0,0 +1,178 @@
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Rust-Lang Libs/Eventure 2024
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+use std::fmt::{Display, Formatter};
+use log::info;
+use rdkafka::error::KafkaError;
+use crate::{common, file_source, in_memory, kafka};
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public structs
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Aggregate report of `shutdown_all`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Number of In-Memory events queued (async mode) at shutdown, dispatched before returning.
+    pub in_memory_drained: usize,
+    /// Number of unregistered In-Memory event handlers.
+    pub in_memory_unregistered: usize,
+    /// Number of stopped Kafka consumers.
+    pub kafka_consumers_stopped: usize,
+    /// Result of flushing the Kafka producer (`None`, if no Kafka event was emitted since setup).
+    pub kafka_producer_flushed: Option<Result<(), KafkaError>>,
+    /// Whether the file source directory watcher was running and got stopped.
+    pub file_source_watcher_stopped: bool,
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Public functions
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+/// Shuts all the message brokers down, for clean process termination: stops the file source directory watcher (see
+/// `file_source::shutdown`) and all Kafka consumers (see `kafka::shutdown`), flushes and drops the Kafka producer, clears the registered Kafka event types, expected
+/// schemas, dedup store and last sequences, then drains In-Memory async queue and unregisters all In-Memory event
+/// handlers (see `in_memory::shutdown`).
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::fmt::{Display, Formatter};
+/// use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+/// use std::thread;
+/// use std::time::Duration;
+/// use rdkafka::mocking::MockCluster;
+/// use serde::{Deserialize, Serialize};
+/// use eventure::{in_memory, kafka, model, ShutdownReport};
+///
+/// static STARTED: AtomicBool = AtomicBool::new(false);
+/// static RELEASED: AtomicBool = AtomicBool::new(false);
+/// static HANDLED: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderCreated {
+///     event_id: String,
+/// }
+///
+/// impl Display for OrderCreated {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{} event with id {}",
+///                "OrderCreated", self.event_id)
+///     }
+/// }
+///
+/// #[typetag::serde]
+/// impl model::Event for OrderCreated {
+///     fn id(&self) -> &str {
+///         &self.event_id[..]
+///     }
+///     fn name(&self) -> &str {
+///         "OrderCreated"
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+///     fn to_json(&self) -> String {
+///         let event = self as &dyn model::Event;
+///         serde_json::to_string(&event).unwrap()
+///     }
+/// }
+///
+/// struct OrderEventHandler;
+///
+/// impl Display for OrderEventHandler {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{}", "OrderEventHandler")
+///     }
+/// }
+///
+/// impl model::EventHandler for OrderEventHandler {
+///     fn handle(&self, event: &(dyn model::Event + '_)) {
+///         if event.id() == "gate" {
+///             // holds the worker, until the other events are queued
+///             STARTED.store(true, Ordering::SeqCst);
+///             while !RELEASED.load(Ordering::SeqCst) {
+///                 thread::sleep(Duration::from_millis(10));
+///             }
+///         }
+///         // the Kafka event (possibly consumed meanwhile) isn't counted
+///         if event.id() != "kafka" {
+///             HANDLED.fetch_add(1, Ordering::SeqCst);
+///         }
+///     }
+///
+///     fn id(&self) -> String {
+///         String::from("OrderEventHandler")
+///     }
+/// }
+///
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("orders", 1, 1).unwrap();
+/// let mut configuration = kafka::configuration("orders", 0);
+/// configuration.bootstrap_servers = cluster.bootstrap_servers();
+/// kafka::setup(configuration);
+/// kafka::register(kafka::message_channel("orders", 0, "consumer_group"), OrderEventHandler).unwrap();
+/// kafka::emit(&OrderCreated { event_id: String::from("kafka") });
+///
+/// in_memory::setup(in_memory::configuration(in_memory::ChannelType::TOPIC, ".*", true)).unwrap();
+/// in_memory::register(in_memory::message_channel(in_memory::ChannelType::TOPIC, "Orders"), OrderEventHandler);
+/// in_memory::emit(&OrderCreated { event_id: String::from("gate") });
+/// while !STARTED.load(Ordering::SeqCst) {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// for index in 0..3 {
+///     in_memory::emit(&OrderCreated { event_id: index.to_string() });
+/// }
+/// // released while shutting down
+/// thread::spawn(|| {
+///     thread::sleep(Duration::from_millis(500));
+///     RELEASED.store(true, Ordering::SeqCst);
+/// });
+///
+/// let report = eventure::shutdown_all();
+/// assert_eq!(report, ShutdownReport {
+///     in_memory_drained: 3,
+///     in_memory_unregistered: 1,
+///     kafka_consumers_stopped: 1,
+///     kafka_producer_flushed: Some(Ok(())),
+///     file_source_watcher_stopped: false,
+/// });
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 4);
+///
+/// // nothing is registered anymore
+/// in_memory::emit(&OrderCreated { event_id: String::from("4") });
+/// assert_eq!(HANDLED.load(Ordering::SeqCst), 4);
+/// ```
+pub fn shutdown_all() -> ShutdownReport {
+    let file_source_watcher_stopped = file_source::shutdown();
+    let (kafka_consumers_stopped, kafka_producer_flushed) = kafka::shutdown_flushing();
+    let (in_memory_drained, in_memory_unregistered) = in_memory::shutdown_counting();
+    let report = ShutdownReport {
+        in_memory_drained,
+        in_memory_unregistered,
+        kafka_consumers_stopped,
+        kafka_producer_flushed,
+        file_source_watcher_stopped,
+    };
+    info!(target: &common::format_target("Eventure"), "shut down: {}", report);
+    report
+}
+
+// -----------------------------------------------------------------------------------------------------------------------------------------
+// Implementation
+// -----------------------------------------------------------------------------------------------------------------------------------------
+
+impl Display for ShutdownReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kafka_producer_flushed = match &self.kafka_producer_flushed {
+            Some(Ok(())) => "yes",
+            Some(Err(_)) => "failed",
+            None => "none",
+        };
+        write!(f, "[in-memory-drained:{},in-memory-unregistered:{},kafka-consumers-stopped:{},kafka-producer-flushed:{},\
+                   file-source-watcher-stopped:{}]",
+               self.in_memory_drained, self.in_memory_unregistered, self.kafka_consumers_stopped, kafka_producer_flushed,
+               self.file_source_watcher_stopped)
+    }
+}